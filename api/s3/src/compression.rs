@@ -0,0 +1,148 @@
+//! Compression of buckets.
+//!
+//! S3 has no API for compression of stored objects, so the [`Compression`] is
+//! configured via the `?compression` subresource of a bucket, in the same
+//! manner as other bucket configurations are.
+
+use std::fmt;
+
+use baza::{Compression, Exec, GetBucketConfig, RelativePath, SetBucketConfig};
+use hyper::{Body, Response, StatusCode};
+use s3_server::errors::S3ErrorCode;
+use serde::{Deserialize, Serialize};
+
+use crate::service::{self, Error};
+
+/// The only supported compression algorithm.
+const ALGORITHM: &str = "zstd";
+
+/// XML representation of a bucket [`Compression`].
+#[derive(Debug, Deserialize, Serialize)]
+struct CompressionConfiguration {
+    /// Algorithm to compress objects with.
+    #[serde(rename = "Algorithm", default = "default_algorithm")]
+    algorithm: String,
+
+    /// Level of the compression.
+    #[serde(rename = "Level", skip_serializing_if = "Option::is_none")]
+    level: Option<i32>,
+}
+
+/// Returns the default [`CompressionConfiguration::algorithm`].
+fn default_algorithm() -> String {
+    ALGORITHM.into()
+}
+
+/// Handles the `PutBucketCompression` operation.
+///
+/// # Errors
+///
+/// - If the provided `body` is not a valid [`CompressionConfiguration`].
+/// - If the [`SetBucketConfig`] operation fails.
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn put_bucket_compression<S>(
+    storage: &S,
+    bucket: RelativePath,
+    body: &[u8],
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<Compression>, Err: fmt::Display>,
+{
+    let config = service::parse_xml::<CompressionConfiguration>(body)?;
+    if !config.algorithm.eq_ignore_ascii_case(ALGORITHM) {
+        return Err(Error::new(
+            S3ErrorCode::InvalidArgument,
+            format!(
+                "Compression algorithm `{}` is not supported",
+                config.algorithm,
+            ),
+        ));
+    }
+    let compression = Compression {
+        level: config.level.unwrap_or(Compression::default().level),
+    };
+    if !Compression::LEVELS.contains(&compression.level) {
+        return Err(Error::new(
+            S3ErrorCode::InvalidArgument,
+            format!(
+                "Compression level must be in {}..={} range",
+                Compression::LEVELS.start(),
+                Compression::LEVELS.end(),
+            ),
+        ));
+    }
+
+    storage
+        .exec(SetBucketConfig { bucket, config: Some(compression) })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    Ok(Response::new(Body::empty()))
+}
+
+/// Handles the `GetBucketCompression` operation.
+///
+/// # Errors
+///
+/// - If the bucket has no [`Compression`] configured.
+/// - If the [`GetBucketConfig`] operation fails.
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn get_bucket_compression<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            GetBucketConfig<Compression>,
+            Ok = Option<Compression>,
+            Err: fmt::Display,
+        >,
+{
+    let compression = storage
+        .exec(GetBucketConfig::new(bucket))
+        .await
+        .map_err(|e| Error::internal("GetBucketConfig", e))?
+        .ok_or_else(|| {
+            Error::custom(
+                "NoSuchCompressionConfiguration",
+                StatusCode::NOT_FOUND,
+                "The compression configuration does not exist",
+            )
+        })?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "CompressionConfiguration",
+        &CompressionConfiguration {
+            algorithm: ALGORITHM.into(),
+            level: Some(compression.level),
+        },
+    )
+}
+
+/// Handles the `DeleteBucketCompression` operation.
+///
+/// Already compressed objects remain compressed.
+///
+/// # Errors
+///
+/// If the [`SetBucketConfig`] operation fails.
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn delete_bucket_compression<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<Compression>, Err: fmt::Display>,
+{
+    storage
+        .exec(SetBucketConfig::<Compression> { bucket, config: None })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = StatusCode::NO_CONTENT;
+    Ok(resp)
+}
@@ -0,0 +1,489 @@
+//! [CORS] configuration of buckets.
+//!
+//! [CORS]: https://developer.mozilla.org/en-US/docs/Web/HTTP/CORS
+
+use baza::{
+    BucketConfig, Exec, GetBucketConfig, RelativePath, SetBucketConfig,
+};
+use hyper::{
+    Body, Method, Request, Response, StatusCode,
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+};
+use s3_server::errors::S3ErrorCode;
+use serde::{Deserialize, Serialize};
+
+use crate::service::{self, Error};
+
+/// Maximum number of [`CorsRule`]s in a [`CorsConfiguration`].
+const MAX_RULES: usize = 100;
+
+/// HTTP methods allowed to be specified in [`CorsRule::allowed_methods`].
+const METHODS: [&str; 5] = ["GET", "PUT", "POST", "DELETE", "HEAD"];
+
+/// [CORS] configuration of a bucket.
+///
+/// [CORS]: https://developer.mozilla.org/en-US/docs/Web/HTTP/CORS
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CorsConfiguration {
+    /// [`CorsRule`]s of this [`CorsConfiguration`], evaluated in order.
+    #[serde(rename = "CORSRule", default)]
+    pub rules: Vec<CorsRule>,
+}
+
+impl BucketConfig for CorsConfiguration {
+    const NAME: &'static str = "cors";
+}
+
+impl CorsConfiguration {
+    /// Validates this [`CorsConfiguration`].
+    ///
+    /// # Errors
+    ///
+    /// If this [`CorsConfiguration`] contains no [`CorsRule`]s, too many of
+    /// them, or any of them is invalid.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let invalid = |msg: &str| Error::new(S3ErrorCode::MalformedXML, msg);
+
+        if self.rules.is_empty() {
+            return Err(invalid("At least one CORSRule must be specified"));
+        }
+        if self.rules.len() > MAX_RULES {
+            return Err(invalid("Too many CORSRules specified"));
+        }
+        for rule in &self.rules {
+            if rule.allowed_origins.is_empty()
+                || rule.allowed_methods.is_empty()
+            {
+                return Err(invalid(
+                    "CORSRule must contain AllowedOrigin and AllowedMethod",
+                ));
+            }
+            if let Some(m) = rule
+                .allowed_methods
+                .iter()
+                .find(|m| !METHODS.contains(&m.as_str()))
+            {
+                return Err(Error::new(
+                    S3ErrorCode::InvalidRequest,
+                    format!(
+                        "Found unsupported HTTP method in CORS config: {m}"
+                    ),
+                ));
+            }
+            if let Some(w) = rule
+                .allowed_origins
+                .iter()
+                .chain(&rule.allowed_headers)
+                .find(|w| w.matches('*').count() > 1)
+            {
+                return Err(Error::new(
+                    S3ErrorCode::InvalidRequest,
+                    format!("Only one wildcard is allowed in CORS config: {w}"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the first [`CorsRule`] allowing a request from the provided
+    /// `origin` with the provided `method` and `headers`.
+    #[must_use]
+    pub(crate) fn find_rule(
+        &self,
+        origin: &str,
+        method: &str,
+        headers: &[&str],
+    ) -> Option<&CorsRule> {
+        self.rules.iter().find(|rule| {
+            rule.allowed_origins.iter().any(|o| wildcard_match(o, origin))
+                && rule.allowed_methods.iter().any(|m| m == method)
+                && headers.iter().all(|h| {
+                    rule.allowed_headers.iter().any(|a| {
+                        wildcard_match(
+                            &a.to_ascii_lowercase(),
+                            &h.to_ascii_lowercase(),
+                        )
+                    })
+                })
+        })
+    }
+}
+
+/// Rule of a [`CorsConfiguration`].
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CorsRule {
+    /// Unique identifier of this [`CorsRule`].
+    #[serde(rename = "ID", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// Headers allowed in a preflight request.
+    #[serde(rename = "AllowedHeader", default)]
+    pub allowed_headers: Vec<String>,
+
+    /// HTTP methods allowed to be executed.
+    #[serde(rename = "AllowedMethod", default)]
+    pub allowed_methods: Vec<String>,
+
+    /// Origins allowed to access the bucket.
+    #[serde(rename = "AllowedOrigin", default)]
+    pub allowed_origins: Vec<String>,
+
+    /// Response headers accessible from client applications.
+    #[serde(rename = "ExposeHeader", default)]
+    pub expose_headers: Vec<String>,
+
+    /// Time in seconds a browser may cache a preflight response for.
+    #[serde(
+        rename = "MaxAgeSeconds",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_age_seconds: Option<u32>,
+}
+
+impl CorsRule {
+    /// Inserts the [CORS] headers allowing a request from the provided
+    /// `origin` by this [`CorsRule`] into the provided [`HeaderMap`].
+    ///
+    /// [CORS]: https://developer.mozilla.org/en-US/docs/Web/HTTP/CORS
+    fn apply(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        let mut insert = |name: HeaderName, value: &str| {
+            if let Ok(value) = HeaderValue::from_str(value) {
+                _ = headers.insert(name, value);
+            }
+        };
+
+        if self.allowed_origins.iter().any(|o| o == "*") {
+            insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+        } else {
+            insert(
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                origin.to_str().unwrap_or_default(),
+            );
+            insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        }
+        insert(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            &self.allowed_methods.join(", "),
+        );
+        if !self.expose_headers.is_empty() {
+            insert(
+                header::ACCESS_CONTROL_EXPOSE_HEADERS,
+                &self.expose_headers.join(", "),
+            );
+        }
+        if let Some(max_age) = self.max_age_seconds {
+            insert(header::ACCESS_CONTROL_MAX_AGE, &max_age.to_string());
+        }
+        insert(
+            header::VARY,
+            "Origin, Access-Control-Request-Headers, \
+             Access-Control-Request-Method",
+        );
+    }
+}
+
+/// Handles the provided [preflight request][0] to the provided `bucket`.
+///
+/// # Errors
+///
+/// - If the request misses its `Origin` or `Access-Control-Request-Method`.
+/// - If the `bucket` has no [`CorsConfiguration`].
+/// - If the request is not allowed by the [`CorsConfiguration`].
+///
+/// [0]: https://developer.mozilla.org/en-US/docs/Glossary/Preflight_request
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn preflight<S>(
+    storage: &S,
+    bucket: RelativePath,
+    req: &Request<Body>,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            GetBucketConfig<CorsConfiguration>,
+            Ok = Option<CorsConfiguration>,
+            Err: std::fmt::Display,
+        >,
+{
+    let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let (Some(origin), Some(method)) = (
+        req.headers().get(header::ORIGIN),
+        header(header::ACCESS_CONTROL_REQUEST_METHOD),
+    ) else {
+        return Err(Error::new(
+            S3ErrorCode::InvalidRequest,
+            "Insufficient information. Origin request header needed.",
+        ));
+    };
+    let requested_headers = header(header::ACCESS_CONTROL_REQUEST_HEADERS)
+        .map(|h| {
+            h.split(',').map(str::trim).filter(|h| !h.is_empty()).collect()
+        })
+        .unwrap_or_else(Vec::new);
+
+    let forbidden = |msg: &str| {
+        Error::new(S3ErrorCode::AccessDenied, format!("CORSResponse: {msg}"))
+    };
+    let config = storage
+        .exec(GetBucketConfig::new(bucket))
+        .await
+        .map_err(|e| Error::internal("GetBucketConfig", e))?
+        .ok_or_else(|| forbidden("CORS is not enabled for this bucket."))?;
+    let rule = config
+        .find_rule(
+            origin.to_str().unwrap_or_default(),
+            method,
+            &requested_headers,
+        )
+        .ok_or_else(|| forbidden("This CORS request is not allowed."))?;
+
+    let mut resp = Response::new(Body::empty());
+    rule.apply(origin, resp.headers_mut());
+    if let Some(h) = req.headers().get(header::ACCESS_CONTROL_REQUEST_HEADERS) {
+        _ = resp
+            .headers_mut()
+            .insert(header::ACCESS_CONTROL_ALLOW_HEADERS, h.clone());
+    }
+
+    tracing::info!("OK");
+    Ok(resp)
+}
+
+/// Inserts the [CORS] headers into the provided [`Response`] to an actual
+/// request with the provided `method` from the provided `origin` to the
+/// provided `bucket`, if its [`CorsConfiguration`] allows it.
+///
+/// [CORS]: https://developer.mozilla.org/en-US/docs/Web/HTTP/CORS
+pub(crate) async fn apply<S>(
+    storage: &S,
+    bucket: RelativePath,
+    origin: &HeaderValue,
+    method: &Method,
+    resp: &mut Response<Body>,
+) where
+    S: Exec<
+            GetBucketConfig<CorsConfiguration>,
+            Ok = Option<CorsConfiguration>,
+            Err: std::fmt::Display,
+        >,
+{
+    let config = match storage.exec(GetBucketConfig::new(bucket)).await {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("GetBucketConfig operation failed: {e}");
+            return;
+        }
+    };
+    if let Some(rule) = config.as_ref().and_then(|c| {
+        c.find_rule(origin.to_str().unwrap_or_default(), method.as_str(), &[])
+    }) {
+        rule.apply(origin, resp.headers_mut());
+    }
+}
+
+/// Handles the [PutBucketCors][0] operation.
+///
+/// # Errors
+///
+/// - If the provided `body` is not a valid [`CorsConfiguration`].
+/// - If the [`SetBucketConfig`] operation fails.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn put_bucket_cors<S>(
+    storage: &S,
+    bucket: RelativePath,
+    body: &[u8],
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<CorsConfiguration>, Err: std::fmt::Display>,
+{
+    let config = service::parse_xml::<CorsConfiguration>(body)?;
+    config.validate()?;
+
+    storage
+        .exec(SetBucketConfig { bucket, config: Some(config) })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    Ok(Response::new(Body::empty()))
+}
+
+/// Handles the [GetBucketCors][0] operation.
+///
+/// # Errors
+///
+/// - If the `bucket` has no [`CorsConfiguration`].
+/// - If the [`GetBucketConfig`] operation fails.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn get_bucket_cors<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            GetBucketConfig<CorsConfiguration>,
+            Ok = Option<CorsConfiguration>,
+            Err: std::fmt::Display,
+        >,
+{
+    let config = storage
+        .exec(GetBucketConfig::new(bucket))
+        .await
+        .map_err(|e| Error::internal("GetBucketConfig", e))?
+        .ok_or_else(|| {
+            Error::custom(
+                "NoSuchCORSConfiguration",
+                StatusCode::NOT_FOUND,
+                "The CORS configuration does not exist",
+            )
+        })?;
+
+    tracing::info!("OK");
+    service::xml_response("CORSConfiguration", &config)
+}
+
+/// Handles the [DeleteBucketCors][0] operation.
+///
+/// # Errors
+///
+/// If the [`SetBucketConfig`] operation fails.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn delete_bucket_cors<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<CorsConfiguration>, Err: std::fmt::Display>,
+{
+    storage
+        .exec(SetBucketConfig::<CorsConfiguration> { bucket, config: None })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = StatusCode::NO_CONTENT;
+    Ok(resp)
+}
+
+/// Checks whether the provided `value` matches the provided `pattern`,
+/// containing at most one `*` wildcard.
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            value.len() >= prefix.len() + suffix.len()
+                && value.starts_with(prefix)
+                && value.ends_with(suffix)
+        }
+        None => pattern == value,
+    }
+}
+
+#[cfg(test)]
+mod spec {
+    use super::{CorsConfiguration, CorsRule, wildcard_match};
+
+    fn config() -> CorsConfiguration {
+        CorsConfiguration {
+            rules: vec![
+                CorsRule {
+                    allowed_origins: vec!["https://*.example.com".into()],
+                    allowed_methods: vec!["GET".into(), "PUT".into()],
+                    allowed_headers: vec![
+                        "x-amz-*".into(),
+                        "Content-Type".into(),
+                    ],
+                    ..CorsRule::default()
+                },
+                CorsRule {
+                    id: Some("public".into()),
+                    allowed_origins: vec!["*".into()],
+                    allowed_methods: vec!["GET".into()],
+                    ..CorsRule::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("*", "https://a.com"));
+        assert!(wildcard_match("https://*.a.com", "https://b.a.com"));
+        assert!(!wildcard_match("https://*.a.com", "https://.a.com.b"));
+        assert!(!wildcard_match("ab*ba", "aba"));
+        assert!(wildcard_match("exact", "exact"));
+        assert!(!wildcard_match("exact", "exactly"));
+    }
+
+    #[test]
+    fn finds_first_matching_rule() {
+        let config = config();
+
+        let rule = config.find_rule("https://app.example.com", "PUT", &[]);
+        assert_eq!(rule, Some(&config.rules[0]));
+
+        let rule = config.find_rule(
+            "https://app.example.com",
+            "PUT",
+            &["X-Amz-Date", "content-type"],
+        );
+        assert_eq!(rule, Some(&config.rules[0]));
+
+        let rule = config.find_rule("https://app.example.com", "GET", &["x"]);
+        assert_eq!(rule, None);
+
+        let rule = config.find_rule("https://other.com", "GET", &[]);
+        assert_eq!(rule, Some(&config.rules[1]));
+
+        assert_eq!(config.find_rule("https://other.com", "PUT", &[]), None);
+    }
+
+    #[test]
+    fn validates() {
+        assert!(config().validate().is_ok());
+        assert!(CorsConfiguration::default().validate().is_err());
+
+        let mut no_methods = config();
+        no_methods.rules[0].allowed_methods.clear();
+        assert!(no_methods.validate().is_err());
+
+        let mut wrong_method = config();
+        wrong_method.rules[0].allowed_methods.push("PATCH".into());
+        assert!(wrong_method.validate().is_err());
+
+        let mut many_wildcards = config();
+        many_wildcards.rules[0].allowed_origins.push("*.a.*".into());
+        assert!(many_wildcards.validate().is_err());
+    }
+
+    #[test]
+    fn parses_xml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <CORSConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+              <CORSRule>
+                <AllowedHeader>x-amz-*</AllowedHeader>
+                <AllowedMethod>GET</AllowedMethod>
+                <AllowedHeader>Content-Type</AllowedHeader>
+                <AllowedMethod>PUT</AllowedMethod>
+                <AllowedOrigin>https://*.example.com</AllowedOrigin>
+              </CORSRule>
+              <CORSRule>
+                <ID>public</ID>
+                <AllowedMethod>GET</AllowedMethod>
+                <AllowedOrigin>*</AllowedOrigin>
+              </CORSRule>
+            </CORSConfiguration>"#;
+
+        assert_eq!(
+            quick_xml::de::from_str::<CorsConfiguration>(xml).unwrap(),
+            config(),
+        );
+    }
+}
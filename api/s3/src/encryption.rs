@@ -0,0 +1,159 @@
+//! Server-side encryption of objects.
+
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use baza::{Encryption, EncryptionKey};
+use md5::{Digest as _, Md5};
+use s3_server::errors::{S3Error, S3ErrorCode};
+
+/// The only supported encryption algorithm.
+const ALGORITHM: &str = "AES256";
+
+/// Parses the [`EncryptionKey`] of a customer out of the provided SSE-C
+/// request headers.
+///
+/// # Errors
+///
+/// If the headers are incomplete, or specify an unsupported algorithm, or
+/// the key doesn't match its MD5 digest.
+pub(crate) fn customer_key(
+    algorithm: Option<String>,
+    key: Option<String>,
+    key_md5: Option<String>,
+) -> Result<Option<EncryptionKey>, S3Error> {
+    let (algorithm, key, key_md5) = match (algorithm, key, key_md5) {
+        (None, None, None) => return Ok(None),
+        (Some(a), Some(k), Some(m)) => (a, k, m),
+        _ => {
+            return Err(S3Error::new(
+                S3ErrorCode::InvalidArgument,
+                "Requests specifying Server Side Encryption with Customer \
+                 provided keys must provide algorithm, key and its MD5",
+            ));
+        }
+    };
+    if algorithm != ALGORITHM {
+        return Err(S3Error::new(
+            S3ErrorCode::InvalidEncryptionAlgorithmError,
+            format!("The encryption algorithm `{algorithm}` is not supported"),
+        ));
+    }
+
+    let key = BASE64_STANDARD
+        .decode(key)
+        .ok()
+        .and_then(|bytes| EncryptionKey::try_from(bytes.as_slice()).ok())
+        .ok_or_else(|| {
+            S3Error::new(
+                S3ErrorCode::InvalidArgument,
+                "The secret key must be a base64-encoded 256-bit key",
+            )
+        })?;
+    if key_digest(&key) != key_md5 {
+        return Err(S3Error::new(
+            S3ErrorCode::InvalidArgument,
+            "The calculated MD5 hash of the key did not match the hash that \
+             was provided",
+        ));
+    }
+    Ok(Some(key))
+}
+
+/// Parses the [`Encryption`] of a new object out of the provided
+/// `x-amz-server-side-encryption` header and the [`customer_key`].
+///
+/// # Errors
+///
+/// If an unsupported algorithm is requested, or both SSE-S3 and SSE-C are
+/// requested at once.
+pub(crate) fn parse(
+    server_side_encryption: Option<String>,
+    customer_key: Option<EncryptionKey>,
+) -> Result<Option<Encryption>, S3Error> {
+    match (server_side_encryption, customer_key) {
+        (None, None) => Ok(None),
+        (None, Some(key)) => Ok(Some(Encryption::Customer(key))),
+        (Some(sse), None) if sse == ALGORITHM => Ok(Some(Encryption::Managed)),
+        (Some(sse), None) => Err(S3Error::new(
+            S3ErrorCode::InvalidArgument,
+            format!("Server-side encryption `{sse}` is not supported"),
+        )),
+        (Some(_), Some(_)) => Err(S3Error::new(
+            S3ErrorCode::InvalidArgument,
+            "Server Side Encryption with Customer provided keys cannot be \
+             combined with another Server Side Encryption",
+        )),
+    }
+}
+
+/// Response headers describing the [`Encryption`] of an object.
+#[derive(Debug, Default)]
+pub(crate) struct Headers {
+    /// `x-amz-server-side-encryption` header.
+    pub(crate) server_side_encryption: Option<String>,
+
+    /// `x-amz-server-side-encryption-customer-algorithm` header.
+    pub(crate) sse_customer_algorithm: Option<String>,
+
+    /// `x-amz-server-side-encryption-customer-key-MD5` header.
+    pub(crate) sse_customer_key_md5: Option<String>,
+}
+
+impl Headers {
+    /// Creates new [`Headers`] describing the provided [`Encryption`].
+    #[must_use]
+    pub(crate) fn new(encryption: Option<&Encryption>) -> Self {
+        match encryption {
+            None => Self::default(),
+            Some(Encryption::Managed) => Self {
+                server_side_encryption: Some(ALGORITHM.into()),
+                ..Self::default()
+            },
+            Some(Encryption::Customer(key)) => Self {
+                sse_customer_algorithm: Some(ALGORITHM.into()),
+                sse_customer_key_md5: Some(key_digest(key)),
+                ..Self::default()
+            },
+        }
+    }
+}
+
+/// Returns the base64-encoded MD5 digest of the provided [`EncryptionKey`].
+fn key_digest(key: &EncryptionKey) -> String {
+    BASE64_STANDARD.encode(Md5::digest(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod customer_key_spec {
+    use base64::{Engine as _, prelude::BASE64_STANDARD};
+    use md5::{Digest as _, Md5};
+
+    use super::customer_key;
+
+    fn case(
+        algorithm: &str,
+        key: &[u8],
+        md5_of: &[u8],
+    ) -> Result<bool, String> {
+        customer_key(
+            Some(algorithm.into()),
+            Some(BASE64_STANDARD.encode(key)),
+            Some(BASE64_STANDARD.encode(Md5::digest(md5_of))),
+        )
+        .map(|k| k.is_some())
+        .map_err(|e| format!("{e:?}"))
+    }
+
+    #[test]
+    fn accepts_valid_key() {
+        assert_eq!(case("AES256", &[1; 32], &[1; 32]), Ok(true));
+        assert!(matches!(customer_key(None, None, None), Ok(None)));
+    }
+
+    #[test]
+    fn rejects_invalid_key() {
+        assert!(case("AES128", &[1; 32], &[1; 32]).is_err());
+        assert!(case("AES256", &[1; 16], &[1; 16]).is_err());
+        assert!(case("AES256", &[1; 32], &[2; 32]).is_err());
+        assert!(customer_key(Some("AES256".into()), None, None).is_err());
+    }
+}
@@ -0,0 +1,100 @@
+//! Integrity of stored objects.
+//!
+//! S3 has no API for integrity checks, so the objects detected as corrupt are
+//! listed via the `?corrupt` subresource of a bucket.
+
+use std::fmt;
+
+use baza::{CorruptFile, Exec, ListCorruptFiles, RelativePath};
+use hyper::{Body, Response, StatusCode};
+use serde::Serialize;
+
+use crate::{
+    service::{self, Error},
+    versioning::format_time,
+};
+
+/// XML representation of a [`ListCorruptFiles`] result.
+#[derive(Debug, Serialize)]
+struct ListCorruptObjectsResult {
+    /// Listed corrupt objects.
+    #[serde(rename = "Object")]
+    objects: Vec<CorruptObject>,
+}
+
+/// XML representation of a single [`CorruptFile`].
+#[derive(Debug, Serialize)]
+struct CorruptObject {
+    /// Key of the object.
+    #[serde(rename = "Key")]
+    key: String,
+
+    /// Hex-encoded SHA-256 checksum persisted when the object was created.
+    #[serde(rename = "ExpectedChecksum")]
+    expected: String,
+
+    /// Hex-encoded SHA-256 checksum of the actually stored contents.
+    #[serde(rename = "ActualChecksum")]
+    actual: String,
+
+    /// Time when the corruption was detected first.
+    #[serde(rename = "DetectedAt")]
+    detected_at: String,
+
+    /// Indicator whether the object is quarantined, so cannot be read.
+    #[serde(rename = "Quarantined")]
+    quarantined: bool,
+}
+
+impl From<CorruptFile> for CorruptObject {
+    fn from(f: CorruptFile) -> Self {
+        let key = f
+            .path
+            .as_ref()
+            .strip_prefix(f.path.bucket())
+            .unwrap_or(f.path.as_ref())
+            .to_string_lossy()
+            .into_owned();
+        Self {
+            key,
+            expected: f.expected,
+            actual: f.actual,
+            detected_at: format_time(f.detected_at),
+            quarantined: f.quarantined,
+        }
+    }
+}
+
+/// Creates a new [`Error`] of an object being refused to be read, because of
+/// its stored contents being corrupt.
+#[must_use]
+pub(crate) fn corrupt_error(message: impl Into<String>) -> Error {
+    Error::custom("ObjectCorrupted", StatusCode::INTERNAL_SERVER_ERROR, message)
+}
+
+/// Handles the `ListCorruptObjects` operation.
+///
+/// # Errors
+///
+/// If the [`ListCorruptFiles`] operation fails.
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn list_corrupt_objects<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<ListCorruptFiles, Ok = Vec<CorruptFile>, Err: fmt::Display>,
+{
+    let files = storage
+        .exec(ListCorruptFiles { bucket })
+        .await
+        .map_err(|e| Error::internal("ListCorruptFiles", e))?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "ListCorruptObjectsResult",
+        &ListCorruptObjectsResult {
+            objects: files.into_iter().map(Into::into).collect(),
+        },
+    )
+}
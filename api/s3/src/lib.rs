@@ -1,17 +1,45 @@
 //! S3 HTTP API implementation of Baza.
 
+mod compression;
+mod cors;
+mod encryption;
+mod integrity;
+mod lifecycle;
+mod links;
+mod object_lock;
+mod post_object;
+mod publish;
+mod quota;
+mod range;
+mod service;
+mod signature;
+mod space;
+mod versioning;
+
 use std::{
+    collections::HashMap,
     convert::Infallible,
+    error::Error as StdError,
     fmt, io,
     net::{TcpListener, ToSocketAddrs},
 };
 
 use baza::{
-    CreateFile, CreateSymlink, Exec, GetFile, ReadOnlyFile, RelativePath,
-    async_trait, futures::future,
+    AccessDeniedError, Compression, CorruptFile, CreateFile, CreateHardLink,
+    CreateSymlink, DeleteFile, DeletedFile, Exec, FileVersion, FreeSpace,
+    GetBucketConfig, GetBucketUsage, GetFile, GetFreeSpace, GetObjectLock,
+    GetQuotaAllowance, GetSymlink, GetSymlinkHistory, Lifecycle,
+    ListCorruptFiles, ListSymlinks, ListVersions, ObjectLock, ObjectLockStatus,
+    PreconditionError, PublishSymlinks, Quota, ReadOnlyFile, RelativePath,
+    RollbackSymlink, SetBucketConfig, SetObjectLegalHold, SetObjectRetention,
+    SymlinkHistoryEntry, Usage, VersionId, Versioning, async_trait,
+    futures::{AsyncReadExt as _, AsyncSeekExt as _, future},
 };
 use derive_more::with_trait::{Display, Error, From};
-use hyper::{server::Server, service::make_service_fn};
+use hyper::{
+    server::Server,
+    service::{make_service_fn, service_fn},
+};
 use s3_server::{
     S3Service, S3Storage, SimpleAuth, dto,
     errors::{S3Error, S3ErrorCode, S3StorageError, S3StorageResult},
@@ -19,10 +47,41 @@ use s3_server::{
 use secrecy::{ExposeSecret as _, SecretString};
 use tokio_util::{compat::FuturesAsyncReadCompatExt as _, io::ReaderStream};
 
+pub use self::cors::{CorsConfiguration, CorsRule};
+use self::{
+    service::{Params, Service},
+    signature::Credentials,
+};
+
 /// [`dto::PutObjectRequest::metadata`] key where [`CreateSymlink::src`] is
 /// expected to be provided.
+///
+/// Besides a full path (like `bucket/dir/file`), the value may be relative to
+/// the symlink's own directory (like `./file` or `../dir/file`), or to its
+/// bucket (like `/dir/file`).
+///
+/// If the key of the symlink ends with `/`, then it aliases a whole directory
+/// (like `bucket/dir/`), so all the keys prefixed with it are resolved into
+/// that directory.
 pub const SYMLINK_META_KEY: &str = "symlink-to";
 
+/// [`dto::PutObjectRequest::metadata`] key where
+/// [`CreateSymlink::expected_src`] is expected to be provided.
+///
+/// Accepts the same forms as the [`SYMLINK_META_KEY`] does.
+pub const SYMLINK_EXPECT_META_KEY: &str = "symlink-expect";
+
+/// [`dto::PutObjectRequest::metadata`] key where
+/// [`CreateSymlink::allow_dangling`] is expected to be provided as `true`.
+pub const SYMLINK_ALLOW_DANGLING_META_KEY: &str = "symlink-allow-dangling";
+
+/// [`dto::PutObjectRequest::metadata`] key where the mode of a link, created
+/// by the [`SYMLINK_META_KEY`], is expected to be provided.
+///
+/// Either `symlink` (default) to create a [`CreateSymlink`], or `hard` to
+/// create a [`CreateHardLink`].
+pub const LINK_MODE_META_KEY: &str = "link-mode";
+
 /// Runs [`S3`] HTTP server.
 ///
 /// # Errors
@@ -36,6 +95,58 @@ pub async fn run_http_server<S, A>(
 ) -> Result<(), RunHttpServerError>
 where
     A: ToSocketAddrs,
+    S: Exec<CreateFile<dto::ByteStream>, Err: fmt::Display + AsRef<io::Error>>
+        + Exec<
+            GetBucketConfig<CorsConfiguration>,
+            Ok = Option<CorsConfiguration>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<CorsConfiguration>, Err: fmt::Display>
+        + Exec<
+            GetBucketConfig<Versioning>,
+            Ok = Option<Versioning>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<Versioning>, Err: fmt::Display>
+        + Exec<ListVersions, Ok = Vec<FileVersion>, Err: fmt::Display>
+        + Exec<
+            GetBucketConfig<Lifecycle>,
+            Ok = Option<Lifecycle>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<Lifecycle>, Err: fmt::Display>
+        + Exec<GetBucketConfig<Quota>, Ok = Option<Quota>, Err: fmt::Display>
+        + Exec<SetBucketConfig<Quota>, Err: fmt::Display>
+        + Exec<GetBucketUsage, Ok = Usage, Err: fmt::Display>
+        + Exec<
+            GetBucketConfig<Compression>,
+            Ok = Option<Compression>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<Compression>, Err: fmt::Display>
+        + Exec<
+            GetBucketConfig<ObjectLock>,
+            Ok = Option<ObjectLock>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<ObjectLock>, Err: fmt::Display>
+        + Exec<
+            GetObjectLock,
+            Ok = Option<ObjectLockStatus>,
+            Err: fmt::Display + AsRef<io::Error>,
+        > + Exec<SetObjectRetention, Err: fmt::Display + AsRef<io::Error>>
+        + Exec<SetObjectLegalHold, Err: fmt::Display + AsRef<io::Error>>
+        + Exec<PublishSymlinks, Err: fmt::Display + AsRef<io::Error>>
+        + Exec<ListSymlinks, Ok = Vec<RelativePath>, Err: fmt::Display>
+        + Exec<
+            GetSymlinkHistory,
+            Ok = Vec<SymlinkHistoryEntry>,
+            Err: fmt::Display,
+        > + Exec<
+            RollbackSymlink,
+            Ok = RelativePath,
+            Err: fmt::Display + AsRef<io::Error>,
+        > + Exec<ListCorruptFiles, Ok = Vec<CorruptFile>, Err: fmt::Display>
+        + Exec<GetFreeSpace, Ok = FreeSpace, Err: fmt::Display>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
     S3<S>: S3Storage + Send + Sync + 'static,
 {
     let mut auth = SimpleAuth::new();
@@ -44,13 +155,21 @@ where
         secret_key.expose_secret().to_owned(),
     );
 
-    let mut s3 = S3Service::new(S3(storage));
+    let mut s3 = S3Service::new(S3(storage.clone()));
     s3.set_auth(auth);
 
-    let service = s3.into_shared();
+    let service = Service::new(
+        s3.into_shared(),
+        storage,
+        Credentials::new(access_key, secret_key),
+    );
     let listener = TcpListener::bind(addr)?;
-    let make_service =
-        make_service_fn(move |_| future::ok::<_, Infallible>(service.clone()));
+    let make_service = make_service_fn(move |_| {
+        let service = service.clone();
+        future::ok::<_, Infallible>(service_fn(move |req| {
+            service.clone().call(req)
+        }))
+    });
 
     tracing::info!("Starting S3 HTTP Server");
     Ok(Server::from_tcp(listener)?.serve(make_service).await?)
@@ -73,18 +192,28 @@ pub enum RunHttpServerError {
 pub struct S3<T>(T);
 
 #[async_trait]
-impl<S, E1, E2, E3> S3Storage for S3<S>
+impl<S, E1, E2, E3, E4, E5, E6, E7, E8> S3Storage for S3<S>
 where
-    S: Exec<CreateFile<dto::ByteStream>, Err = E1>
-        + Exec<CreateSymlink, Err = E2>
+    S: Exec<CreateFile<dto::ByteStream>, Ok = Option<VersionId>, Err = E1>
+        + Exec<CreateSymlink, Ok = Option<VersionId>, Err = E2>
+        + Exec<CreateHardLink, Ok = Option<VersionId>, Err = E6>
         + Exec<GetFile, Ok = Option<ReadOnlyFile>, Err = E3>
+        + Exec<DeleteFile, Ok = DeletedFile, Err = E4>
+        + Exec<GetSymlink, Ok = Option<RelativePath>, Err = E5>
+        + Exec<GetFreeSpace, Ok = FreeSpace, Err = E7>
+        + Exec<GetQuotaAllowance, Ok = Option<u64>, Err = E8>
         + fmt::Debug
         + Send
         + Sync
         + 'static,
-    E1: fmt::Display,
-    E2: fmt::Display,
-    E3: fmt::Display,
+    E1: fmt::Display + AsRef<io::Error>,
+    E2: fmt::Display + AsRef<io::Error>,
+    E3: fmt::Display + AsRef<io::Error>,
+    E4: fmt::Display + AsRef<io::Error>,
+    E5: fmt::Display,
+    E6: fmt::Display + AsRef<io::Error>,
+    E7: fmt::Display,
+    E8: fmt::Display + AsRef<io::Error>,
 {
     async fn complete_multipart_upload(
         &self,
@@ -127,11 +256,32 @@ where
         unimplemented!()
     }
 
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = input.bucket.as_str(), key = input.key.as_str()),
+    )]
     async fn delete_object(
         &self,
-        _: dto::DeleteObjectRequest,
+        input: dto::DeleteObjectRequest,
     ) -> S3StorageResult<dto::DeleteObjectOutput, dto::DeleteObjectError> {
-        unimplemented!()
+        let path = parse_s3_path(input.bucket, input.key)?;
+        let version_id = input.version_id.map(parse_version_id).transpose()?;
+
+        let bypass_governance =
+            input.bypass_governance_retention.unwrap_or_default();
+
+        let deleted = self
+            .0
+            .exec(DeleteFile { path, version_id, bypass_governance })
+            .await
+            .map_err(|e| storage_error("DeleteFile operation failed", e))?;
+
+        tracing::info!("OK");
+        Ok(dto::DeleteObjectOutput {
+            delete_marker: deleted.delete_marker.then_some(true),
+            version_id: deleted.version_id.map(|id| id.to_string()),
+            ..dto::DeleteObjectOutput::default()
+        })
     }
 
     async fn delete_objects(
@@ -161,21 +311,87 @@ where
         input: dto::GetObjectRequest,
     ) -> S3StorageResult<dto::GetObjectOutput, dto::GetObjectError> {
         let path = parse_s3_path(input.bucket, input.key.clone())?;
-
-        let file = self
+        let params = Params::current();
+        let version_id = params.version_id.map(parse_version_id).transpose()?;
+        let is_versioned = version_id.is_some();
+
+        if params.symlink {
+            if let Some(target) =
+                self.symlink(&path, version_id.clone()).await?
+            {
+                tracing::info!("OK");
+                return Ok(dto::GetObjectOutput {
+                    content_length: Some(0),
+                    metadata: Some(symlink_metadata(&target)),
+                    version_id: version_id.map(|id| id.to_string()),
+                    ..dto::GetObjectOutput::default()
+                });
+            }
+        }
+
+        let encryption_key = encryption::customer_key(
+            input.sse_customer_algorithm,
+            input.sse_customer_key,
+            input.sse_customer_key_md5,
+        )?;
+
+        let mut file = self
             .0
-            .exec(GetFile { path })
+            .exec(GetFile { path, version_id, encryption_key })
             .await
-            .map_err(|e| internal_error("GetFile operation failed", e))?
-            .ok_or(S3StorageError::Operation(
-                dto::GetObjectError::NoSuchKey(input.key),
-            ))?;
-
-        let reader = ReaderStream::new(file.compat());
+            .map_err(|e| storage_error("GetFile operation failed", e))?
+            .ok_or_else(|| {
+                if is_versioned {
+                    S3Error::new(
+                        S3ErrorCode::NoSuchVersion,
+                        "The specified version does not exist",
+                    )
+                    .into()
+                } else {
+                    S3StorageError::Operation(dto::GetObjectError::NoSuchKey(
+                        input.key,
+                    ))
+                }
+            })?;
+        let size = file
+            .size()
+            .await
+            .map_err(|e| internal_error("Failed to read file size", e))?;
+        let version_id = file.version_id().map(ToString::to_string);
+        let last_modified = versioning::format_time(file.last_modified());
+        let metadata = file.symlink_target().map(symlink_metadata);
+        let sse = encryption::Headers::new(file.encryption());
+
+        let range = input
+            .range
+            .as_deref()
+            .map(|r| range::parse(r, size))
+            .transpose()?
+            .flatten();
+        let (length, content_range) = match range {
+            Some((first, last)) => {
+                _ = file
+                    .seek(io::SeekFrom::Start(first))
+                    .await
+                    .map_err(|e| internal_error("Failed to seek file", e))?;
+                (last - first + 1, Some(format!("bytes {first}-{last}/{size}")))
+            }
+            None => (size, None),
+        };
+        let reader = ReaderStream::new(file.take(length).compat());
 
         tracing::info!("OK");
         Ok(dto::GetObjectOutput {
             body: Some(dto::ByteStream::new(reader)),
+            accept_ranges: Some("bytes".into()),
+            content_length: i64::try_from(length).ok(),
+            content_range,
+            last_modified: Some(last_modified),
+            metadata,
+            server_side_encryption: sse.server_side_encryption,
+            sse_customer_algorithm: sse.sse_customer_algorithm,
+            sse_customer_key_md5: sse.sse_customer_key_md5,
+            version_id,
             ..dto::GetObjectOutput::default()
         })
     }
@@ -187,11 +403,64 @@ where
         unimplemented!()
     }
 
+    #[tracing::instrument(
+        skip_all,
+        fields(bucket = input.bucket.as_str(), key = input.key.as_str()),
+    )]
     async fn head_object(
         &self,
-        _: dto::HeadObjectRequest,
+        input: dto::HeadObjectRequest,
     ) -> S3StorageResult<dto::HeadObjectOutput, dto::HeadObjectError> {
-        unimplemented!()
+        let path = parse_s3_path(input.bucket, input.key.clone())?;
+        let params = Params::current();
+        let version_id = params.version_id.map(parse_version_id).transpose()?;
+
+        if params.symlink {
+            if let Some(target) =
+                self.symlink(&path, version_id.clone()).await?
+            {
+                tracing::info!("OK");
+                return Ok(dto::HeadObjectOutput {
+                    content_length: Some(0),
+                    metadata: Some(symlink_metadata(&target)),
+                    version_id: version_id.map(|id| id.to_string()),
+                    ..dto::HeadObjectOutput::default()
+                });
+            }
+        }
+
+        let encryption_key = encryption::customer_key(
+            input.sse_customer_algorithm,
+            input.sse_customer_key,
+            input.sse_customer_key_md5,
+        )?;
+
+        let file = self
+            .0
+            .exec(GetFile { path, version_id, encryption_key })
+            .await
+            .map_err(|e| storage_error("GetFile operation failed", e))?
+            .ok_or(S3StorageError::Operation(
+                dto::HeadObjectError::NoSuchKey(input.key),
+            ))?;
+        let size = file
+            .size()
+            .await
+            .map_err(|e| internal_error("Failed to read file size", e))?;
+        let sse = encryption::Headers::new(file.encryption());
+
+        tracing::info!("OK");
+        Ok(dto::HeadObjectOutput {
+            accept_ranges: Some("bytes".into()),
+            content_length: i64::try_from(size).ok(),
+            last_modified: Some(versioning::format_time(file.last_modified())),
+            metadata: file.symlink_target().map(symlink_metadata),
+            server_side_encryption: sse.server_side_encryption,
+            sse_customer_algorithm: sse.sse_customer_algorithm,
+            sse_customer_key_md5: sse.sse_customer_key_md5,
+            version_id: file.version_id().map(ToString::to_string),
+            ..dto::HeadObjectOutput::default()
+        })
     }
 
     async fn list_buckets(
@@ -224,33 +493,121 @@ where
         &self,
         input: dto::PutObjectRequest,
     ) -> S3StorageResult<dto::PutObjectOutput, dto::PutObjectError> {
-        let path = parse_s3_path(input.bucket, input.key)?;
-
-        if let Some(original) =
-            input.metadata.and_then(|mut meta| meta.remove(SYMLINK_META_KEY))
-        {
-            let op = CreateSymlink {
-                src: parse_relative_path(SYMLINK_META_KEY, original)?,
-                dest: path,
+        // Symlinks with keys ending with `/` alias whole directories.
+        let directory = input.key.ends_with('/')
+            && input
+                .metadata
+                .as_ref()
+                .is_some_and(|m| m.contains_key(SYMLINK_META_KEY));
+        let key = if directory {
+            input.key.trim_end_matches('/').to_owned()
+        } else {
+            input.key
+        };
+        let path = parse_s3_path(input.bucket, key)?;
+        let encryption = encryption::parse(
+            input.server_side_encryption,
+            encryption::customer_key(
+                input.sse_customer_algorithm,
+                input.sse_customer_key,
+                input.sse_customer_key_md5,
+            )?,
+        )?;
+        let sse = encryption::Headers::new(encryption.as_ref());
+
+        let mut meta = input.metadata.unwrap_or_default();
+        let expected_src = meta
+            .remove(SYMLINK_EXPECT_META_KEY)
+            .map(|s| parse_symlink_target(SYMLINK_EXPECT_META_KEY, &path, &s))
+            .transpose()?;
+
+        let allow_dangling = meta
+            .remove(SYMLINK_ALLOW_DANGLING_META_KEY)
+            .is_some_and(|v| v.eq_ignore_ascii_case("true"));
+
+        let link_mode = meta.remove(LINK_MODE_META_KEY);
+
+        let version_id = if let Some(original) = meta.remove(SYMLINK_META_KEY) {
+            let original = if directory {
+                original.trim_end_matches('/')
+            } else {
+                &original
             };
-
-            self.0
-                .exec(op)
-                .await
-                .map_err(|e| internal_error("Symlink operation failed", e))?;
+            let src = parse_symlink_target(SYMLINK_META_KEY, &path, original)?;
+            match link_mode.as_deref() {
+                None | Some("symlink") => {
+                    let op = CreateSymlink {
+                        src,
+                        dest: path,
+                        expected_src,
+                        allow_dangling,
+                        directory,
+                    };
+                    self.0.exec(op).await.map_err(symlink_error)?
+                }
+                Some("hard") => {
+                    if expected_src.is_some() || allow_dangling {
+                        return Err(S3Error::new(
+                            S3ErrorCode::InvalidArgument,
+                            format!(
+                                "`{SYMLINK_EXPECT_META_KEY}` and \
+                                 `{SYMLINK_ALLOW_DANGLING_META_KEY}` metadata \
+                                 cannot be used with hard links",
+                            ),
+                        )
+                        .into());
+                    }
+                    let op = CreateHardLink { src, dest: path };
+                    self.0.exec(op).await.map_err(symlink_error)?
+                }
+                Some(mode) => {
+                    return Err(S3Error::new(
+                        S3ErrorCode::InvalidArgument,
+                        format!(
+                            "Invalid `{LINK_MODE_META_KEY}`: `{mode}`, \
+                             expected `symlink` or `hard`",
+                        ),
+                    )
+                    .into());
+                }
+            }
         } else {
+            if expected_src.is_some() || allow_dangling || link_mode.is_some() {
+                return Err(S3Error::new(
+                    S3ErrorCode::InvalidArgument,
+                    format!(
+                        "`{SYMLINK_EXPECT_META_KEY}`, \
+                         `{SYMLINK_ALLOW_DANGLING_META_KEY}` and \
+                         `{LINK_MODE_META_KEY}` metadata require \
+                         `{SYMLINK_META_KEY}` one",
+                    ),
+                )
+                .into());
+            }
+            space::ensure_available(&self.0, input.content_length).await?;
+            quota::ensure_allowed(&self.0, &path, input.content_length).await?;
+
             let op = CreateFile {
                 path,
                 bytes: input.body.unwrap_or_else(|| vec![].into()),
+                encryption,
+                content_type: input.content_type,
             };
 
-            self.0.exec(op).await.map_err(|e| {
-                internal_error("CreateFile operation failed", e)
-            })?;
+            self.0
+                .exec(op)
+                .await
+                .map_err(|e| storage_error("CreateFile operation failed", e))?
         };
 
         tracing::info!("OK");
-        Ok(dto::PutObjectOutput::default())
+        Ok(dto::PutObjectOutput {
+            server_side_encryption: sse.server_side_encryption,
+            sse_customer_algorithm: sse.sse_customer_algorithm,
+            sse_customer_key_md5: sse.sse_customer_key_md5,
+            version_id: version_id.map(|id| id.to_string()),
+            ..dto::PutObjectOutput::default()
+        })
     }
 
     async fn upload_part(
@@ -261,6 +618,34 @@ where
     }
 }
 
+impl<S> S3<S>
+where
+    S: Exec<GetSymlink, Ok = Option<RelativePath>, Err: fmt::Display>,
+{
+    /// Returns the [`RelativePath`] of the target of the symlink at the
+    /// provided `path`, if it's a symlink.
+    ///
+    /// # Errors
+    ///
+    /// If the [`GetSymlink`] operation fails.
+    async fn symlink(
+        &self,
+        path: &RelativePath,
+        version_id: Option<VersionId>,
+    ) -> Result<Option<RelativePath>, S3Error> {
+        self.0
+            .exec(GetSymlink { path: path.clone(), version_id })
+            .await
+            .map_err(|e| internal_error("GetSymlink operation failed", e))
+    }
+}
+
+/// Returns the object metadata reporting the provided symlink `target` under
+/// the [`SYMLINK_META_KEY`].
+fn symlink_metadata(target: &RelativePath) -> HashMap<String, String> {
+    HashMap::from([(SYMLINK_META_KEY.to_owned(), target.to_string())])
+}
+
 /// Parses the provided `bucket` and `key` into a single [`RelativePath`].
 fn parse_s3_path(bucket: String, key: String) -> Result<RelativePath, S3Error> {
     Ok(parse_relative_path("bucket", bucket)?
@@ -277,7 +662,198 @@ fn parse_relative_path(attr: &str, s: String) -> Result<RelativePath, S3Error> {
     })
 }
 
+/// Parses the provided symlink `target` into a [`RelativePath`], resolving it
+/// against the provided `link` if it's relative to the `link`'s directory
+/// (starts with `./` or `../`) or bucket (starts with `/`).
+///
+/// # Errors
+///
+/// - If the `target` escapes the bucket of the `link`.
+/// - If the resolved `target` is not a valid [`RelativePath`].
+fn parse_symlink_target(
+    attr: &str,
+    link: &RelativePath,
+    target: &str,
+) -> Result<RelativePath, S3Error> {
+    let link = link.to_string();
+    let mut base = link.split('/').collect::<Vec<_>>();
+    let mut rest = target;
+    if let Some(key) = target.strip_prefix('/') {
+        base.truncate(1);
+        rest = key;
+    } else if target.starts_with("./") || target.starts_with("../") {
+        _ = base.pop();
+        rest = target.strip_prefix("./").unwrap_or(target);
+        while let Some(r) = rest.strip_prefix("../") {
+            if base.len() <= 1 {
+                return Err(S3Error::new(
+                    S3ErrorCode::InvalidArgument,
+                    format!("Invalid {attr}: `{target}` escapes the bucket"),
+                ));
+            }
+            _ = base.pop();
+            rest = r;
+        }
+    } else {
+        base.clear();
+    }
+    base.push(rest);
+
+    parse_relative_path(attr, base.join("/"))
+}
+
+/// Parses the provided [`String`] into a [`VersionId`].
+fn parse_version_id(s: String) -> Result<VersionId, S3Error> {
+    s.try_into().map_err(|e| {
+        S3Error::new(
+            S3ErrorCode::InvalidArgument,
+            format!("Invalid versionId: {e}"),
+        )
+    })
+}
+
+/// Constructs an [`S3Error`] out of the provided storage operation error.
+///
+/// Exceeded [`Quota`]s are reported as the `QuotaExceeded` error, objects
+/// protected by an [`ObjectLock`] or encrypted with another key as the
+/// `AccessDenied` one, invalid encryption parameters as the `InvalidRequest`
+/// one, symlinks not pointing to the [`CreateSymlink::expected_src`] as the
+/// `PreconditionFailed` one, objects protected by symlinks pointing to them
+/// as the `ObjectHasSymlinks` one, objects with corrupt contents as the
+/// `ObjectCorrupted` one, the storage being read-only as the
+/// `ServiceUnavailable` one, and the storage being full as the
+/// `InsufficientStorage` one, while all other errors are considered internal.
+fn storage_error<E>(msg: &str, e: E) -> S3Error
+where
+    E: fmt::Display + AsRef<io::Error>,
+{
+    map_storage_error(msg, e, S3Error::new, service::Error::into_s3_error)
+}
+
+/// Maps the provided storage operation error in the same way as the
+/// [`storage_error()`] does, constructing the result with the provided
+/// `standard` function for errors having an [`S3ErrorCode`], and with the
+/// `custom` one for other [`service::Error`]s.
+pub(crate) fn map_storage_error<E, T>(
+    msg: &str,
+    e: E,
+    standard: impl FnOnce(S3ErrorCode, String) -> T,
+    custom: impl FnOnce(service::Error) -> T,
+) -> T
+where
+    E: fmt::Display + AsRef<io::Error>,
+{
+    match e.as_ref().kind() {
+        io::ErrorKind::QuotaExceeded => {
+            custom(quota::exceeded_error(e.to_string()))
+        }
+        io::ErrorKind::PermissionDenied
+            if caused_by::<AccessDeniedError>(e.as_ref()) =>
+        {
+            standard(S3ErrorCode::AccessDenied, e.to_string())
+        }
+        io::ErrorKind::InvalidInput => {
+            standard(S3ErrorCode::InvalidRequest, e.to_string())
+        }
+        io::ErrorKind::AlreadyExists
+            if caused_by::<PreconditionError>(e.as_ref()) =>
+        {
+            standard(S3ErrorCode::PreconditionFailed, e.to_string())
+        }
+        io::ErrorKind::ResourceBusy => {
+            custom(links::linked_error(e.to_string()))
+        }
+        io::ErrorKind::InvalidData => {
+            custom(integrity::corrupt_error(e.to_string()))
+        }
+        io::ErrorKind::ReadOnlyFilesystem => {
+            standard(S3ErrorCode::ServiceUnavailable, e.to_string())
+        }
+        io::ErrorKind::StorageFull => {
+            custom(space::insufficient_error(e.to_string()))
+        }
+        _ => standard(S3ErrorCode::InternalError, format!("{msg}: {e}")),
+    }
+}
+
+/// Constructs an [`S3Error`] out of the provided failed [`CreateSymlink`] or
+/// [`CreateHardLink`] operation error.
+///
+/// Links not resolving to an existing file or crossing filesystems are
+/// reported as the `InvalidArgument` error, while all other errors are
+/// reported as the [`storage_error()`] does.
+fn symlink_error<E>(e: E) -> S3Error
+where
+    E: fmt::Display + AsRef<io::Error>,
+{
+    match e.as_ref().kind() {
+        io::ErrorKind::NotFound
+        | io::ErrorKind::IsADirectory
+        | io::ErrorKind::NotADirectory
+        | io::ErrorKind::TooManyLinks
+        | io::ErrorKind::CrossesDevices => {
+            S3Error::new(S3ErrorCode::InvalidArgument, e.to_string())
+        }
+        _ => storage_error("Link operation failed", e),
+    }
+}
+
+/// Checks whether the provided [`io::Error`] is caused by an error of the
+/// provided type `T`.
+///
+/// Storage operations report denials and failed preconditions of their own
+/// this way, so they aren't confused with the same [`io::ErrorKind`]s
+/// returned by the underlying filesystem.
+pub(crate) fn caused_by<T: StdError + 'static>(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<T>())
+}
+
 /// Constructs an internal [`S3Error`].
 fn internal_error<E: fmt::Display>(msg: &str, e: E) -> S3Error {
     S3Error::new(S3ErrorCode::InternalError, format!("{msg}: {e}"))
 }
+
+#[cfg(test)]
+mod map_storage_error_spec {
+    use std::{convert, io};
+
+    use derive_more::with_trait::Display;
+
+    use super::map_storage_error;
+    use crate::service::Error;
+
+    /// Storage operation error of the provided [`io::ErrorKind`].
+    #[derive(Debug, Display)]
+    #[display("{_0}")]
+    struct Failure(io::Error);
+
+    impl AsRef<io::Error> for Failure {
+        fn as_ref(&self) -> &io::Error {
+            &self.0
+        }
+    }
+
+    fn code_of(kind: io::ErrorKind) -> &'static str {
+        let e = Failure(io::Error::new(kind, "failure"));
+        map_storage_error("Op failed", e, Error::new, convert::identity).code
+    }
+
+    #[test]
+    fn maps_storage_errors_to_s3_errors() {
+        assert_eq!(code_of(io::ErrorKind::QuotaExceeded), "QuotaExceeded");
+        assert_eq!(code_of(io::ErrorKind::ResourceBusy), "ObjectHasSymlinks");
+        assert_eq!(code_of(io::ErrorKind::InvalidData), "ObjectCorrupted");
+        assert_eq!(code_of(io::ErrorKind::StorageFull), "InsufficientStorage");
+        assert_eq!(
+            code_of(io::ErrorKind::ReadOnlyFilesystem),
+            "ServiceUnavailable",
+        );
+    }
+
+    #[test]
+    fn considers_unmarked_errors_internal() {
+        assert_eq!(code_of(io::ErrorKind::PermissionDenied), "InternalError");
+        assert_eq!(code_of(io::ErrorKind::AlreadyExists), "InternalError");
+        assert_eq!(code_of(io::ErrorKind::Other), "InternalError");
+    }
+}
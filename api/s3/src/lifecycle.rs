@@ -0,0 +1,406 @@
+//! [Lifecycle][0] configuration of buckets.
+//!
+//! [0]: https://docs.aws.amazon.com/AmazonS3/latest/userguide/lifecycle.html
+
+use std::fmt;
+
+use baza::{
+    Exec, GetBucketConfig, Lifecycle, LifecycleRule, RelativePath,
+    SetBucketConfig,
+};
+use hyper::{Body, Response, StatusCode};
+use s3_server::errors::S3ErrorCode;
+use serde::{Deserialize, Serialize, de::IgnoredAny};
+
+use crate::service::{self, Error};
+
+/// Maximum number of [`Rule`]s in a [`LifecycleConfiguration`].
+const MAX_RULES: usize = 1000;
+
+/// XML representation of a bucket [`Lifecycle`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct LifecycleConfiguration {
+    /// [`Rule`]s of this [`LifecycleConfiguration`].
+    #[serde(rename = "Rule", default)]
+    rules: Vec<Rule>,
+}
+
+/// XML representation of a [`LifecycleRule`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Rule {
+    /// Unique identifier of this [`Rule`].
+    #[serde(rename = "ID", skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+
+    /// [`Filter`] of the keys this [`Rule`] is applied to.
+    #[serde(rename = "Filter", skip_serializing_if = "Option::is_none")]
+    filter: Option<Filter>,
+
+    /// Legacy way to specify prefix of the keys this [`Rule`] is applied to.
+    #[serde(rename = "Prefix", skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+
+    /// Status of this [`Rule`]: either `Enabled` or `Disabled`.
+    #[serde(rename = "Status")]
+    status: String,
+
+    /// Expiration of current versions.
+    #[serde(rename = "Expiration", skip_serializing_if = "Option::is_none")]
+    expiration: Option<Expiration>,
+
+    /// Expiration of noncurrent versions.
+    #[serde(
+        rename = "NoncurrentVersionExpiration",
+        skip_serializing_if = "Option::is_none"
+    )]
+    noncurrent_version_expiration: Option<NoncurrentVersionExpiration>,
+
+    /// Aborting of incomplete multipart uploads.
+    #[serde(
+        rename = "AbortIncompleteMultipartUpload",
+        skip_serializing_if = "Option::is_none"
+    )]
+    abort_incomplete_multipart_upload: Option<AbortIncompleteMultipartUpload>,
+}
+
+/// Filter of the keys a [`Rule`] is applied to.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Filter {
+    /// Prefix of the keys.
+    #[serde(rename = "Prefix", skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+
+    /// Tag of the objects (not supported).
+    #[serde(rename = "Tag", default, skip_serializing)]
+    tag: Option<IgnoredAny>,
+
+    /// Conjunction of multiple filters (not supported).
+    #[serde(rename = "And", default, skip_serializing)]
+    and: Option<IgnoredAny>,
+}
+
+/// Expiration of current versions in a [`Rule`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Expiration {
+    /// Number of days after creation the current version expires.
+    #[serde(rename = "Days", skip_serializing_if = "Option::is_none")]
+    days: Option<u32>,
+
+    /// Date when the current version expires (not supported).
+    #[serde(rename = "Date", default, skip_serializing)]
+    date: Option<IgnoredAny>,
+}
+
+/// Expiration of noncurrent versions in a [`Rule`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct NoncurrentVersionExpiration {
+    /// Number of days after becoming noncurrent a version expires.
+    #[serde(rename = "NoncurrentDays")]
+    noncurrent_days: u32,
+}
+
+/// Aborting of incomplete multipart uploads in a [`Rule`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct AbortIncompleteMultipartUpload {
+    /// Number of days after initiation an incomplete upload is aborted.
+    #[serde(rename = "DaysAfterInitiation")]
+    days_after_initiation: u32,
+}
+
+impl TryFrom<Rule> for LifecycleRule {
+    type Error = Error;
+
+    fn try_from(rule: Rule) -> Result<Self, Self::Error> {
+        let enabled = match rule.status.as_str() {
+            "Enabled" => true,
+            "Disabled" => false,
+            _ => {
+                return Err(Error::new(
+                    S3ErrorCode::MalformedXML,
+                    "Lifecycle rule status must be either `Enabled` or \
+                     `Disabled`",
+                ));
+            }
+        };
+
+        let filter = rule.filter.unwrap_or_default();
+        if filter.tag.is_some() || filter.and.is_some() {
+            return Err(Error::new(
+                S3ErrorCode::NotImplemented,
+                "Only `Prefix` lifecycle rule filters are supported",
+            ));
+        }
+        let expiration = rule.expiration.unwrap_or_default();
+        if expiration.date.is_some() {
+            return Err(Error::new(
+                S3ErrorCode::NotImplemented,
+                "Only `Days` lifecycle expiration is supported",
+            ));
+        }
+
+        let converted = Self {
+            id: rule.id,
+            enabled,
+            prefix: filter.prefix.or(rule.prefix).unwrap_or_default(),
+            expiration_days: expiration.days,
+            noncurrent_version_expiration_days: rule
+                .noncurrent_version_expiration
+                .map(|e| e.noncurrent_days),
+            abort_incomplete_multipart_upload_days: rule
+                .abort_incomplete_multipart_upload
+                .map(|a| a.days_after_initiation),
+        };
+        let days = [
+            converted.expiration_days,
+            converted.noncurrent_version_expiration_days,
+            converted.abort_incomplete_multipart_upload_days,
+        ];
+        if days.iter().all(Option::is_none) {
+            return Err(Error::new(
+                S3ErrorCode::InvalidArgument,
+                "Lifecycle rule must specify at least one action",
+            ));
+        }
+        if days.contains(&Some(0)) {
+            return Err(Error::new(
+                S3ErrorCode::InvalidArgument,
+                "Lifecycle rule days must be a positive integer",
+            ));
+        }
+        Ok(converted)
+    }
+}
+
+impl From<LifecycleRule> for Rule {
+    fn from(rule: LifecycleRule) -> Self {
+        Self {
+            id: rule.id,
+            filter: Some(Filter {
+                prefix: Some(rule.prefix),
+                ..Filter::default()
+            }),
+            prefix: None,
+            status: if rule.enabled { "Enabled" } else { "Disabled" }.into(),
+            expiration: rule
+                .expiration_days
+                .map(|days| Expiration { days: Some(days), date: None }),
+            noncurrent_version_expiration: rule
+                .noncurrent_version_expiration_days
+                .map(|noncurrent_days| NoncurrentVersionExpiration {
+                    noncurrent_days,
+                }),
+            abort_incomplete_multipart_upload: rule
+                .abort_incomplete_multipart_upload_days
+                .map(|days_after_initiation| AbortIncompleteMultipartUpload {
+                    days_after_initiation,
+                }),
+        }
+    }
+}
+
+/// Handles the [PutBucketLifecycleConfiguration][0] operation.
+///
+/// # Errors
+///
+/// - If the provided `body` is not a valid [`LifecycleConfiguration`].
+/// - If the [`SetBucketConfig`] operation fails.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn put_bucket_lifecycle<S>(
+    storage: &S,
+    bucket: RelativePath,
+    body: &[u8],
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<Lifecycle>, Err: fmt::Display>,
+{
+    let config = service::parse_xml::<LifecycleConfiguration>(body)?;
+    if config.rules.is_empty() || config.rules.len() > MAX_RULES {
+        return Err(Error::new(
+            S3ErrorCode::MalformedXML,
+            format!("Lifecycle configuration must have 1..={MAX_RULES} rules"),
+        ));
+    }
+    let lifecycle = Lifecycle {
+        rules: config
+            .rules
+            .into_iter()
+            .map(LifecycleRule::try_from)
+            .collect::<Result<_, _>>()?,
+    };
+
+    storage
+        .exec(SetBucketConfig { bucket, config: Some(lifecycle) })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    Ok(Response::new(Body::empty()))
+}
+
+/// Handles the [GetBucketLifecycleConfiguration][0] operation.
+///
+/// # Errors
+///
+/// - If the [`GetBucketConfig`] operation fails.
+/// - If the bucket has no [`Lifecycle`] configured.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn get_bucket_lifecycle<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            GetBucketConfig<Lifecycle>,
+            Ok = Option<Lifecycle>,
+            Err: fmt::Display,
+        >,
+{
+    let lifecycle = storage
+        .exec(GetBucketConfig::new(bucket))
+        .await
+        .map_err(|e| Error::internal("GetBucketConfig", e))?
+        .ok_or_else(|| {
+            Error::new(
+                S3ErrorCode::NoSuchLifecycleConfiguration,
+                "The lifecycle configuration does not exist",
+            )
+        })?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "LifecycleConfiguration",
+        &LifecycleConfiguration {
+            rules: lifecycle.rules.into_iter().map(Into::into).collect(),
+        },
+    )
+}
+
+/// Handles the [DeleteBucketLifecycle][0] operation.
+///
+/// # Errors
+///
+/// If the [`SetBucketConfig`] operation fails.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn delete_bucket_lifecycle<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<Lifecycle>, Err: fmt::Display>,
+{
+    storage
+        .exec(SetBucketConfig::<Lifecycle> { bucket, config: None })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = StatusCode::NO_CONTENT;
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod spec {
+    use baza::LifecycleRule;
+
+    use super::{LifecycleConfiguration, Rule};
+    use crate::service;
+
+    fn parse(rule: &str) -> Result<LifecycleRule, String> {
+        let xml = format!(
+            "<LifecycleConfiguration><Rule>{rule}</Rule>\
+             </LifecycleConfiguration>",
+        );
+        let mut config =
+            service::parse_xml::<LifecycleConfiguration>(xml.as_bytes())
+                .map_err(|e| e.code.to_owned())?;
+        LifecycleRule::try_from(config.rules.remove(0))
+            .map_err(|e| e.code.to_owned())
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rule = parse(
+            "<ID>tmp</ID><Filter><Prefix>tmp/</Prefix></Filter>\
+             <Status>Enabled</Status><Expiration><Days>3</Days></Expiration>\
+             <NoncurrentVersionExpiration><NoncurrentDays>1</NoncurrentDays>\
+             </NoncurrentVersionExpiration>",
+        )
+        .unwrap();
+
+        assert_eq!(
+            rule,
+            LifecycleRule {
+                id: Some("tmp".into()),
+                enabled: true,
+                prefix: "tmp/".into(),
+                expiration_days: Some(3),
+                noncurrent_version_expiration_days: Some(1),
+                abort_incomplete_multipart_upload_days: None,
+            },
+        );
+
+        let legacy = parse(
+            "<Prefix>old/</Prefix><Status>Disabled</Status>\
+             <Expiration><Days>1</Days></Expiration>",
+        )
+        .unwrap();
+        assert_eq!(legacy.prefix, "old/");
+        assert!(!legacy.enabled);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for (rule, code) in [
+            ("<Status>Enabled</Status>", "InvalidArgument"),
+            (
+                "<Status>On</Status><Expiration><Days>1</Days></Expiration>",
+                "MalformedXML",
+            ),
+            (
+                "<Status>Enabled</Status><Expiration><Days>0</Days>\
+                 </Expiration>",
+                "InvalidArgument",
+            ),
+            (
+                "<Status>Enabled</Status><Expiration>\
+                 <Date>2030-01-01T00:00:00Z</Date></Expiration>",
+                "NotImplemented",
+            ),
+            (
+                "<Filter><Tag><Key>k</Key><Value>v</Value></Tag></Filter>\
+                 <Status>Enabled</Status><Expiration><Days>1</Days>\
+                 </Expiration>",
+                "NotImplemented",
+            ),
+        ] {
+            assert_eq!(parse(rule).unwrap_err(), code, "rule: {rule}");
+        }
+    }
+
+    #[test]
+    fn renders_rules() {
+        let rule = Rule::from(LifecycleRule {
+            id: None,
+            enabled: true,
+            prefix: "logs/".into(),
+            expiration_days: None,
+            noncurrent_version_expiration_days: Some(7),
+            abort_incomplete_multipart_upload_days: None,
+        });
+
+        assert_eq!(rule.filter.unwrap().prefix.as_deref(), Some("logs/"));
+        assert_eq!(rule.status, "Enabled");
+        assert!(rule.expiration.is_none());
+        assert_eq!(
+            rule.noncurrent_version_expiration.unwrap().noncurrent_days,
+            7
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Reverse index and history of symlinks.
+//!
+//! S3 has no API for symlinks at all, so the symlinks pointing to an object
+//! are listed via the `?links` subresource of the object, while the history
+//! of a symlink is managed via its `?symlink-history` and `?symlink-rollback`
+//! subresources.
+
+use std::{fmt, io};
+
+use baza::{
+    AccessDeniedError, Exec, GetSymlinkHistory, ListSymlinks,
+    PreconditionError, RelativePath, RollbackSymlink, SymlinkHistoryEntry,
+};
+use hyper::{Body, Response, StatusCode};
+use s3_server::errors::S3ErrorCode;
+use serde::Serialize;
+
+use crate::{
+    caused_by,
+    service::{self, Error},
+    versioning::format_time,
+};
+
+/// XML representation of a [`ListSymlinks`] result.
+#[derive(Debug, Serialize)]
+struct ListSymlinksResult {
+    /// Listed symlinks.
+    #[serde(rename = "Symlink")]
+    symlinks: Vec<Symlink>,
+}
+
+/// XML representation of a single symlink in a [`ListSymlinksResult`].
+#[derive(Debug, Serialize)]
+struct Symlink {
+    /// Bucket of the symlink.
+    #[serde(rename = "Bucket")]
+    bucket: String,
+
+    /// Key of the symlink.
+    #[serde(rename = "Key")]
+    key: String,
+}
+
+impl From<RelativePath> for Symlink {
+    fn from(path: RelativePath) -> Self {
+        let bucket = path.bucket();
+        let key = path
+            .as_ref()
+            .strip_prefix(&bucket)
+            .unwrap_or(path.as_ref())
+            .to_string_lossy()
+            .into_owned();
+        Self { bucket: bucket.to_string(), key }
+    }
+}
+
+/// Creates a new [`Error`] of an object being refused to be overwritten or
+/// deleted, because of symlinks pointing to it.
+#[must_use]
+pub(crate) fn linked_error(message: impl Into<String>) -> Error {
+    Error::custom("ObjectHasSymlinks", StatusCode::CONFLICT, message)
+}
+
+/// Handles the `ListSymlinks` operation.
+///
+/// # Errors
+///
+/// If the [`ListSymlinks`] operation fails.
+#[tracing::instrument(skip_all, fields(%target))]
+pub(crate) async fn list_symlinks<S>(
+    storage: &S,
+    target: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<ListSymlinks, Ok = Vec<RelativePath>, Err: fmt::Display>,
+{
+    let links = storage
+        .exec(ListSymlinks { target })
+        .await
+        .map_err(|e| Error::internal("ListSymlinks", e))?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "ListSymlinksResult",
+        &ListSymlinksResult {
+            symlinks: links.into_iter().map(Into::into).collect(),
+        },
+    )
+}
+
+/// XML representation of a [`GetSymlinkHistory`] result.
+#[derive(Debug, Serialize)]
+struct SymlinkHistory {
+    /// Previous targets of the symlink, from the latest to the oldest one.
+    #[serde(rename = "Entry")]
+    entries: Vec<HistoryEntry>,
+}
+
+/// XML representation of a single [`SymlinkHistoryEntry`].
+#[derive(Debug, Serialize)]
+struct HistoryEntry {
+    /// Bucket and key of the original source file the symlink pointed to.
+    #[serde(rename = "Target")]
+    target: String,
+
+    /// Time when the symlink was repointed from the `target`.
+    #[serde(rename = "ReplacedAt")]
+    replaced_at: String,
+}
+
+impl From<SymlinkHistoryEntry> for HistoryEntry {
+    fn from(e: SymlinkHistoryEntry) -> Self {
+        Self {
+            target: e.target.to_string(),
+            replaced_at: format_time(e.replaced_at),
+        }
+    }
+}
+
+/// XML representation of a [`RollbackSymlink`] result.
+#[derive(Debug, Serialize)]
+struct RollbackSymlinkResult {
+    /// Bucket and key of the original source file the symlink points to now.
+    #[serde(rename = "Target")]
+    target: String,
+}
+
+/// Handles the `GetSymlinkHistory` operation.
+///
+/// # Errors
+///
+/// If the [`GetSymlinkHistory`] operation fails.
+#[tracing::instrument(skip_all, fields(%path))]
+pub(crate) async fn get_symlink_history<S>(
+    storage: &S,
+    path: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            GetSymlinkHistory,
+            Ok = Vec<SymlinkHistoryEntry>,
+            Err: fmt::Display,
+        >,
+{
+    let entries = storage
+        .exec(GetSymlinkHistory { path })
+        .await
+        .map_err(|e| Error::internal("GetSymlinkHistory", e))?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "SymlinkHistory",
+        &SymlinkHistory {
+            entries: entries.into_iter().map(Into::into).collect(),
+        },
+    )
+}
+
+/// Handles the `RollbackSymlink` operation.
+///
+/// # Errors
+///
+/// If the [`RollbackSymlink`] operation fails.
+#[tracing::instrument(skip_all, fields(%path))]
+pub(crate) async fn rollback_symlink<S>(
+    storage: &S,
+    path: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            RollbackSymlink,
+            Ok = RelativePath,
+            Err: fmt::Display + AsRef<io::Error>,
+        >,
+{
+    let target =
+        storage.exec(RollbackSymlink { path }).await.map_err(rollback_error)?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "RollbackSymlinkResult",
+        &RollbackSymlinkResult { target: target.to_string() },
+    )
+}
+
+/// Constructs an [`Error`] out of the provided failed [`RollbackSymlink`]
+/// operation error.
+fn rollback_error<E>(e: E) -> Error
+where
+    E: fmt::Display + AsRef<io::Error>,
+{
+    match e.as_ref().kind() {
+        io::ErrorKind::NotFound
+        | io::ErrorKind::IsADirectory
+        | io::ErrorKind::NotADirectory
+        | io::ErrorKind::TooManyLinks => {
+            Error::new(S3ErrorCode::InvalidArgument, e.to_string())
+        }
+        io::ErrorKind::AlreadyExists
+            if caused_by::<PreconditionError>(e.as_ref()) =>
+        {
+            Error::new(S3ErrorCode::PreconditionFailed, e.to_string())
+        }
+        io::ErrorKind::ResourceBusy => linked_error(e.to_string()),
+        io::ErrorKind::PermissionDenied
+            if caused_by::<AccessDeniedError>(e.as_ref()) =>
+        {
+            Error::new(S3ErrorCode::AccessDenied, e.to_string())
+        }
+        _ => Error::internal("RollbackSymlink", e),
+    }
+}
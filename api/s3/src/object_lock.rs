@@ -0,0 +1,451 @@
+//! [Object Lock][0] of buckets and objects.
+//!
+//! [0]: https://docs.aws.amazon.com/AmazonS3/latest/userguide/object-lock.html
+
+use std::{fmt, io};
+
+use baza::{
+    AccessDeniedError, DefaultRetention, Exec, GetBucketConfig, GetObjectLock,
+    ObjectLock, ObjectLockStatus, RelativePath, Retention, RetentionMode,
+    SetBucketConfig, SetObjectLegalHold, SetObjectRetention, VersionId,
+};
+use hyper::{Body, Response, StatusCode};
+use s3_server::errors::S3ErrorCode;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::{
+    caused_by,
+    service::{self, Error},
+    versioning,
+};
+
+/// Number of days in a year, as counted by S3 for a [`DefaultRetention`].
+const YEAR_DAYS: u32 = 365;
+
+/// XML representation of a bucket [`ObjectLock`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct ObjectLockConfiguration {
+    /// Indicator whether the [`ObjectLock`] is enabled: always `Enabled`.
+    #[serde(rename = "ObjectLockEnabled", default)]
+    object_lock_enabled: Option<String>,
+
+    /// [`Rule`] of this [`ObjectLockConfiguration`].
+    #[serde(rename = "Rule", skip_serializing_if = "Option::is_none")]
+    rule: Option<Rule>,
+}
+
+/// XML representation of an [`ObjectLock`] rule.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Rule {
+    /// [`DefaultRetention`] of this [`Rule`].
+    #[serde(rename = "DefaultRetention")]
+    default_retention: DefaultRetentionXml,
+}
+
+/// XML representation of a [`DefaultRetention`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct DefaultRetentionXml {
+    /// [`RetentionMode`]: either `GOVERNANCE` or `COMPLIANCE`.
+    #[serde(rename = "Mode")]
+    mode: String,
+
+    /// Number of days the objects are retained for.
+    #[serde(rename = "Days", skip_serializing_if = "Option::is_none")]
+    days: Option<u32>,
+
+    /// Number of years the objects are retained for.
+    #[serde(rename = "Years", skip_serializing_if = "Option::is_none")]
+    years: Option<u32>,
+}
+
+/// XML representation of an object [`Retention`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct RetentionXml {
+    /// [`RetentionMode`]: either `GOVERNANCE` or `COMPLIANCE`.
+    #[serde(rename = "Mode", skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+
+    /// [RFC 3339] timestamp the object is retained until.
+    ///
+    /// [RFC 3339]: https://datatracker.ietf.org/doc/html/rfc3339
+    #[serde(
+        rename = "RetainUntilDate",
+        skip_serializing_if = "Option::is_none"
+    )]
+    retain_until_date: Option<String>,
+}
+
+/// XML representation of an object legal hold.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct LegalHold {
+    /// Status of this [`LegalHold`]: either `ON` or `OFF`.
+    #[serde(rename = "Status")]
+    status: String,
+}
+
+/// Handles the `PutObjectLockConfiguration` operation.
+///
+/// # Errors
+///
+/// - If the provided `body` is not a valid [`ObjectLockConfiguration`].
+/// - If the [`SetBucketConfig`] operation fails.
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn put_object_lock_configuration<S>(
+    storage: &S,
+    bucket: RelativePath,
+    body: &[u8],
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<ObjectLock>, Err: fmt::Display>,
+{
+    let config = service::parse_xml::<ObjectLockConfiguration>(body)?;
+    if config.object_lock_enabled.as_deref() != Some("Enabled") {
+        return Err(Error::new(
+            S3ErrorCode::MalformedXML,
+            "`ObjectLockEnabled` must be `Enabled`",
+        ));
+    }
+    let default_retention = config
+        .rule
+        .map(|r| {
+            let r = r.default_retention;
+            let days = match (r.days, r.years) {
+                (Some(days), None) => days,
+                (None, Some(years)) => years.saturating_mul(YEAR_DAYS),
+                _ => {
+                    return Err(Error::new(
+                        S3ErrorCode::MalformedXML,
+                        "Default retention must specify either `Days` or \
+                         `Years`",
+                    ));
+                }
+            };
+            if days == 0 {
+                return Err(Error::new(
+                    S3ErrorCode::InvalidArgument,
+                    "Default retention period must be a positive integer",
+                ));
+            }
+            Ok(DefaultRetention { mode: parse_mode(&r.mode)?, days })
+        })
+        .transpose()?;
+
+    storage
+        .exec(SetBucketConfig {
+            bucket,
+            config: Some(ObjectLock { default_retention }),
+        })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    Ok(Response::new(Body::empty()))
+}
+
+/// Handles the `GetObjectLockConfiguration` operation.
+///
+/// # Errors
+///
+/// - If the bucket has no [`ObjectLock`] configured.
+/// - If the [`GetBucketConfig`] operation fails.
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn get_object_lock_configuration<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            GetBucketConfig<ObjectLock>,
+            Ok = Option<ObjectLock>,
+            Err: fmt::Display,
+        >,
+{
+    let config = storage
+        .exec(GetBucketConfig::new(bucket))
+        .await
+        .map_err(|e| Error::internal("GetBucketConfig", e))?
+        .ok_or_else(|| {
+            Error::custom(
+                "ObjectLockConfigurationNotFoundError",
+                StatusCode::NOT_FOUND,
+                "Object Lock configuration does not exist for this bucket",
+            )
+        })?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "ObjectLockConfiguration",
+        &ObjectLockConfiguration {
+            object_lock_enabled: Some("Enabled".into()),
+            rule: config.default_retention.map(|r| Rule {
+                default_retention: DefaultRetentionXml {
+                    mode: format_mode(r.mode).into(),
+                    days: Some(r.days),
+                    years: None,
+                },
+            }),
+        },
+    )
+}
+
+/// Handles the `PutObjectRetention` operation.
+///
+/// An empty [`RetentionXml`] removes the existing [`Retention`] of the object.
+///
+/// # Errors
+///
+/// - If the provided `body` is not a valid [`RetentionXml`].
+/// - If the [`SetObjectRetention`] operation fails.
+#[tracing::instrument(skip_all, fields(%path, ?version_id))]
+pub(crate) async fn put_object_retention<S>(
+    storage: &S,
+    path: RelativePath,
+    version_id: Option<String>,
+    bypass_governance: bool,
+    body: &[u8],
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetObjectRetention, Err: fmt::Display + AsRef<io::Error>>,
+{
+    let xml = service::parse_xml::<RetentionXml>(body)?;
+    let retention = match (xml.mode, xml.retain_until_date) {
+        (None, None) => None,
+        (Some(mode), Some(date)) => {
+            let retain_until = OffsetDateTime::parse(&date, &Rfc3339)
+                .map_err(|e| {
+                    Error::new(
+                        S3ErrorCode::InvalidArgument,
+                        format!("Invalid `RetainUntilDate`: {e}"),
+                    )
+                })?
+                .into();
+            Some(Retention { mode: parse_mode(&mode)?, retain_until })
+        }
+        _ => {
+            return Err(Error::new(
+                S3ErrorCode::MalformedXML,
+                "Retention must specify both `Mode` and `RetainUntilDate`",
+            ));
+        }
+    };
+
+    storage
+        .exec(SetObjectRetention {
+            path,
+            version_id: parse_version_id(version_id)?,
+            retention,
+            bypass_governance,
+        })
+        .await
+        .map_err(|e| lock_error("SetObjectRetention", e))?;
+
+    tracing::info!("OK");
+    Ok(Response::new(Body::empty()))
+}
+
+/// Handles the `GetObjectRetention` operation.
+///
+/// # Errors
+///
+/// - If the object has no [`Retention`].
+/// - If the [`GetObjectLock`] operation fails.
+#[tracing::instrument(skip_all, fields(%path, ?version_id))]
+pub(crate) async fn get_object_retention<S>(
+    storage: &S,
+    path: RelativePath,
+    version_id: Option<String>,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            GetObjectLock,
+            Ok = Option<ObjectLockStatus>,
+            Err: fmt::Display + AsRef<io::Error>,
+        >,
+{
+    let retention = get_status(storage, path, version_id)
+        .await?
+        .retention
+        .ok_or_else(|| {
+            Error::custom(
+                "NoSuchObjectLockConfiguration",
+                StatusCode::NOT_FOUND,
+                "The specified object does not have a retention configured",
+            )
+        })?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "Retention",
+        &RetentionXml {
+            mode: Some(format_mode(retention.mode).into()),
+            retain_until_date: Some(versioning::format_time(
+                retention.retain_until,
+            )),
+        },
+    )
+}
+
+/// Handles the `PutObjectLegalHold` operation.
+///
+/// # Errors
+///
+/// - If the provided `body` is not a valid [`LegalHold`].
+/// - If the [`SetObjectLegalHold`] operation fails.
+#[tracing::instrument(skip_all, fields(%path, ?version_id))]
+pub(crate) async fn put_object_legal_hold<S>(
+    storage: &S,
+    path: RelativePath,
+    version_id: Option<String>,
+    body: &[u8],
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetObjectLegalHold, Err: fmt::Display + AsRef<io::Error>>,
+{
+    let enabled = match service::parse_xml::<LegalHold>(body)?.status.as_str() {
+        "ON" => true,
+        "OFF" => false,
+        status => {
+            return Err(Error::new(
+                S3ErrorCode::MalformedXML,
+                format!("Invalid legal hold `Status`: {status}"),
+            ));
+        }
+    };
+
+    storage
+        .exec(SetObjectLegalHold {
+            path,
+            version_id: parse_version_id(version_id)?,
+            enabled,
+        })
+        .await
+        .map_err(|e| lock_error("SetObjectLegalHold", e))?;
+
+    tracing::info!("OK");
+    Ok(Response::new(Body::empty()))
+}
+
+/// Handles the `GetObjectLegalHold` operation.
+///
+/// # Errors
+///
+/// If the [`GetObjectLock`] operation fails.
+#[tracing::instrument(skip_all, fields(%path, ?version_id))]
+pub(crate) async fn get_object_legal_hold<S>(
+    storage: &S,
+    path: RelativePath,
+    version_id: Option<String>,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            GetObjectLock,
+            Ok = Option<ObjectLockStatus>,
+            Err: fmt::Display + AsRef<io::Error>,
+        >,
+{
+    let status = get_status(storage, path, version_id).await?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "LegalHold",
+        &LegalHold {
+            status: if status.legal_hold { "ON" } else { "OFF" }.into(),
+        },
+    )
+}
+
+/// Executes the [`GetObjectLock`] operation for the provided object.
+///
+/// # Errors
+///
+/// - If the object doesn't exist.
+/// - If the [`GetObjectLock`] operation fails.
+async fn get_status<S>(
+    storage: &S,
+    path: RelativePath,
+    version_id: Option<String>,
+) -> Result<ObjectLockStatus, Error>
+where
+    S: Exec<
+            GetObjectLock,
+            Ok = Option<ObjectLockStatus>,
+            Err: fmt::Display + AsRef<io::Error>,
+        >,
+{
+    storage
+        .exec(GetObjectLock { path, version_id: parse_version_id(version_id)? })
+        .await
+        .map_err(|e| lock_error("GetObjectLock", e))?
+        .ok_or_else(|| {
+            Error::new(
+                S3ErrorCode::NoSuchKey,
+                "The specified key does not exist",
+            )
+        })
+}
+
+/// Parses the provided `versionId` query parameter into a [`VersionId`].
+///
+/// # Errors
+///
+/// If the provided `versionId` is not a valid [`VersionId`].
+fn parse_version_id(id: Option<String>) -> Result<Option<VersionId>, Error> {
+    id.map(|id| {
+        VersionId::try_from(id).map_err(|e| {
+            Error::new(
+                S3ErrorCode::InvalidArgument,
+                format!("Invalid versionId: {e}"),
+            )
+        })
+    })
+    .transpose()
+}
+
+/// Parses the provided XML representation of a [`RetentionMode`].
+///
+/// # Errors
+///
+/// If the provided `mode` is neither `GOVERNANCE` nor `COMPLIANCE`.
+fn parse_mode(mode: &str) -> Result<RetentionMode, Error> {
+    match mode {
+        "GOVERNANCE" => Ok(RetentionMode::Governance),
+        "COMPLIANCE" => Ok(RetentionMode::Compliance),
+        _ => Err(Error::new(
+            S3ErrorCode::MalformedXML,
+            format!("Invalid retention `Mode`: {mode}"),
+        )),
+    }
+}
+
+/// Returns the XML representation of the provided [`RetentionMode`].
+const fn format_mode(mode: RetentionMode) -> &'static str {
+    match mode {
+        RetentionMode::Governance => "GOVERNANCE",
+        RetentionMode::Compliance => "COMPLIANCE",
+    }
+}
+
+/// Constructs an [`Error`] out of the provided failed [`ObjectLock`]
+/// `op`eration error.
+fn lock_error<E>(op: &str, e: E) -> Error
+where
+    E: fmt::Display + AsRef<io::Error>,
+{
+    match e.as_ref().kind() {
+        io::ErrorKind::InvalidInput => Error::new(
+            S3ErrorCode::InvalidRequest,
+            "Bucket is missing Object Lock Configuration",
+        ),
+        io::ErrorKind::NotFound => Error::new(
+            S3ErrorCode::NoSuchKey,
+            "The specified key does not exist",
+        ),
+        io::ErrorKind::PermissionDenied
+            if caused_by::<AccessDeniedError>(e.as_ref()) =>
+        {
+            Error::new(S3ErrorCode::AccessDenied, e.to_string())
+        }
+        _ => Error::internal(op, e),
+    }
+}
@@ -0,0 +1,711 @@
+//! [POST Object][0] uploads via HTML forms.
+//!
+//! [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/RESTObjectPOST.html
+
+use std::{
+    convert, fmt, io,
+    ops::RangeInclusive,
+    sync::{Arc, OnceLock},
+};
+
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use baza::{
+    CreateFile, Encryption, Exec, FreeSpace, GetFreeSpace, RelativePath,
+    futures::{Stream, StreamExt as _, TryStreamExt as _, stream},
+};
+use hyper::{Body, Method, Request, Response, StatusCode, body::Bytes, header};
+use percent_encoding::utf8_percent_encode;
+use s3_server::{dto, errors::S3ErrorCode, path::S3Path};
+use serde::Deserialize;
+use serde_json::Value as Json;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::{
+    map_storage_error,
+    service::{Error, xml_escape},
+    signature::{self, Credentials, Scope, URI_ENCODE_SET},
+    space,
+};
+
+/// Maximum size of a single form field preceding the uploaded file.
+const MAX_FIELD_SIZE: u64 = 20 * 1024;
+
+/// Checks whether the provided [`Request`] is a [POST Object][0] one.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/RESTObjectPOST.html
+#[must_use]
+pub(crate) fn is_match(req: &Request<Body>) -> bool {
+    req.method() == Method::POST
+        && bucket(req).is_some()
+        && req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| {
+                v.to_ascii_lowercase().starts_with("multipart/form-data")
+            })
+}
+
+/// Handles the provided [POST Object][0] [`Request`] by executing a
+/// [`CreateFile`] operation, once the form is authenticated and satisfies its
+/// [`Policy`].
+///
+/// # Errors
+///
+/// - If the form is malformed.
+/// - If the form is not signed properly.
+/// - If the form doesn't satisfy its [`Policy`].
+/// - If the storage has not enough free space for the uploaded file.
+/// - If the [`CreateFile`] operation fails.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/RESTObjectPOST.html
+#[tracing::instrument(skip_all, fields(bucket = bucket(&req)))]
+pub(crate) async fn handle<S, E>(
+    storage: &S,
+    credentials: &Credentials,
+    req: Request<Body>,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<CreateFile<dto::ByteStream>, Err = E>
+        + Exec<GetFreeSpace, Ok = FreeSpace, Err: fmt::Display>,
+    E: fmt::Display + AsRef<io::Error>,
+{
+    let bucket = bucket(&req).unwrap_or_default().to_owned();
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("localhost")
+        .to_owned();
+
+    let boundary = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| multer::parse_boundary(v).ok())
+        .ok_or_else(|| malformed("Missing multipart boundary"))?;
+    let mut multipart = multer::Multipart::with_constraints(
+        req.into_body(),
+        boundary,
+        multer::Constraints::new().size_limit(
+            multer::SizeLimit::new()
+                .per_field(MAX_FIELD_SIZE)
+                .for_field("file", u64::MAX),
+        ),
+    );
+
+    // All the fields following the file are ignored.
+    let mut form = Form::default();
+    let file = loop {
+        let field = multipart
+            .next_field()
+            .await
+            .map_err(|e| malformed(e.to_string()))?
+            .ok_or_else(|| {
+                Error::new(
+                    S3ErrorCode::IncorrectNumberOfFilesInPostRequest,
+                    "POST requires exactly one file upload per request",
+                )
+            })?;
+        let name = field.name().unwrap_or_default().to_ascii_lowercase();
+        if name == "file" {
+            break field;
+        }
+        let value = field.text().await.map_err(|e| malformed(e.to_string()))?;
+        form.0.push((name, value));
+    };
+
+    authenticate(credentials, &form)?;
+    let length = Policy::decode(form.get("policy").unwrap_or_default())?
+        .check(OffsetDateTime::now_utc(), &bucket, &form)?;
+
+    let key = form
+        .get("key")
+        .ok_or_else(|| {
+            Error::new(
+                S3ErrorCode::InvalidArgument,
+                "Bucket POST must contain a field named 'key'",
+            )
+        })?
+        .replace("${filename}", file.file_name().unwrap_or_default());
+    let path = RelativePath::try_from(bucket.clone())
+        .and_then(|b| Ok(b.join(RelativePath::try_from(key.clone())?)))
+        .map_err(|e| {
+            Error::new(
+                S3ErrorCode::InvalidArgument,
+                format!("Invalid key: {e}"),
+            )
+        })?;
+
+    let encryption = match form.get("x-amz-server-side-encryption") {
+        None => None,
+        Some("AES256") => Some(Encryption::Managed),
+        Some(sse) => {
+            return Err(Error::new(
+                S3ErrorCode::InvalidArgument,
+                format!("Server-side encryption `{sse}` is not supported"),
+            ));
+        }
+    };
+
+    let content_type = form
+        .get("content-type")
+        .map(ToOwned::to_owned)
+        .or_else(|| file.content_type().map(ToString::to_string));
+
+    // Form parts rarely declare their length, so the upper bound allowed by
+    // the `Policy` is checked instead.
+    let declared = file
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok())
+        .or_else(|| Some(*length.end()).filter(|&max| max != u64::MAX))
+        .map(|l| i64::try_from(l).unwrap_or(i64::MAX));
+    space::check_available(storage, declared).await?;
+
+    let violation = Arc::new(OnceLock::new());
+    let bytes = limit_length(
+        file.map_err(io::Error::other),
+        length,
+        Arc::clone(&violation),
+    );
+    storage
+        .exec(CreateFile {
+            path,
+            bytes: dto::ByteStream::new(bytes),
+            encryption,
+            content_type,
+        })
+        .await
+        .map_err(|e| match violation.get() {
+            Some(&code) => Error::new(
+                code,
+                "Your proposed upload size doesn't match the allowed range",
+            ),
+            None => map_storage_error(
+                "CreateFile operation failed",
+                e,
+                Error::new,
+                convert::identity,
+            ),
+        })?;
+
+    tracing::info!("OK");
+    Ok(success_response(&form, &host, &bucket, &key))
+}
+
+/// Extracts the bucket name from the provided [`Request`], if it targets a
+/// bucket.
+fn bucket(req: &Request<Body>) -> Option<&str> {
+    match S3Path::try_from_path(req.uri().path()).ok()? {
+        S3Path::Bucket { bucket } => Some(bucket),
+        S3Path::Root | S3Path::Object { .. } => None,
+    }
+}
+
+/// Constructs an [`Error`] of a malformed form.
+fn malformed(msg: impl Into<String>) -> Error {
+    Error::new(S3ErrorCode::MalformedPOSTRequest, msg)
+}
+
+/// Checks the signature of the provided [`Form`]'s policy.
+///
+/// # Errors
+///
+/// If the [`Form`] is not signed, or its signature is invalid.
+fn authenticate(credentials: &Credentials, form: &Form) -> Result<(), Error> {
+    let policy = form.get("policy").ok_or_else(|| {
+        Error::new(
+            S3ErrorCode::AccessDenied,
+            "Anonymous POST requests are not allowed",
+        )
+    })?;
+    if form.get("x-amz-algorithm") != Some(signature::ALGORITHM) {
+        return Err(Error::new(
+            S3ErrorCode::InvalidArgument,
+            format!("`x-amz-algorithm` must be `{}`", signature::ALGORITHM),
+        ));
+    }
+    let scope = form
+        .get("x-amz-credential")
+        .and_then(Scope::parse)
+        .ok_or_else(|| {
+            Error::new(
+                S3ErrorCode::InvalidArgument,
+                "Invalid `x-amz-credential`",
+            )
+        })?;
+    let secret_key = credentials.secret_key(scope.access_key).ok_or_else(|| {
+        Error::new(
+            S3ErrorCode::InvalidAccessKeyId,
+            "The AWS Access Key Id you provided does not exist in our records",
+        )
+    })?;
+
+    let signature = form.get("x-amz-signature").unwrap_or_default();
+    if !scope.verify(secret_key, policy, signature) {
+        return Err(Error::new(
+            S3ErrorCode::SignatureDoesNotMatch,
+            "The request signature we calculated does not match the signature \
+             you provided",
+        ));
+    }
+    Ok(())
+}
+
+/// Wraps the provided [`Stream`] of `bytes` to fail once its total length goes
+/// out of the provided `range`.
+///
+/// The [`S3ErrorCode`] describing the failure is stored into the provided
+/// `violation` slot.
+fn limit_length<S>(
+    bytes: S,
+    range: RangeInclusive<u64>,
+    violation: Arc<OnceLock<S3ErrorCode>>,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + Sync + Unpin + 'static,
+{
+    let fail = move |code| {
+        _ = violation.set(code);
+        Some((Err(io::Error::other(code.as_static_str())), None))
+    };
+    stream::unfold(Some((bytes, 0)), move |state| {
+        let (min, max) = (*range.start(), *range.end());
+        let fail = fail.clone();
+        async move {
+            let (mut bytes, len) = state?;
+            match bytes.next().await {
+                Some(Ok(chunk)) => {
+                    let len = len + chunk.len() as u64;
+                    if len > max {
+                        fail(S3ErrorCode::EntityTooLarge)
+                    } else {
+                        Some((Ok(chunk), Some((bytes, len))))
+                    }
+                }
+                Some(Err(e)) => Some((Err(e), None)),
+                None if len < min => fail(S3ErrorCode::EntityTooSmall),
+                None => None,
+            }
+        }
+    })
+}
+
+/// Builds a [`Response`] to a successful upload, as requested by the
+/// `success_action_redirect` or `success_action_status` field of the provided
+/// [`Form`].
+fn success_response(
+    form: &Form,
+    host: &str,
+    bucket: &str,
+    key: &str,
+) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+
+    if let Some(url) = form
+        .get("success_action_redirect")
+        .filter(|url| url.parse::<hyper::Uri>().is_ok())
+    {
+        let sep = if url.contains('?') { '&' } else { '?' };
+        let location = format!(
+            "{url}{sep}bucket={}&key={}",
+            utf8_percent_encode(bucket, URI_ENCODE_SET),
+            utf8_percent_encode(key, URI_ENCODE_SET),
+        );
+        *resp.status_mut() = StatusCode::SEE_OTHER;
+        if let Ok(location) = header::HeaderValue::from_str(&location) {
+            _ = resp.headers_mut().insert(header::LOCATION, location);
+        }
+        return resp;
+    }
+
+    *resp.status_mut() = match form.get("success_action_status") {
+        Some("200") => StatusCode::OK,
+        Some("201") => {
+            let location = format!(
+                "http://{host}/{bucket}/{}",
+                key.split('/')
+                    .map(|s| utf8_percent_encode(s, URI_ENCODE_SET).to_string())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+            );
+            *resp.body_mut() = Body::from(format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+                 <PostResponse>\
+                 <Location>{}</Location>\
+                 <Bucket>{}</Bucket>\
+                 <Key>{}</Key>\
+                 </PostResponse>",
+                xml_escape(&location),
+                xml_escape(bucket),
+                xml_escape(key),
+            ));
+            _ = resp.headers_mut().insert(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static("application/xml"),
+            );
+            StatusCode::CREATED
+        }
+        _ => StatusCode::NO_CONTENT,
+    };
+    resp
+}
+
+/// Text fields of a [POST Object][0] form, preceding the uploaded file.
+///
+/// Field names are stored lowercased, as they're case-insensitive.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/RESTObjectPOST.html
+#[derive(Clone, Debug, Default)]
+struct Form(Vec<(String, String)>);
+
+impl Form {
+    /// Returns the value of the field with the provided lowercased `name`.
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// [POST policy][0] restricting what can be uploaded with a [`Form`].
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/sigv4-post-example.html
+#[derive(Clone, Debug)]
+struct Policy {
+    /// Moment this [`Policy`] expires at.
+    expiration: OffsetDateTime,
+
+    /// [`Condition`]s the [`Form`] must satisfy.
+    conditions: Vec<Condition>,
+}
+
+impl Policy {
+    /// Decodes a [`Policy`] out of the provided base64-encoded JSON document.
+    ///
+    /// # Errors
+    ///
+    /// If the provided document is not a valid [`Policy`].
+    fn decode(encoded: &str) -> Result<Self, Error> {
+        /// Raw JSON representation of a [`Policy`].
+        #[derive(Deserialize)]
+        struct Raw {
+            /// Expiration moment in [RFC 3339] format.
+            ///
+            /// [RFC 3339]: https://www.rfc-editor.org/rfc/rfc3339
+            expiration: String,
+
+            /// JSON representations of [`Condition`]s.
+            conditions: Vec<Json>,
+        }
+
+        let invalid = |msg| Error::new(S3ErrorCode::InvalidPolicyDocument, msg);
+
+        let raw = BASE64_STANDARD
+            .decode(encoded)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Raw>(&json).ok())
+            .ok_or_else(|| invalid("Invalid Policy: Invalid JSON"))?;
+        Ok(Self {
+            expiration: OffsetDateTime::parse(&raw.expiration, &Rfc3339)
+                .map_err(|_| {
+                    invalid("Invalid Policy: Invalid 'expiration' value")
+                })?,
+            conditions: raw
+                .conditions
+                .iter()
+                .map(|c| {
+                    Condition::parse(c).ok_or_else(|| {
+                        invalid("Invalid Policy: Invalid condition")
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Checks whether the provided [`Form`] targeting the provided `bucket`
+    /// satisfies this [`Policy`] at the provided moment of time.
+    ///
+    /// Returns the allowed range of the uploaded file length.
+    ///
+    /// # Errors
+    ///
+    /// - If this [`Policy`] has expired.
+    /// - If any of [`Condition`]s is not satisfied.
+    /// - If any of [`Form`] fields is not covered by [`Condition`]s.
+    fn check(
+        &self,
+        now: OffsetDateTime,
+        bucket: &str,
+        form: &Form,
+    ) -> Result<RangeInclusive<u64>, Error> {
+        let denied = |msg: String| {
+            Error::new(
+                S3ErrorCode::AccessDenied,
+                format!("Invalid according to Policy: {msg}"),
+            )
+        };
+
+        if now >= self.expiration {
+            return Err(denied("Policy expired".into()));
+        }
+
+        let value = |field: &str| {
+            if field == "bucket" { Some(bucket) } else { form.get(field) }
+        };
+        let mut length = 0..=u64::MAX;
+        for cond in &self.conditions {
+            let satisfied = match cond {
+                Condition::Eq { field, value: expected } => {
+                    value(field) == Some(expected.as_str())
+                }
+                Condition::StartsWith { field, prefix } => {
+                    value(field).is_some_and(|v| v.starts_with(prefix.as_str()))
+                }
+                Condition::ContentLengthRange(range) => {
+                    length = range.clone();
+                    true
+                }
+            };
+            if !satisfied {
+                return Err(denied(format!("Policy Condition failed: {cond}")));
+            }
+        }
+
+        if let Some((name, _)) = form.0.iter().find(|(name, _)| {
+            !matches!(name.as_str(), "policy" | "x-amz-signature" | "file")
+                && !name.starts_with("x-ignore-")
+                && !self.conditions.iter().any(|c| c.field() == Some(name))
+        }) {
+            return Err(denied(format!("Extra input fields: {name}")));
+        }
+
+        Ok(length)
+    }
+}
+
+/// Condition of a [`Policy`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Condition {
+    /// Value of the `field` must be equal to the specified one.
+    Eq {
+        /// Lowercased name of the field.
+        field: String,
+
+        /// Expected value of the field.
+        value: String,
+    },
+
+    /// Value of the `field` must start with the specified `prefix`.
+    StartsWith {
+        /// Lowercased name of the field.
+        field: String,
+
+        /// Expected prefix of the field value.
+        prefix: String,
+    },
+
+    /// Length of the uploaded file must be in the specified range.
+    ContentLengthRange(RangeInclusive<u64>),
+}
+
+impl Condition {
+    /// Parses a [`Condition`] out of its JSON representation.
+    fn parse(json: &Json) -> Option<Self> {
+        let field = |f: &Json| {
+            f.as_str()?.strip_prefix('$').map(str::to_ascii_lowercase)
+        };
+        let string = |s: &Json| s.as_str().map(ToOwned::to_owned);
+        let number = |n: &Json| match n {
+            Json::Number(n) => n.as_u64(),
+            Json::String(s) => s.parse().ok(),
+            _ => None,
+        };
+
+        match json {
+            Json::Object(map) if map.len() == 1 => {
+                let (name, value) = map.iter().next()?;
+                Some(Self::Eq {
+                    field: name.trim_start_matches('$').to_ascii_lowercase(),
+                    value: string(value)?,
+                })
+            }
+            Json::Array(items) => match items.as_slice() {
+                [op, f, v] if op.as_str()?.eq_ignore_ascii_case("eq") => {
+                    Some(Self::Eq { field: field(f)?, value: string(v)? })
+                }
+                [op, f, p]
+                    if op.as_str()?.eq_ignore_ascii_case("starts-with") =>
+                {
+                    Some(Self::StartsWith {
+                        field: field(f)?,
+                        prefix: string(p)?,
+                    })
+                }
+                [op, min, max]
+                    if op
+                        .as_str()?
+                        .eq_ignore_ascii_case("content-length-range") =>
+                {
+                    Some(Self::ContentLengthRange(number(min)?..=number(max)?))
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the lowercased name of the field this [`Condition`] is applied
+    /// to, if any.
+    fn field(&self) -> Option<&str> {
+        match self {
+            Self::Eq { field, .. } | Self::StartsWith { field, .. } => {
+                Some(field)
+            }
+            Self::ContentLengthRange(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eq { field, value } => {
+                write!(f, "[\"eq\", \"${field}\", \"{value}\"]")
+            }
+            Self::StartsWith { field, prefix } => {
+                write!(f, "[\"starts-with\", \"${field}\", \"{prefix}\"]")
+            }
+            Self::ContentLengthRange(range) => write!(
+                f,
+                "[\"content-length-range\", {}, {}]",
+                range.start(),
+                range.end(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod policy_spec {
+    use base64::{Engine as _, prelude::BASE64_STANDARD};
+    use time::{Duration, OffsetDateTime, macros::datetime};
+
+    use super::{Form, Policy};
+
+    fn policy(json: &str) -> Policy {
+        Policy::decode(&BASE64_STANDARD.encode(json)).unwrap()
+    }
+
+    fn form(fields: &[(&str, &str)]) -> Form {
+        Form(
+            fields
+                .iter()
+                .map(|(n, v)| ((*n).to_owned(), (*v).to_owned()))
+                .collect(),
+        )
+    }
+
+    const NOW: OffsetDateTime = datetime!(2022-08-01 12:00 UTC);
+
+    #[test]
+    fn rejects_invalid_documents() {
+        for json in [
+            "not a json",
+            r#"{"conditions": []}"#,
+            r#"{"expiration": "tomorrow", "conditions": []}"#,
+            r#"{"expiration": "2022-08-02T00:00:00Z", "conditions": [1]}"#,
+            r#"{"expiration": "2022-08-02T00:00:00Z",
+                "conditions": [["ends-with", "$key", "a"]]}"#,
+            r#"{"expiration": "2022-08-02T00:00:00Z",
+                "conditions": [["content-length-range", "a", 1]]}"#,
+        ] {
+            let err =
+                Policy::decode(&BASE64_STANDARD.encode(json)).unwrap_err();
+            assert!(
+                err.code == "InvalidPolicyDocument",
+                "wrong error for `{json}`: {err}",
+            );
+        }
+        assert!(Policy::decode("%%%").is_err());
+    }
+
+    #[test]
+    fn rejects_expired() {
+        let p = policy(
+            r#"{"expiration": "2022-08-01T12:00:00.000Z", "conditions": []}"#,
+        );
+
+        assert!(p.check(NOW - Duration::SECOND, "b", &form(&[])).is_ok());
+        assert_eq!(
+            p.check(NOW, "b", &form(&[])).unwrap_err().code,
+            "AccessDenied",
+        );
+    }
+
+    #[test]
+    fn checks_conditions() {
+        let p = policy(
+            r#"{"expiration": "2022-08-02T00:00:00Z", "conditions": [
+                {"bucket": "uploads"},
+                ["starts-with", "$key", "user/"],
+                ["eq", "$Content-Type", "image/png"],
+                {"x-amz-credential": "baza/20220801/us-east-1/s3/aws4_request"}
+            ]}"#,
+        );
+        let ok = [
+            ("key", "user/${filename}"),
+            ("content-type", "image/png"),
+            ("x-amz-credential", "baza/20220801/us-east-1/s3/aws4_request"),
+            ("policy", "..."),
+            ("x-amz-signature", "..."),
+            ("x-ignore-me", "..."),
+        ];
+
+        assert!(p.check(NOW, "uploads", &form(&ok)).is_ok());
+        assert!(p.check(NOW, "other", &form(&ok)).is_err());
+
+        let mut wrong_key = ok;
+        wrong_key[0].1 = "admin/file";
+        assert!(p.check(NOW, "uploads", &form(&wrong_key)).is_err());
+
+        let mut wrong_type = ok;
+        wrong_type[1].1 = "image/jpeg";
+        assert!(p.check(NOW, "uploads", &form(&wrong_type)).is_err());
+
+        assert!(p.check(NOW, "uploads", &form(&ok[1..])).is_err());
+    }
+
+    #[test]
+    fn rejects_extra_fields() {
+        let p = policy(
+            r#"{"expiration": "2022-08-02T00:00:00Z", "conditions": [
+                ["starts-with", "$key", ""]
+            ]}"#,
+        );
+
+        assert!(p.check(NOW, "b", &form(&[("key", "a")])).is_ok());
+        assert!(
+            p.check(NOW, "b", &form(&[("key", "a"), ("acl", "private")]))
+                .is_err(),
+        );
+    }
+
+    #[test]
+    fn returns_content_length_range() {
+        let p = policy(
+            r#"{"expiration": "2022-08-02T00:00:00Z", "conditions": [
+                ["content-length-range", 10, "100"]
+            ]}"#,
+        );
+
+        assert_eq!(p.check(NOW, "b", &form(&[])).unwrap(), 10..=100);
+        assert_eq!(
+            policy(
+                r#"{"expiration": "2022-08-02T00:00:00Z", "conditions": []}"#
+            )
+            .check(NOW, "b", &form(&[]))
+            .unwrap(),
+            0..=u64::MAX,
+        );
+    }
+}
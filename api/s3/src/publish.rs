@@ -0,0 +1,116 @@
+//! Atomic publishing of symlink sets.
+//!
+//! S3 has no API for symlinks at all, so a set of them is published via the
+//! `?symlinks` subresource of the directory to publish them in, accepting a
+//! manifest of the symlinks.
+
+use std::{fmt, io};
+
+use baza::{Exec, PreconditionError, PublishSymlinks, RelativePath};
+use hyper::{Body, Response};
+use s3_server::errors::S3ErrorCode;
+use serde::Deserialize;
+
+use crate::{
+    caused_by,
+    service::{self, Error},
+};
+
+/// Maximum number of [`Symlink`]s in a [`SymlinkManifest`].
+const MAX_SYMLINKS: usize = 1000;
+
+/// XML representation of a [`PublishSymlinks`] manifest.
+#[derive(Debug, Deserialize)]
+struct SymlinkManifest {
+    /// [`Symlink`]s to publish.
+    #[serde(rename = "Symlink", default)]
+    symlinks: Vec<Symlink>,
+}
+
+/// XML representation of a single symlink in a [`SymlinkManifest`].
+#[derive(Debug, Deserialize)]
+struct Symlink {
+    /// Key of the symlink, relative to the published directory.
+    #[serde(rename = "Key")]
+    key: String,
+
+    /// Bucket and key of the original source file the symlink points to.
+    #[serde(rename = "Target")]
+    target: String,
+}
+
+/// Handles the `PublishSymlinks` operation.
+///
+/// # Errors
+///
+/// - If the provided `body` is not a valid [`SymlinkManifest`].
+/// - If the [`PublishSymlinks`] operation fails.
+#[tracing::instrument(skip_all, fields(%dir))]
+pub(crate) async fn publish_symlinks<S>(
+    storage: &S,
+    dir: RelativePath,
+    body: &[u8],
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<PublishSymlinks, Err: fmt::Display + AsRef<io::Error>>,
+{
+    let manifest = service::parse_xml::<SymlinkManifest>(body)?;
+    if manifest.symlinks.len() > MAX_SYMLINKS {
+        return Err(Error::new(
+            S3ErrorCode::InvalidArgument,
+            format!("Symlink manifest cannot exceed {MAX_SYMLINKS} symlinks"),
+        ));
+    }
+    let links = manifest
+        .symlinks
+        .into_iter()
+        .map(|s| {
+            Ok((parse_path("Key", s.key)?, parse_path("Target", s.target)?))
+        })
+        .collect::<Result<_, Error>>()?;
+
+    storage
+        .exec(PublishSymlinks { dir, links })
+        .await
+        .map_err(publish_error)?;
+
+    tracing::info!("OK");
+    Ok(Response::new(Body::empty()))
+}
+
+/// Parses the provided `value` of the provided [`Symlink`] `field` into a
+/// [`RelativePath`].
+///
+/// # Errors
+///
+/// If the provided `value` is not a valid [`RelativePath`].
+fn parse_path(field: &str, value: String) -> Result<RelativePath, Error> {
+    value.try_into().map_err(|e| {
+        Error::new(
+            S3ErrorCode::InvalidArgument,
+            format!("Invalid symlink `{field}`: {e}"),
+        )
+    })
+}
+
+/// Constructs an [`Error`] out of the provided failed [`PublishSymlinks`]
+/// operation error.
+fn publish_error<E>(e: E) -> Error
+where
+    E: fmt::Display + AsRef<io::Error>,
+{
+    match e.as_ref().kind() {
+        io::ErrorKind::InvalidInput
+        | io::ErrorKind::NotFound
+        | io::ErrorKind::IsADirectory
+        | io::ErrorKind::TooManyLinks => {
+            Error::new(S3ErrorCode::InvalidArgument, e.to_string())
+        }
+        io::ErrorKind::AlreadyExists
+            if caused_by::<PreconditionError>(e.as_ref()) =>
+        {
+            Error::new(S3ErrorCode::PreconditionFailed, e.to_string())
+        }
+        _ => Error::internal("PublishSymlinks", e),
+    }
+}
@@ -0,0 +1,191 @@
+//! Quotas of buckets.
+//!
+//! S3 has no API for bucket quotas, so the [`Quota`] is configured via the
+//! `?quota` subresource of a bucket, in the same manner as other bucket
+//! configurations are.
+
+use std::{fmt, io};
+
+use baza::{
+    Exec, GetBucketConfig, GetBucketUsage, GetQuotaAllowance, Quota,
+    RelativePath, SetBucketConfig, Usage,
+};
+use hyper::{Body, Response, StatusCode};
+use s3_server::errors::{S3Error, S3ErrorCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    service::{self, Error},
+    storage_error,
+};
+
+/// XML representation of a bucket [`Quota`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct QuotaConfiguration {
+    /// Maximum number of bytes stored in the bucket.
+    #[serde(rename = "MaxBytes", skip_serializing_if = "Option::is_none")]
+    max_bytes: Option<u64>,
+
+    /// Maximum number of objects stored in the bucket.
+    #[serde(rename = "MaxObjects", skip_serializing_if = "Option::is_none")]
+    max_objects: Option<u64>,
+
+    /// Current [`Usage`] of the bucket.
+    ///
+    /// Ignored when the [`QuotaConfiguration`] is put.
+    #[serde(rename = "Usage", default, skip_deserializing)]
+    usage: Option<UsageXml>,
+}
+
+/// XML representation of a bucket [`Usage`].
+#[derive(Debug, Serialize)]
+struct UsageXml {
+    /// Number of bytes stored in the bucket.
+    #[serde(rename = "Bytes")]
+    bytes: u64,
+
+    /// Number of objects stored in the bucket.
+    #[serde(rename = "Objects")]
+    objects: u64,
+}
+
+/// Creates a new [`Error`] of a bucket [`Quota`] being exceeded.
+#[must_use]
+pub(crate) fn exceeded_error(message: impl Into<String>) -> Error {
+    Error::custom("QuotaExceeded", StatusCode::FORBIDDEN, message)
+}
+
+/// Ensures the [`Quota`] of the bucket of the provided `path` allows an upload
+/// of the provided `length` (if known) to be stored there.
+///
+/// # Errors
+///
+/// - If the [`GetQuotaAllowance`] operation fails.
+/// - If the `length` exceeds the bytes allowed by the [`Quota`].
+pub(crate) async fn ensure_allowed<S>(
+    storage: &S,
+    path: &RelativePath,
+    length: Option<i64>,
+) -> Result<(), S3Error>
+where
+    S: Exec<
+            GetQuotaAllowance,
+            Ok = Option<u64>,
+            Err: fmt::Display + AsRef<io::Error>,
+        >,
+{
+    let Some(length) = length.and_then(|l| u64::try_from(l).ok()) else {
+        return Ok(());
+    };
+    let allowance = storage
+        .exec(GetQuotaAllowance { path: path.clone() })
+        .await
+        .map_err(|e| storage_error("GetQuotaAllowance operation failed", e))?;
+
+    if let Some(allowed) = allowance.filter(|&a| length > a) {
+        return Err(exceeded_error(format!(
+            "Object of {length} bytes exceeds {allowed} bytes allowed by \
+             quota of `{}` bucket",
+            path.bucket(),
+        ))
+        .into_s3_error());
+    }
+    Ok(())
+}
+
+/// Handles the `PutBucketQuota` operation.
+///
+/// # Errors
+///
+/// - If the provided `body` is not a valid [`QuotaConfiguration`].
+/// - If the [`SetBucketConfig`] operation fails.
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn put_bucket_quota<S>(
+    storage: &S,
+    bucket: RelativePath,
+    body: &[u8],
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<Quota>, Err: fmt::Display>,
+{
+    let config = service::parse_xml::<QuotaConfiguration>(body)?;
+    if config.max_bytes.is_none() && config.max_objects.is_none() {
+        return Err(Error::new(
+            S3ErrorCode::MalformedXML,
+            "Quota must limit either `MaxBytes` or `MaxObjects`",
+        ));
+    }
+    let quota =
+        Quota { max_bytes: config.max_bytes, max_objects: config.max_objects };
+
+    storage
+        .exec(SetBucketConfig { bucket, config: Some(quota) })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    Ok(Response::new(Body::empty()))
+}
+
+/// Handles the `GetBucketQuota` operation, returning the current [`Usage`] of
+/// the bucket along with its [`Quota`] (if any).
+///
+/// # Errors
+///
+/// If the [`GetBucketConfig`] or [`GetBucketUsage`] operation fails.
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn get_bucket_quota<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<GetBucketConfig<Quota>, Ok = Option<Quota>, Err: fmt::Display>
+        + Exec<GetBucketUsage, Ok = Usage, Err: fmt::Display>,
+{
+    let quota = storage
+        .exec(GetBucketConfig::new(bucket.clone()))
+        .await
+        .map_err(|e| Error::internal("GetBucketConfig", e))?
+        .unwrap_or_default();
+    let usage = storage
+        .exec(GetBucketUsage { bucket })
+        .await
+        .map_err(|e| Error::internal("GetBucketUsage", e))?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "QuotaConfiguration",
+        &QuotaConfiguration {
+            max_bytes: quota.max_bytes,
+            max_objects: quota.max_objects,
+            usage: Some(UsageXml {
+                bytes: usage.bytes,
+                objects: usage.objects,
+            }),
+        },
+    )
+}
+
+/// Handles the `DeleteBucketQuota` operation.
+///
+/// # Errors
+///
+/// If the [`SetBucketConfig`] operation fails.
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn delete_bucket_quota<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<Quota>, Err: fmt::Display>,
+{
+    storage
+        .exec(SetBucketConfig::<Quota> { bucket, config: None })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = StatusCode::NO_CONTENT;
+    Ok(resp)
+}
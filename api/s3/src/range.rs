@@ -0,0 +1,90 @@
+//! [Range][0] requests of objects.
+//!
+//! [0]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Range_requests
+
+use s3_server::errors::{S3Error, S3ErrorCode};
+
+/// Parses the provided `Range` header value into the first and the last
+/// (inclusive) bytes of an object of the provided `size`.
+///
+/// Returns [`None`] if the `range` is malformed or specifies multiple ranges,
+/// so should be ignored, as S3 does.
+///
+/// # Errors
+///
+/// With [`S3ErrorCode::InvalidRange`], if the `range` cannot be satisfied.
+pub(crate) fn parse(
+    range: &str,
+    size: u64,
+) -> Result<Option<(u64, u64)>, S3Error> {
+    let Some(spec) = range.strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    let Some((first, last)) =
+        spec.split_once('-').filter(|_| !spec.contains(','))
+    else {
+        return Ok(None);
+    };
+    let parse = |s: &str| s.trim().parse::<u64>().ok();
+
+    let bounds = match (first.trim().is_empty(), last.trim().is_empty()) {
+        (true, false) => {
+            let Some(suffix) = parse(last) else {
+                return Ok(None);
+            };
+            (suffix > 0 && size > 0)
+                .then(|| (size.saturating_sub(suffix), size - 1))
+        }
+        (false, true) => {
+            let Some(first) = parse(first) else {
+                return Ok(None);
+            };
+            (first < size).then(|| (first, size - 1))
+        }
+        (false, false) => {
+            let (Some(first), Some(last)) = (parse(first), parse(last)) else {
+                return Ok(None);
+            };
+            if first > last {
+                return Ok(None);
+            }
+            (first < size).then(|| (first, last.min(size - 1)))
+        }
+        (true, true) => return Ok(None),
+    };
+    bounds.map(Some).ok_or_else(|| {
+        S3Error::new(
+            S3ErrorCode::InvalidRange,
+            "The requested range is not satisfiable",
+        )
+    })
+}
+
+#[cfg(test)]
+mod parse_spec {
+    use super::parse;
+
+    #[test]
+    fn parses_bounded_and_open_ranges() {
+        assert_eq!(parse("bytes=0-9", 100).ok(), Some(Some((0, 9))));
+        assert_eq!(parse("bytes=90-200", 100).ok(), Some(Some((90, 99))));
+        assert_eq!(parse("bytes=10-", 100).ok(), Some(Some((10, 99))));
+        assert_eq!(parse("bytes=-10", 100).ok(), Some(Some((90, 99))));
+        assert_eq!(parse("bytes=-200", 100).ok(), Some(Some((0, 99))));
+    }
+
+    #[test]
+    fn ignores_malformed_ranges() {
+        assert_eq!(parse("items=0-9", 100).ok(), Some(None));
+        assert_eq!(parse("bytes=9-0", 100).ok(), Some(None));
+        assert_eq!(parse("bytes=0-1,5-6", 100).ok(), Some(None));
+        assert_eq!(parse("bytes=a-b", 100).ok(), Some(None));
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        assert!(parse("bytes=100-", 100).is_err());
+        assert!(parse("bytes=-0", 100).is_err());
+        assert!(parse("bytes=0-", 0).is_err());
+    }
+}
@@ -0,0 +1,857 @@
+//! [`hyper`] service of the S3 HTTP API.
+
+use std::{cell::RefCell, error::Error as StdError, fmt, io, sync::Arc};
+
+use baza::{
+    Compression, CorruptFile, CreateFile, Exec, FileVersion, FreeSpace,
+    GetBucketConfig, GetBucketUsage, GetFreeSpace, GetObjectLock,
+    GetSymlinkHistory, Lifecycle, ListCorruptFiles, ListSymlinks, ListVersions,
+    ObjectLock, ObjectLockStatus, PublishSymlinks, Quota, RelativePath,
+    RollbackSymlink, SetBucketConfig, SetObjectLegalHold, SetObjectRetention,
+    SymlinkHistoryEntry, Usage, Versioning,
+};
+use derive_more::with_trait::Display;
+use hyper::{
+    Body, Method, Request, Response, StatusCode, body::HttpBody as _, header,
+};
+use percent_encoding::percent_decode_str;
+use s3_server::{
+    SharedS3Service, dto,
+    errors::{S3Error, S3ErrorCode},
+    path::S3Path,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use time::OffsetDateTime;
+
+use crate::{
+    compression,
+    cors::{self, CorsConfiguration},
+    integrity, lifecycle, links, object_lock, post_object, publish, quota,
+    signature::{self, Credentials},
+    versioning,
+};
+
+/// Maximum size of a request body buffered by [`Service`].
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+tokio::task_local! {
+    /// [`Params`] of the request currently handled by the [`S3Service`].
+    ///
+    /// [`S3Service`]: s3_server::S3Service
+    static PARAMS: Params;
+
+    /// [`Error`] to respond with to the request currently handled by the
+    /// [`S3Service`], instead of the one rendered by it.
+    ///
+    /// [`S3Service`]: s3_server::S3Service
+    static OVERRIDE: RefCell<Option<Error>>;
+}
+
+/// Parameters of a request, ignored by the [`S3Service`] when extracting
+/// its inputs.
+///
+/// [`S3Service`]: s3_server::S3Service
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Params {
+    /// `versionId` query parameter of the request.
+    pub(crate) version_id: Option<String>,
+
+    /// Indicator whether `symlink` query parameter is present in the request,
+    /// requesting a symlink itself instead of the file it points to.
+    pub(crate) symlink: bool,
+}
+
+impl Params {
+    /// Returns the [`Params`] of the request currently handled by the
+    /// [`S3Service`].
+    ///
+    /// [`S3Service`]: s3_server::S3Service
+    #[must_use]
+    pub(crate) fn current() -> Self {
+        PARAMS.try_with(Clone::clone).unwrap_or_default()
+    }
+}
+
+/// [`hyper`] service handling the requests unsupported by the [`S3Service`]
+/// and delegating all the other ones to it.
+///
+/// [`S3Service`]: s3_server::S3Service
+#[derive(Debug)]
+pub(crate) struct Service<S> {
+    /// [`S3Service`] to delegate the requests to.
+    ///
+    /// [`S3Service`]: s3_server::S3Service
+    s3: SharedS3Service,
+
+    /// Storage to execute operations on.
+    storage: S,
+
+    /// [`Credentials`] to authenticate requests with.
+    credentials: Arc<Credentials>,
+}
+
+impl<S: Clone> Clone for Service<S> {
+    fn clone(&self) -> Self {
+        Self {
+            s3: self.s3.clone(),
+            storage: self.storage.clone(),
+            credentials: Arc::clone(&self.credentials),
+        }
+    }
+}
+
+impl<S> Service<S> {
+    /// Creates a new [`Service`].
+    #[must_use]
+    pub(crate) fn new(
+        s3: SharedS3Service,
+        storage: S,
+        credentials: Credentials,
+    ) -> Self {
+        Self { s3, storage, credentials: Arc::new(credentials) }
+    }
+}
+
+impl<S> Service<S>
+where
+    S: Exec<CreateFile<dto::ByteStream>, Err: fmt::Display + AsRef<io::Error>>
+        + Exec<
+            GetBucketConfig<CorsConfiguration>,
+            Ok = Option<CorsConfiguration>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<CorsConfiguration>, Err: fmt::Display>
+        + Exec<
+            GetBucketConfig<Versioning>,
+            Ok = Option<Versioning>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<Versioning>, Err: fmt::Display>
+        + Exec<ListVersions, Ok = Vec<FileVersion>, Err: fmt::Display>
+        + Exec<
+            GetBucketConfig<Lifecycle>,
+            Ok = Option<Lifecycle>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<Lifecycle>, Err: fmt::Display>
+        + Exec<GetBucketConfig<Quota>, Ok = Option<Quota>, Err: fmt::Display>
+        + Exec<SetBucketConfig<Quota>, Err: fmt::Display>
+        + Exec<GetBucketUsage, Ok = Usage, Err: fmt::Display>
+        + Exec<
+            GetBucketConfig<Compression>,
+            Ok = Option<Compression>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<Compression>, Err: fmt::Display>
+        + Exec<
+            GetBucketConfig<ObjectLock>,
+            Ok = Option<ObjectLock>,
+            Err: fmt::Display,
+        > + Exec<SetBucketConfig<ObjectLock>, Err: fmt::Display>
+        + Exec<
+            GetObjectLock,
+            Ok = Option<ObjectLockStatus>,
+            Err: fmt::Display + AsRef<io::Error>,
+        > + Exec<SetObjectRetention, Err: fmt::Display + AsRef<io::Error>>
+        + Exec<SetObjectLegalHold, Err: fmt::Display + AsRef<io::Error>>
+        + Exec<PublishSymlinks, Err: fmt::Display + AsRef<io::Error>>
+        + Exec<ListSymlinks, Ok = Vec<RelativePath>, Err: fmt::Display>
+        + Exec<
+            GetSymlinkHistory,
+            Ok = Vec<SymlinkHistoryEntry>,
+            Err: fmt::Display,
+        > + Exec<
+            RollbackSymlink,
+            Ok = RelativePath,
+            Err: fmt::Display + AsRef<io::Error>,
+        > + Exec<ListCorruptFiles, Ok = Vec<CorruptFile>, Err: fmt::Display>
+        + Exec<GetFreeSpace, Ok = FreeSpace, Err: fmt::Display>
+        + Send
+        + Sync,
+{
+    /// Handles the provided HTTP [`Request`].
+    ///
+    /// # Errors
+    ///
+    /// If the [`S3Service`] fails to produce a [`Response`].
+    ///
+    /// [`S3Service`]: s3_server::S3Service
+    pub(crate) async fn call(
+        self,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, Box<dyn StdError + Send + Sync>> {
+        let bucket = bucket(&req);
+
+        // Preflight requests are never signed, so must be handled before any
+        // authentication happens.
+        if req.method() == Method::OPTIONS {
+            let resp = match bucket {
+                Some(bucket) => {
+                    cors::preflight(&self.storage, bucket, &req).await
+                }
+                None => Err(Error::new(
+                    S3ErrorCode::InvalidRequest,
+                    "CORS preflight is supported for buckets and objects only",
+                )),
+            };
+            return Ok(resp.unwrap_or_else(Error::into_response));
+        }
+
+        let cors = req
+            .headers()
+            .get(header::ORIGIN)
+            .cloned()
+            .zip(bucket)
+            .map(|(origin, bucket)| (origin, bucket, req.method().clone()));
+
+        let mut resp = if post_object::is_match(&req) {
+            post_object::handle(&self.storage, &self.credentials, req)
+                .await
+                .unwrap_or_else(Error::into_response)
+        } else if let Some(op) = Operation::recognize(&req) {
+            self.execute(op, req).await.unwrap_or_else(Error::into_response)
+        } else {
+            let params = Params {
+                version_id: query_param(&req, "versionId"),
+                symlink: query_param(&req, "symlink").is_some(),
+            };
+            let resp = PARAMS
+                .scope(
+                    params,
+                    OVERRIDE.scope(RefCell::default(), async {
+                        let resp = self.s3.hyper_call(req).await?;
+                        let err = OVERRIDE.with(RefCell::take);
+                        Ok::<_, Box<dyn StdError + Send + Sync>>((resp, err))
+                    }),
+                )
+                .await?;
+            match resp {
+                (_, Some(err)) => err.into_response(),
+                (mut resp, None) => {
+                    // `S3Service` doesn't distinguish partial responses.
+                    if resp.status() == StatusCode::OK
+                        && resp.headers().contains_key(header::CONTENT_RANGE)
+                    {
+                        *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+                    }
+                    resp
+                }
+            }
+        };
+
+        if let Some((origin, bucket, method)) = cors {
+            cors::apply(&self.storage, bucket, &origin, &method, &mut resp)
+                .await;
+        }
+        Ok(resp)
+    }
+
+    /// Authenticates the provided [`Request`] and executes the provided
+    /// [`Operation`] with it.
+    ///
+    /// # Errors
+    ///
+    /// - If the [`Request`] cannot be authenticated.
+    /// - If the [`Operation`] fails.
+    async fn execute(
+        &self,
+        op: Operation,
+        req: Request<Body>,
+    ) -> Result<Response<Body>, Error> {
+        let (parts, body) = req.into_parts();
+        let body = read_body(body).await?;
+        signature::authenticate(
+            &self.credentials,
+            &parts,
+            &body,
+            OffsetDateTime::now_utc(),
+        )?;
+
+        match op {
+            Operation::PutBucketCors(bucket) => {
+                cors::put_bucket_cors(&self.storage, bucket, &body).await
+            }
+            Operation::GetBucketCors(bucket) => {
+                cors::get_bucket_cors(&self.storage, bucket).await
+            }
+            Operation::DeleteBucketCors(bucket) => {
+                cors::delete_bucket_cors(&self.storage, bucket).await
+            }
+            Operation::PutBucketVersioning(bucket) => {
+                versioning::put_bucket_versioning(&self.storage, bucket, &body)
+                    .await
+            }
+            Operation::GetBucketVersioning(bucket) => {
+                versioning::get_bucket_versioning(&self.storage, bucket).await
+            }
+            Operation::ListObjectVersions { bucket, prefix } => {
+                versioning::list_object_versions(&self.storage, bucket, prefix)
+                    .await
+            }
+            Operation::PutBucketLifecycle(bucket) => {
+                lifecycle::put_bucket_lifecycle(&self.storage, bucket, &body)
+                    .await
+            }
+            Operation::GetBucketLifecycle(bucket) => {
+                lifecycle::get_bucket_lifecycle(&self.storage, bucket).await
+            }
+            Operation::DeleteBucketLifecycle(bucket) => {
+                lifecycle::delete_bucket_lifecycle(&self.storage, bucket).await
+            }
+            Operation::PutBucketQuota(bucket) => {
+                quota::put_bucket_quota(&self.storage, bucket, &body).await
+            }
+            Operation::GetBucketQuota(bucket) => {
+                quota::get_bucket_quota(&self.storage, bucket).await
+            }
+            Operation::DeleteBucketQuota(bucket) => {
+                quota::delete_bucket_quota(&self.storage, bucket).await
+            }
+            Operation::PutBucketCompression(bucket) => {
+                compression::put_bucket_compression(
+                    &self.storage,
+                    bucket,
+                    &body,
+                )
+                .await
+            }
+            Operation::GetBucketCompression(bucket) => {
+                compression::get_bucket_compression(&self.storage, bucket).await
+            }
+            Operation::DeleteBucketCompression(bucket) => {
+                compression::delete_bucket_compression(&self.storage, bucket)
+                    .await
+            }
+            Operation::PutObjectLockConfiguration(bucket) => {
+                object_lock::put_object_lock_configuration(
+                    &self.storage,
+                    bucket,
+                    &body,
+                )
+                .await
+            }
+            Operation::GetObjectLockConfiguration(bucket) => {
+                object_lock::get_object_lock_configuration(
+                    &self.storage,
+                    bucket,
+                )
+                .await
+            }
+            Operation::PutObjectRetention {
+                path,
+                version_id,
+                bypass_governance,
+            } => {
+                object_lock::put_object_retention(
+                    &self.storage,
+                    path,
+                    version_id,
+                    bypass_governance,
+                    &body,
+                )
+                .await
+            }
+            Operation::GetObjectRetention { path, version_id } => {
+                object_lock::get_object_retention(
+                    &self.storage,
+                    path,
+                    version_id,
+                )
+                .await
+            }
+            Operation::PutObjectLegalHold { path, version_id } => {
+                object_lock::put_object_legal_hold(
+                    &self.storage,
+                    path,
+                    version_id,
+                    &body,
+                )
+                .await
+            }
+            Operation::GetObjectLegalHold { path, version_id } => {
+                object_lock::get_object_legal_hold(
+                    &self.storage,
+                    path,
+                    version_id,
+                )
+                .await
+            }
+            Operation::PublishSymlinks(dir) => {
+                publish::publish_symlinks(&self.storage, dir, &body).await
+            }
+            Operation::ListSymlinks(target) => {
+                links::list_symlinks(&self.storage, target).await
+            }
+            Operation::GetSymlinkHistory(path) => {
+                links::get_symlink_history(&self.storage, path).await
+            }
+            Operation::RollbackSymlink(path) => {
+                links::rollback_symlink(&self.storage, path).await
+            }
+            Operation::ListCorruptObjects(bucket) => {
+                integrity::list_corrupt_objects(&self.storage, bucket).await
+            }
+        }
+    }
+}
+
+/// S3 operation handled by [`Service`] itself, as not supported by the
+/// [`S3Service`].
+///
+/// [`S3Service`]: s3_server::S3Service
+#[derive(Clone, Debug)]
+enum Operation {
+    /// [PutBucketCors][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    PutBucketCors(RelativePath),
+
+    /// [GetBucketCors][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    GetBucketCors(RelativePath),
+
+    /// [DeleteBucketCors][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    DeleteBucketCors(RelativePath),
+
+    /// [PutBucketVersioning][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    PutBucketVersioning(RelativePath),
+
+    /// [GetBucketVersioning][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    GetBucketVersioning(RelativePath),
+
+    /// [ListObjectVersions][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    ListObjectVersions {
+        /// [`RelativePath`] of the bucket to list object versions in.
+        bucket: RelativePath,
+
+        /// Prefix of the keys to list versions of.
+        prefix: Option<String>,
+    },
+
+    /// [PutBucketLifecycleConfiguration][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    PutBucketLifecycle(RelativePath),
+
+    /// [GetBucketLifecycleConfiguration][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    GetBucketLifecycle(RelativePath),
+
+    /// [DeleteBucketLifecycle][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    DeleteBucketLifecycle(RelativePath),
+
+    /// `PutBucketQuota` operation, not present in S3.
+    PutBucketQuota(RelativePath),
+
+    /// `GetBucketQuota` operation, not present in S3.
+    GetBucketQuota(RelativePath),
+
+    /// `DeleteBucketQuota` operation, not present in S3.
+    DeleteBucketQuota(RelativePath),
+
+    /// `PutBucketCompression` operation, not present in S3.
+    PutBucketCompression(RelativePath),
+
+    /// `GetBucketCompression` operation, not present in S3.
+    GetBucketCompression(RelativePath),
+
+    /// `DeleteBucketCompression` operation, not present in S3.
+    DeleteBucketCompression(RelativePath),
+
+    /// [PutObjectLockConfiguration][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    PutObjectLockConfiguration(RelativePath),
+
+    /// [GetObjectLockConfiguration][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    GetObjectLockConfiguration(RelativePath),
+
+    /// [PutObjectRetention][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    PutObjectRetention {
+        /// [`RelativePath`] of the object.
+        path: RelativePath,
+
+        /// `versionId` query parameter of the request.
+        version_id: Option<String>,
+
+        /// Indicator whether `x-amz-bypass-governance-retention` header is
+        /// set.
+        bypass_governance: bool,
+    },
+
+    /// [GetObjectRetention][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    GetObjectRetention {
+        /// [`RelativePath`] of the object.
+        path: RelativePath,
+
+        /// `versionId` query parameter of the request.
+        version_id: Option<String>,
+    },
+
+    /// [PutObjectLegalHold][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    PutObjectLegalHold {
+        /// [`RelativePath`] of the object.
+        path: RelativePath,
+
+        /// `versionId` query parameter of the request.
+        version_id: Option<String>,
+    },
+
+    /// [GetObjectLegalHold][0] operation.
+    ///
+    /// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+    GetObjectLegalHold {
+        /// [`RelativePath`] of the object.
+        path: RelativePath,
+
+        /// `versionId` query parameter of the request.
+        version_id: Option<String>,
+    },
+
+    /// `PublishSymlinks` operation, not present in S3.
+    PublishSymlinks(RelativePath),
+
+    /// `ListSymlinks` operation, not present in S3.
+    ListSymlinks(RelativePath),
+
+    /// `GetSymlinkHistory` operation, not present in S3.
+    GetSymlinkHistory(RelativePath),
+
+    /// `RollbackSymlink` operation, not present in S3.
+    RollbackSymlink(RelativePath),
+
+    /// `ListCorruptObjects` operation, not present in S3.
+    ListCorruptObjects(RelativePath),
+}
+
+impl Operation {
+    /// Recognizes an [`Operation`] requested by the provided [`Request`], if
+    /// any.
+    fn recognize(req: &Request<Body>) -> Option<Self> {
+        let has_param = |name| query_param(req, name).is_some();
+
+        let bucket = match S3Path::try_from_path(req.uri().path()).ok()? {
+            S3Path::Bucket { bucket } => bucket_path(bucket)?,
+            S3Path::Object { bucket, key } => {
+                let path = bucket_path(bucket)?
+                    .join(RelativePath::try_from(key.to_owned()).ok()?);
+                return Self::recognize_object(req, path);
+            }
+            S3Path::Root => return None,
+        };
+        if has_param("cors") {
+            return match *req.method() {
+                Method::PUT => Some(Self::PutBucketCors(bucket)),
+                Method::GET => Some(Self::GetBucketCors(bucket)),
+                Method::DELETE => Some(Self::DeleteBucketCors(bucket)),
+                _ => None,
+            };
+        }
+        if has_param("versioning") {
+            return match *req.method() {
+                Method::PUT => Some(Self::PutBucketVersioning(bucket)),
+                Method::GET => Some(Self::GetBucketVersioning(bucket)),
+                _ => None,
+            };
+        }
+        if has_param("lifecycle") {
+            return match *req.method() {
+                Method::PUT => Some(Self::PutBucketLifecycle(bucket)),
+                Method::GET => Some(Self::GetBucketLifecycle(bucket)),
+                Method::DELETE => Some(Self::DeleteBucketLifecycle(bucket)),
+                _ => None,
+            };
+        }
+        if has_param("quota") {
+            return match *req.method() {
+                Method::PUT => Some(Self::PutBucketQuota(bucket)),
+                Method::GET => Some(Self::GetBucketQuota(bucket)),
+                Method::DELETE => Some(Self::DeleteBucketQuota(bucket)),
+                _ => None,
+            };
+        }
+        if has_param("compression") {
+            return match *req.method() {
+                Method::PUT => Some(Self::PutBucketCompression(bucket)),
+                Method::GET => Some(Self::GetBucketCompression(bucket)),
+                Method::DELETE => Some(Self::DeleteBucketCompression(bucket)),
+                _ => None,
+            };
+        }
+        if has_param("object-lock") {
+            return match *req.method() {
+                Method::PUT => Some(Self::PutObjectLockConfiguration(bucket)),
+                Method::GET => Some(Self::GetObjectLockConfiguration(bucket)),
+                _ => None,
+            };
+        }
+        if has_param("corrupt") && req.method() == Method::GET {
+            return Some(Self::ListCorruptObjects(bucket));
+        }
+        if has_param("versions") && req.method() == Method::GET {
+            return Some(Self::ListObjectVersions {
+                bucket,
+                prefix: query_param(req, "prefix"),
+            });
+        }
+        None
+    }
+
+    /// Recognizes an [`Operation`] on the object of the provided `path`
+    /// requested by the provided [`Request`], if any.
+    fn recognize_object(
+        req: &Request<Body>,
+        path: RelativePath,
+    ) -> Option<Self> {
+        let has_param = |name| query_param(req, name).is_some();
+        let version_id = query_param(req, "versionId");
+
+        if has_param("retention") {
+            let bypass_governance = req
+                .headers()
+                .get("x-amz-bypass-governance-retention")
+                .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"true"));
+            return match *req.method() {
+                Method::PUT => Some(Self::PutObjectRetention {
+                    path,
+                    version_id,
+                    bypass_governance,
+                }),
+                Method::GET => {
+                    Some(Self::GetObjectRetention { path, version_id })
+                }
+                _ => None,
+            };
+        }
+        if has_param("legal-hold") {
+            return match *req.method() {
+                Method::PUT => {
+                    Some(Self::PutObjectLegalHold { path, version_id })
+                }
+                Method::GET => {
+                    Some(Self::GetObjectLegalHold { path, version_id })
+                }
+                _ => None,
+            };
+        }
+        if has_param("symlinks") && req.method() == Method::PUT {
+            return Some(Self::PublishSymlinks(path));
+        }
+        if has_param("links") && req.method() == Method::GET {
+            return Some(Self::ListSymlinks(path));
+        }
+        if has_param("symlink-history") && req.method() == Method::GET {
+            return Some(Self::GetSymlinkHistory(path));
+        }
+        if has_param("symlink-rollback") && req.method() == Method::POST {
+            return Some(Self::RollbackSymlink(path));
+        }
+        None
+    }
+}
+
+/// Error of handling a request by [`Service`], rendered as an S3 XML error
+/// response.
+#[derive(Debug, Display)]
+#[display("{code}: {message}")]
+pub(crate) struct Error {
+    /// S3 error code of this [`Error`].
+    pub(crate) code: &'static str,
+
+    /// HTTP [`StatusCode`] of this [`Error`].
+    pub(crate) status: StatusCode,
+
+    /// Human-readable message of this [`Error`].
+    pub(crate) message: String,
+}
+
+impl Error {
+    /// Creates a new [`Error`] out of the provided [`S3ErrorCode`] and
+    /// `message`.
+    #[must_use]
+    pub(crate) fn new(code: S3ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code: code.as_static_str(),
+            status: code
+                .as_status_code()
+                .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            message: message.into(),
+        }
+    }
+
+    /// Creates a new [`Error`] with the provided `code`, which is not present
+    /// in the [`S3ErrorCode`].
+    #[must_use]
+    pub(crate) fn custom(
+        code: &'static str,
+        status: StatusCode,
+        message: impl Into<String>,
+    ) -> Self {
+        Self { code, status, message: message.into() }
+    }
+
+    /// Creates a new [`S3ErrorCode::InternalError`] of the failed `op`eration.
+    #[must_use]
+    pub(crate) fn internal(op: &str, e: impl fmt::Display) -> Self {
+        Self::new(
+            S3ErrorCode::InternalError,
+            format!("{op} operation failed: {e}"),
+        )
+    }
+
+    /// Makes this [`Error`] the response to the request currently handled by
+    /// the [`S3Service`], as it cannot be expressed with an [`S3Error`].
+    ///
+    /// Returns the [`S3Error`] to fail the [`S3Storage`] operation with.
+    ///
+    /// [`S3Service`]: s3_server::S3Service
+    /// [`S3Storage`]: s3_server::S3Storage
+    #[must_use]
+    pub(crate) fn into_s3_error(self) -> S3Error {
+        let fallback =
+            S3Error::new(S3ErrorCode::InternalError, self.message.clone());
+        _ = OVERRIDE.try_with(|e| e.replace(Some(self)));
+        fallback
+    }
+
+    /// Renders this [`Error`] as an S3 XML error [`Response`].
+    #[must_use]
+    pub(crate) fn into_response(self) -> Response<Body> {
+        tracing::info!("{self}");
+
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <Error><Code>{}</Code><Message>{}</Message></Error>",
+            self.code,
+            xml_escape(&self.message),
+        );
+        let mut resp = Response::new(Body::from(body));
+        *resp.status_mut() = self.status;
+        _ = resp.headers_mut().insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/xml"),
+        );
+        resp
+    }
+}
+
+/// Extracts the [`RelativePath`] of the bucket targeted by the provided
+/// [`Request`], if any.
+fn bucket(req: &Request<Body>) -> Option<RelativePath> {
+    match S3Path::try_from_path(req.uri().path()).ok()? {
+        S3Path::Bucket { bucket } | S3Path::Object { bucket, .. } => {
+            bucket_path(bucket)
+        }
+        S3Path::Root => None,
+    }
+}
+
+/// Returns the percent-decoded value of the query parameter with the provided
+/// `name` of the provided [`Request`], if any.
+fn query_param(req: &Request<Body>, name: &str) -> Option<String> {
+    req.uri().query()?.split('&').find_map(|p| {
+        let (k, v) = p.split_once('=').unwrap_or((p, ""));
+        (k == name).then(|| percent_decode_str(v).decode_utf8_lossy().into())
+    })
+}
+
+/// Converts the provided `bucket` name into a [`RelativePath`].
+fn bucket_path(bucket: &str) -> Option<RelativePath> {
+    RelativePath::try_from(bucket.to_owned()).ok()
+}
+
+/// Reads the whole provided request [`Body`].
+///
+/// # Errors
+///
+/// If the [`Body`] exceeds [`MAX_BODY_SIZE`] or fails to be read.
+async fn read_body(mut body: Body) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| {
+            Error::new(S3ErrorCode::IncompleteBody, e.to_string())
+        })?;
+        if bytes.len() + chunk.len() > MAX_BODY_SIZE {
+            return Err(Error::new(
+                S3ErrorCode::MaxMessageLengthExceeded,
+                "Your request was too big",
+            ));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Parses the provided XML `body` of a request.
+///
+/// # Errors
+///
+/// If the provided `body` is not a valid XML representation of `T`.
+pub(crate) fn parse_xml<T: DeserializeOwned>(body: &[u8]) -> Result<T, Error> {
+    std::str::from_utf8(body)
+        .ok()
+        .and_then(|xml| quick_xml::de::from_str(xml).ok())
+        .ok_or_else(|| {
+            Error::new(
+                S3ErrorCode::MalformedXML,
+                "The XML you provided was not well-formed or did not validate \
+                 against our published schema",
+            )
+        })
+}
+
+/// Renders the provided `value` as an XML [`Response`] with the provided
+/// `root` element.
+///
+/// # Errors
+///
+/// If the provided `value` cannot be serialized into XML.
+pub(crate) fn xml_response<T: Serialize>(
+    root: &str,
+    value: &T,
+) -> Result<Response<Body>, Error> {
+    let xml = quick_xml::se::to_string_with_root(root, value)
+        .map_err(|e| Error::internal("XML serialization", e))?;
+
+    let mut resp = Response::new(Body::from(format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>{xml}",
+    )));
+    _ = resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/xml"),
+    );
+    Ok(resp)
+}
+
+/// Escapes the provided `s`tring to be used as XML text.
+#[must_use]
+pub(crate) fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
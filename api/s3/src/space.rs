@@ -0,0 +1,76 @@
+//! Free space of the storage.
+//!
+//! Uploads are rejected before being read, if their `Content-Length` exceeds
+//! the free space available, or if the storage is read-only, because of low
+//! free space.
+
+use std::fmt;
+
+use baza::{Exec, FreeSpace, GetFreeSpace};
+use hyper::StatusCode;
+use s3_server::errors::{S3Error, S3ErrorCode};
+
+use crate::service::Error;
+
+/// Creates a new [`Error`] of the storage having not enough free space.
+#[must_use]
+pub(crate) fn insufficient_error(message: impl Into<String>) -> Error {
+    Error::custom(
+        "InsufficientStorage",
+        StatusCode::INSUFFICIENT_STORAGE,
+        message,
+    )
+}
+
+/// Ensures the provided `storage` is writable and has enough free space for
+/// an upload of the provided `length` (if known).
+///
+/// # Errors
+///
+/// See the [`check_available()`] function.
+pub(crate) async fn ensure_available<S>(
+    storage: &S,
+    length: Option<i64>,
+) -> Result<(), S3Error>
+where
+    S: Exec<GetFreeSpace, Ok = FreeSpace, Err: fmt::Display>,
+{
+    check_available(storage, length).await.map_err(Error::into_s3_error)
+}
+
+/// Checks whether the provided `storage` is writable and has enough free
+/// space for an upload of the provided `length` (if known).
+///
+/// # Errors
+///
+/// - If the [`GetFreeSpace`] operation fails.
+/// - If the `storage` is read-only.
+/// - If the `length` exceeds the [`FreeSpace::available`] bytes.
+pub(crate) async fn check_available<S>(
+    storage: &S,
+    length: Option<i64>,
+) -> Result<(), Error>
+where
+    S: Exec<GetFreeSpace, Ok = FreeSpace, Err: fmt::Display>,
+{
+    let space = storage
+        .exec(GetFreeSpace)
+        .await
+        .map_err(|e| Error::internal("GetFreeSpace", e))?;
+
+    if space.read_only {
+        return Err(Error::new(
+            S3ErrorCode::ServiceUnavailable,
+            "Storage is read-only, because of low free space",
+        ));
+    }
+    if let Some(length) = length.and_then(|l| u64::try_from(l).ok()) {
+        if length > space.available {
+            return Err(insufficient_error(format!(
+                "Object of {length} bytes exceeds {} bytes of free space",
+                space.available,
+            )));
+        }
+    }
+    Ok(())
+}
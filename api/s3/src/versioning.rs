@@ -0,0 +1,233 @@
+//! [Versioning][0] of buckets.
+//!
+//! [0]: https://docs.aws.amazon.com/AmazonS3/latest/userguide/Versioning.html
+
+use std::{fmt, time::SystemTime};
+
+use baza::{
+    Exec, FileVersion, GetBucketConfig, ListVersions, RelativePath,
+    SetBucketConfig, Versioning,
+};
+use hyper::{Body, Response};
+use s3_server::errors::S3ErrorCode;
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::service::{self, Error};
+
+/// XML representation of a bucket [`Versioning`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct VersioningConfiguration {
+    /// Status of the [`Versioning`].
+    #[serde(rename = "Status", skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+}
+
+/// Handles the [PutBucketVersioning][0] operation.
+///
+/// # Errors
+///
+/// - If the provided `body` is not a valid [`VersioningConfiguration`].
+/// - If the [`SetBucketConfig`] operation fails.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn put_bucket_versioning<S>(
+    storage: &S,
+    bucket: RelativePath,
+    body: &[u8],
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<SetBucketConfig<Versioning>, Err: fmt::Display>,
+{
+    let config = service::parse_xml::<VersioningConfiguration>(body)?;
+    let versioning = match config.status.as_deref() {
+        Some("Enabled") => Versioning::Enabled,
+        Some("Suspended") => Versioning::Suspended,
+        _ => {
+            return Err(Error::new(
+                S3ErrorCode::MalformedXML,
+                "Versioning status must be either `Enabled` or `Suspended`",
+            ));
+        }
+    };
+
+    storage
+        .exec(SetBucketConfig { bucket, config: Some(versioning) })
+        .await
+        .map_err(|e| Error::internal("SetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    Ok(Response::new(Body::empty()))
+}
+
+/// Handles the [GetBucketVersioning][0] operation.
+///
+/// # Errors
+///
+/// If the [`GetBucketConfig`] operation fails.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn get_bucket_versioning<S>(
+    storage: &S,
+    bucket: RelativePath,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<
+            GetBucketConfig<Versioning>,
+            Ok = Option<Versioning>,
+            Err: fmt::Display,
+        >,
+{
+    let versioning = storage
+        .exec(GetBucketConfig::new(bucket))
+        .await
+        .map_err(|e| Error::internal("GetBucketConfig", e))?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "VersioningConfiguration",
+        &VersioningConfiguration {
+            status: versioning.map(|v| format!("{v:?}")),
+        },
+    )
+}
+
+/// XML representation of a [ListObjectVersions][0] result.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[derive(Debug, Serialize)]
+struct ListVersionsResult {
+    /// Name of the listed bucket.
+    #[serde(rename = "Name")]
+    name: String,
+
+    /// Prefix of the listed keys.
+    #[serde(rename = "Prefix")]
+    prefix: String,
+
+    /// Indicator whether the result is truncated.
+    #[serde(rename = "IsTruncated")]
+    is_truncated: bool,
+
+    /// Listed versions and delete markers.
+    #[serde(rename = "$value")]
+    entries: Vec<ListEntry>,
+}
+
+/// Entry of a [`ListVersionsResult`].
+#[derive(Debug, Serialize)]
+enum ListEntry {
+    /// Version of an object.
+    Version {
+        /// Key of the object.
+        #[serde(rename = "Key")]
+        key: String,
+
+        /// ID of the version.
+        #[serde(rename = "VersionId")]
+        version_id: String,
+
+        /// Indicator whether the version is the current one.
+        #[serde(rename = "IsLatest")]
+        is_latest: bool,
+
+        /// Time when the version was created.
+        #[serde(rename = "LastModified")]
+        last_modified: String,
+
+        /// Size of the version in bytes.
+        #[serde(rename = "Size")]
+        size: u64,
+
+        /// Storage class of the version.
+        #[serde(rename = "StorageClass")]
+        storage_class: &'static str,
+    },
+
+    /// Delete marker of an object.
+    DeleteMarker {
+        /// Key of the object.
+        #[serde(rename = "Key")]
+        key: String,
+
+        /// ID of the delete marker.
+        #[serde(rename = "VersionId")]
+        version_id: String,
+
+        /// Indicator whether the delete marker is the current version.
+        #[serde(rename = "IsLatest")]
+        is_latest: bool,
+
+        /// Time when the delete marker was created.
+        #[serde(rename = "LastModified")]
+        last_modified: String,
+    },
+}
+
+impl From<FileVersion> for ListEntry {
+    fn from(v: FileVersion) -> Self {
+        let last_modified = format_time(v.last_modified);
+        if v.is_delete_marker {
+            Self::DeleteMarker {
+                key: v.key,
+                version_id: v.id.to_string(),
+                is_latest: v.is_latest,
+                last_modified,
+            }
+        } else {
+            Self::Version {
+                key: v.key,
+                version_id: v.id.to_string(),
+                is_latest: v.is_latest,
+                last_modified,
+                size: v.size,
+                storage_class: "STANDARD",
+            }
+        }
+    }
+}
+
+/// Handles the [ListObjectVersions][0] operation.
+///
+/// All the versions are listed at once, so the result is never truncated.
+///
+/// # Errors
+///
+/// If the [`ListVersions`] operation fails.
+///
+/// [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/API_Operations.html
+#[tracing::instrument(skip_all, fields(%bucket))]
+pub(crate) async fn list_object_versions<S>(
+    storage: &S,
+    bucket: RelativePath,
+    prefix: Option<String>,
+) -> Result<Response<Body>, Error>
+where
+    S: Exec<ListVersions, Ok = Vec<FileVersion>, Err: fmt::Display>,
+{
+    let name = bucket.to_string();
+    let versions = storage
+        .exec(ListVersions { bucket, prefix: prefix.clone() })
+        .await
+        .map_err(|e| Error::internal("ListVersions", e))?;
+
+    tracing::info!("OK");
+    service::xml_response(
+        "ListVersionsResult",
+        &ListVersionsResult {
+            name,
+            prefix: prefix.unwrap_or_default(),
+            is_truncated: false,
+            entries: versions.into_iter().map(Into::into).collect(),
+        },
+    )
+}
+
+/// Formats the provided [`SystemTime`] as [RFC 3339] timestamp.
+///
+/// [RFC 3339]: https://datatracker.ietf.org/doc/html/rfc3339
+pub(crate) fn format_time(t: SystemTime) -> String {
+    OffsetDateTime::from(t).format(&Rfc3339).unwrap_or_default()
+}
@@ -25,6 +25,36 @@ struct World {
 
     /// Latest [`s3::GetObjectResponse`] (if any).
     get_object_response: Option<s3::GetObjectResponse>,
+
+    /// [`s3::PostPolicy`] to upload POST forms with.
+    post_policy: s3::PostPolicy,
+
+    /// Latest [`s3::PostObjectResponse`] (if any).
+    post_object_response: Option<s3::PostObjectResponse>,
+
+    /// Latest [`s3::CorsResponse`] (if any).
+    cors_response: Option<s3::CorsResponse>,
+
+    /// Error of the latest lifecycle configuration request (if any).
+    lifecycle_error: Option<String>,
+
+    /// Error of the latest upload (if any).
+    upload_error: Option<String>,
+
+    /// Error of the latest request to a locked object (if any).
+    object_lock_error: Option<String>,
+
+    /// `symlink-to` metadata of the latest symlink request (if any).
+    symlink_to: Option<String>,
+
+    /// Error of the latest symlink creation or repointing (if any).
+    symlink_error: Option<String>,
+
+    /// Error of the latest symlinks publishing (if any).
+    publish_error: Option<String>,
+
+    /// `ListSymlinks` response body of the latest listing (if any).
+    listed_symlinks: Option<String>,
 }
 
 #[tokio::main]
@@ -0,0 +1,26 @@
+Feature: Bucket compression
+
+  Scenario: Compression: compressible object
+    Given `compress` bucket is compressed
+    And `export.json` was put into `compress` bucket as `file`
+    When `file` is read from `compress` bucket
+    Then `export.json` file is returned
+    And `export.json` is stored compressed in `compress` bucket as `file`
+    And `file` in `compress` bucket has the size of `export.json`
+
+  Scenario: Compression: range of compressed object
+    Given `compress` bucket is compressed
+    And `export.json` was put into `compress` bucket as `file`
+    When `file` is read from `compress` bucket within bytes 70000-70099
+    Then bytes 70000-70099 of `export.json` are returned
+
+  Scenario: Compression: already compressed object
+    Given `compress` bucket is compressed
+    And `rms.jpg` was put into `compress` bucket as `file` with `image/jpeg` content type
+    When `file` is read from `compress` bucket
+    Then `rms.jpg` file is returned
+    And `rms.jpg` is stored uncompressed in `compress` bucket as `file`
+
+  Scenario: Compression: bucket without compression
+    Given `export.json` was put into `plain` bucket as `file`
+    Then `export.json` is stored uncompressed in `plain` bucket as `file`
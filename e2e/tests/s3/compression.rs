@@ -0,0 +1,148 @@
+//! Bucket compression E2E (end-to-end) tests.
+
+use std::io;
+
+use cucumber::{given, then, when};
+use rusoto_core::{
+    DispatchSignedRequest as _, HttpClient, Region, signature::SignedRequest,
+};
+use rusoto_credential::AwsCredentials;
+use rusoto_s3::{
+    GetObjectRequest, HeadObjectRequest, PutObjectRequest, S3 as _,
+};
+use tokio::io::AsyncReadExt as _;
+
+use super::{API_URL, DATA_DIR, s3_client};
+use crate::{World, sample_file};
+
+#[given(regex = r"^`(\S+)` bucket is compressed$")]
+async fn compression_configured(w: &mut World, bucket: String) {
+    let region =
+        Region::Custom { name: "test".into(), endpoint: API_URL.into() };
+    let bucket = w.unique.filename(bucket);
+    let mut req =
+        SignedRequest::new("PUT", "s3", &region, &format!("/{bucket}"));
+    req.add_param("compression", "");
+    req.set_payload(Some(
+        "<CompressionConfiguration>\
+           <Algorithm>zstd</Algorithm>\
+         </CompressionConfiguration>",
+    ));
+    req.sign(&AwsCredentials::new("baza", "baza", None, None));
+
+    let resp = HttpClient::new()
+        .expect("Failed to initialize Rusoto HTTP client")
+        .dispatch(req, None)
+        .await
+        .expect("Failed to send request")
+        .buffer()
+        .await
+        .expect("Failed to read response");
+    assert_eq!(
+        resp.status.as_u16(),
+        200,
+        "`PutBucketCompression` failed: {}",
+        resp.body_as_str(),
+    );
+}
+
+#[given(regex = "^`(\\S+)` was put into `(\\S+)` bucket as `(\\S+)` \
+                 with `(\\S+)` content type$")]
+async fn object_is_put_with_content_type(
+    w: &mut World,
+    sample: String,
+    bucket: String,
+    key: String,
+    content_type: String,
+) {
+    let req = PutObjectRequest {
+        bucket: w.unique.filename(bucket),
+        key,
+        body: Some(sample_file(sample).to_owned().into()),
+        content_type: Some(content_type),
+        ..PutObjectRequest::default()
+    };
+
+    _ = s3_client()
+        .put_object(req)
+        .await
+        .unwrap_or_else(|e| panic!("`PutObjectRequest` failed: {e}"));
+}
+
+#[when(regex = "^`(\\S+)` is read from `(\\S+)` bucket\
+                ( within bytes \\d+-\\d+|)$")]
+async fn object_is_read(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    range: String,
+) {
+    let req = GetObjectRequest {
+        bucket: w.unique.filename(bucket),
+        key,
+        range: range
+            .strip_prefix(" within bytes ")
+            .map(|r| format!("bytes={r}")),
+        ..GetObjectRequest::default()
+    };
+
+    w.get_object_response = Some(
+        async {
+            let resp = s3_client().get_object(req).await?;
+            let mut buf = Vec::new();
+            _ = resp
+                .body
+                .unwrap()
+                .into_async_read()
+                .read_to_end(&mut buf)
+                .await
+                .unwrap();
+            Ok(buf)
+        }
+        .await,
+    );
+}
+
+#[then(regex = "^`(\\S+)` is stored (compressed|uncompressed) \
+                in `(\\S+)` bucket as `(\\S+)`$")]
+async fn object_is_stored(
+    w: &mut World,
+    sample: String,
+    how: String,
+    bucket: String,
+    key: String,
+) -> io::Result<()> {
+    let bucket = w.unique.filename(bucket);
+    let stored = async_fs::read(format!("{DATA_DIR}/{bucket}/{key}")).await?;
+
+    let sample = sample_file(sample);
+    if how == "compressed" {
+        assert!(stored.len() < sample.len() / 2, "File is not compressed");
+    } else {
+        assert!(stored == sample, "File is compressed");
+    }
+    Ok(())
+}
+
+#[then(regex = r"^`(\S+)` in `(\S+)` bucket has the size of `(\S+)`$")]
+async fn object_has_size(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    sample: String,
+) {
+    let req = HeadObjectRequest {
+        bucket: w.unique.filename(bucket),
+        key,
+        ..HeadObjectRequest::default()
+    };
+
+    let resp = s3_client()
+        .head_object(req)
+        .await
+        .unwrap_or_else(|e| panic!("`HeadObjectRequest` failed: {e}"));
+    assert_eq!(
+        resp.content_length,
+        i64::try_from(sample_file(sample).len()).ok(),
+    );
+}
@@ -0,0 +1,12 @@
+Feature: Confinement of filesystem access
+
+  Scenario: Confinement: escaping symlink is not followed on read
+    Given `escape` symlink on `links` bucket points outside the data directory
+    When trying to load `secret` under `escape` from `links` bucket
+    Then `AccessDenied` GetObject error is returned
+
+  Scenario: Confinement: escaping symlink is not followed on write
+    Given `escape` symlink on `links` bucket points outside the data directory
+    When trying to upload `rms.jpg` as `file` under `escape` to `links` bucket
+    Then upload is rejected with `AccessDenied` error
+    And nothing is written outside the data directory
@@ -0,0 +1,75 @@
+//! Confinement of filesystem access E2E (end-to-end) tests.
+
+use std::{env, io, path::PathBuf};
+
+use cucumber::{given, then, when};
+use rusoto_core::RusotoError;
+
+use super::{DATA_DIR, try_get_object, try_put_object};
+use crate::{World, sample_file};
+
+#[given(regex = "^`(\\S+)` symlink on `(\\S+)` bucket points outside \
+                 the data directory$")]
+async fn escaping_symlink_is_placed(
+    w: &mut World,
+    key: String,
+    bucket: String,
+) -> io::Result<()> {
+    let outside = outside_dir(w);
+    async_fs::create_dir_all(&outside).await?;
+    async_fs::write(outside.join("secret"), b"secret").await?;
+
+    let dir = format!("{DATA_DIR}/{bucket}");
+    async_fs::create_dir_all(&dir).await?;
+    async_fs::unix::symlink(
+        &outside,
+        format!("{dir}/{}", w.unique.filename(key)),
+    )
+    .await
+}
+
+#[when(regex = r"^trying to load `(\S+)` under `(\S+)` from `(\S+)` bucket$")]
+async fn trying_to_load_under(
+    w: &mut World,
+    key: String,
+    dir: String,
+    bucket: String,
+) {
+    let key = format!("{}/{key}", w.unique.filename(dir));
+    w.get_object_response = Some(try_get_object(bucket, key, None).await);
+}
+
+#[when(regex = "^trying to upload `(\\S+)` as `(\\S+)` under `(\\S+)` \
+                to `(\\S+)` bucket$")]
+async fn trying_to_upload_under(
+    w: &mut World,
+    sample: String,
+    key: String,
+    dir: String,
+    bucket: String,
+) {
+    let key = format!("{}/{key}", w.unique.filename(dir));
+    let res =
+        try_put_object(bucket, key, sample_file(sample), None::<String>).await;
+    w.upload_error = res.err().map(|e| match e {
+        RusotoError::Unknown(resp) => resp.body_as_str().to_owned(),
+        e => e.to_string(),
+    });
+}
+
+#[then("nothing is written outside the data directory")]
+async fn nothing_is_written_outside(w: &mut World) -> io::Result<()> {
+    let mut entries = async_fs::read_dir(outside_dir(w)).await?;
+    let mut names = Vec::new();
+    while let Some(entry) = baza::futures::StreamExt::next(&mut entries).await {
+        names.push(entry?.file_name());
+    }
+    assert_eq!(names, ["secret"], "Files are written outside");
+    Ok(())
+}
+
+/// Returns the [`PathBuf`] of the directory outside the [`DATA_DIR`] for the
+/// current scenario.
+fn outside_dir(w: &World) -> PathBuf {
+    env::temp_dir().join(w.unique.filename("baza-outside"))
+}
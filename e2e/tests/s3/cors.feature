@@ -0,0 +1,40 @@
+Feature: Bucket CORS
+
+  Scenario: Bucket CORS: configuration is stored
+    When `media` bucket is configured to allow `GET` requests from `https://app.example.com`
+    Then CORS configuration of `media` bucket allows `GET` requests from `https://app.example.com`
+
+  Scenario: Bucket CORS: configuration is deleted
+    Given `media` bucket allows `GET` requests from `https://app.example.com`
+    When CORS configuration of `media` bucket is deleted
+    Then `media` bucket has no CORS configuration
+
+  Scenario: Bucket CORS: allowed preflight request
+    Given `media` bucket allows `PUT` requests from `https://*.example.com`
+    When preflight `PUT` request is sent to `media` bucket as `file` from `https://app.example.com`
+    Then preflight request is allowed
+    And `https://app.example.com` origin is allowed
+
+  Scenario: Bucket CORS: preflight request from unknown origin
+    Given `media` bucket allows `PUT` requests from `https://app.example.com`
+    When preflight `PUT` request is sent to `media` bucket as `file` from `https://evil.com`
+    Then preflight request is rejected
+
+  Scenario: Bucket CORS: preflight request of disallowed method
+    Given `media` bucket allows `GET` requests from `https://app.example.com`
+    When preflight `PUT` request is sent to `media` bucket as `file` from `https://app.example.com`
+    Then preflight request is rejected
+
+  Scenario: Bucket CORS: preflight request without configuration
+    When preflight `GET` request is sent to `media` bucket as `file` from `https://app.example.com`
+    Then preflight request is rejected
+
+  Scenario: Bucket CORS: actual request
+    Given `media` bucket allows `GET` requests from `https://app.example.com`
+    When `file` is requested from `media` bucket from `https://app.example.com`
+    Then `https://app.example.com` origin is allowed
+
+  Scenario: Bucket CORS: actual request from unknown origin
+    Given `media` bucket allows `GET` requests from `https://app.example.com`
+    When `file` is requested from `media` bucket from `https://evil.com`
+    Then no CORS headers are returned
@@ -0,0 +1,209 @@
+//! [CORS] configuration E2E (end-to-end) tests.
+//!
+//! [CORS]: https://docs.aws.amazon.com/AmazonS3/latest/userguide/cors.html
+
+use cucumber::{given, then, when};
+use hyper::{Body, Client, HeaderMap, Method, Request, header};
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+    CORSConfiguration, CORSRule, DeleteBucketCorsRequest, GetBucketCorsRequest,
+    PutBucketCorsRequest, S3 as _,
+};
+
+use super::{API_URL, s3_client};
+use crate::World;
+
+/// Response to a request with an `Origin` header.
+#[derive(Clone, Debug)]
+pub(crate) struct CorsResponse {
+    /// HTTP status code of the response.
+    status: u16,
+
+    /// Headers of the response.
+    headers: HeaderMap,
+}
+
+#[given(regex = r"^`(\S+)` bucket allows `(\S+)` requests from `(\S+)`$")]
+#[when(regex = "^`(\\S+)` bucket is configured to allow `(\\S+)` requests \
+                  from `(\\S+)`$")]
+async fn cors_configured(
+    w: &mut World,
+    bucket: String,
+    method: String,
+    origin: String,
+) {
+    let req = PutBucketCorsRequest {
+        bucket: w.unique.filename(bucket),
+        cors_configuration: CORSConfiguration {
+            cors_rules: vec![CORSRule {
+                allowed_methods: vec![method],
+                allowed_origins: vec![origin],
+                allowed_headers: Some(vec!["*".into()]),
+                expose_headers: Some(vec!["ETag".into()]),
+                max_age_seconds: Some(600),
+                ..CORSRule::default()
+            }],
+        },
+        ..PutBucketCorsRequest::default()
+    };
+
+    s3_client()
+        .put_bucket_cors(req)
+        .await
+        .unwrap_or_else(|e| panic!("`PutBucketCorsRequest` failed: {e}"));
+}
+
+#[when(regex = r"^CORS configuration of `(\S+)` bucket is deleted$")]
+async fn cors_deleted(w: &mut World, bucket: String) {
+    let req = DeleteBucketCorsRequest {
+        bucket: w.unique.filename(bucket),
+        ..DeleteBucketCorsRequest::default()
+    };
+
+    s3_client()
+        .delete_bucket_cors(req)
+        .await
+        .unwrap_or_else(|e| panic!("`DeleteBucketCorsRequest` failed: {e}"));
+}
+
+#[then(regex = "^CORS configuration of `(\\S+)` bucket allows `(\\S+)` \
+                  requests from `(\\S+)`$")]
+async fn cors_is_stored(
+    w: &mut World,
+    bucket: String,
+    method: String,
+    origin: String,
+) {
+    let req = GetBucketCorsRequest {
+        bucket: w.unique.filename(bucket),
+        ..GetBucketCorsRequest::default()
+    };
+
+    let rules = s3_client()
+        .get_bucket_cors(req)
+        .await
+        .unwrap_or_else(|e| panic!("`GetBucketCorsRequest` failed: {e}"))
+        .cors_rules
+        .unwrap_or_default();
+
+    assert_eq!(rules.len(), 1, "Unexpected CORS rules: {rules:#?}");
+    assert_eq!(rules[0].allowed_methods, [method]);
+    assert_eq!(rules[0].allowed_origins, [origin]);
+}
+
+#[then(regex = r"^`(\S+)` bucket has no CORS configuration$")]
+async fn no_cors_is_stored(w: &mut World, bucket: String) {
+    let req = GetBucketCorsRequest {
+        bucket: w.unique.filename(bucket),
+        ..GetBucketCorsRequest::default()
+    };
+
+    match s3_client().get_bucket_cors(req).await {
+        Err(RusotoError::Unknown(resp))
+            if resp.body_as_str().contains("NoSuchCORSConfiguration") => {}
+        res => panic!("Expected `NoSuchCORSConfiguration`, got: {res:#?}"),
+    }
+}
+
+#[when(regex = "^preflight `(\\S+)` request is sent to `(\\S+)` bucket \
+                  as `(\\S+)` from `(\\S+)`$")]
+async fn preflight_sent(
+    w: &mut World,
+    method: String,
+    bucket: String,
+    key: String,
+    origin: String,
+) {
+    let req = Request::builder()
+        .method(Method::OPTIONS)
+        .uri(format!("{API_URL}/{}/{key}", w.unique.filename(bucket)))
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "x-amz-date")
+        .body(Body::empty())
+        .expect("Failed to build preflight request");
+    w.cors_response = Some(send(req).await);
+}
+
+#[when(regex = r"^`(\S+)` is requested from `(\S+)` bucket from `(\S+)`$")]
+async fn requested_with_origin(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    origin: String,
+) {
+    let req = Request::builder()
+        .method(Method::GET)
+        .uri(format!("{API_URL}/{}/{key}", w.unique.filename(bucket)))
+        .header(header::ORIGIN, origin)
+        .body(Body::empty())
+        .expect("Failed to build request");
+    w.cors_response = Some(send(req).await);
+}
+
+#[then(regex = r"^`(\S+)` origin is allowed$")]
+async fn origin_is_allowed(w: &mut World, origin: String) {
+    let resp = w.cors_response.take().expect("No CORS response");
+
+    assert_eq!(
+        resp.headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN),
+        Some(&origin.parse().unwrap()),
+        "Expected origin to be allowed, got: {resp:#?}",
+    );
+    assert_eq!(
+        resp.headers.get(header::ACCESS_CONTROL_EXPOSE_HEADERS),
+        Some(&"ETag".parse().unwrap()),
+        "Expected `ETag` header to be exposed, got: {resp:#?}",
+    );
+}
+
+#[then("preflight request is allowed")]
+async fn preflight_is_allowed(w: &mut World) {
+    let resp = w.cors_response.as_ref().expect("No CORS response");
+
+    assert_eq!(resp.status, 200, "Expected preflight to succeed: {resp:#?}");
+    assert_eq!(
+        resp.headers.get(header::ACCESS_CONTROL_ALLOW_HEADERS),
+        Some(&"x-amz-date".parse().unwrap()),
+        "Expected requested headers to be allowed, got: {resp:#?}",
+    );
+    assert_eq!(
+        resp.headers.get(header::ACCESS_CONTROL_MAX_AGE),
+        Some(&"600".parse().unwrap()),
+        "Expected `Access-Control-Max-Age` header, got: {resp:#?}",
+    );
+}
+
+#[then("preflight request is rejected")]
+async fn preflight_is_rejected(w: &mut World) {
+    let resp = w.cors_response.take().expect("No CORS response");
+
+    assert_eq!(resp.status, 403, "Expected preflight to fail: {resp:#?}");
+    assert!(
+        resp.headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none(),
+        "Expected no CORS headers, got: {resp:#?}",
+    );
+}
+
+#[then("no CORS headers are returned")]
+async fn no_cors_headers(w: &mut World) {
+    let resp = w.cors_response.take().expect("No CORS response");
+
+    assert!(
+        resp.headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none(),
+        "Expected no CORS headers, got: {resp:#?}",
+    );
+}
+
+/// Sends the provided [`Request`] and returns its [`CorsResponse`].
+async fn send(req: Request<Body>) -> CorsResponse {
+    let resp = Client::new()
+        .request(req)
+        .await
+        .unwrap_or_else(|e| panic!("Request failed: {e}"));
+
+    CorsResponse {
+        status: resp.status().as_u16(),
+        headers: resp.headers().clone(),
+    }
+}
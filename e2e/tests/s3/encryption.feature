@@ -0,0 +1,33 @@
+Feature: Server-side encryption
+
+  Scenario: Encryption: customer-provided key
+    Given `rms.jpg` was uploaded to `data` bucket as `sse_c` with customer encryption
+    When trying to load `sse_c` from `data` bucket with customer key
+    Then `rms.jpg` file is returned
+    And `rms.jpg` is stored encrypted as `data/sse_c`
+
+  Scenario: Encryption: missing customer-provided key
+    Given `rms.jpg` was uploaded to `data` bucket as `sse_c` with customer encryption
+    When trying to load `sse_c` from `data` bucket
+    Then `InvalidRequest` GetObject error is returned
+
+  Scenario: Encryption: another customer-provided key
+    Given `rms.jpg` was uploaded to `data` bucket as `sse_c` with customer encryption
+    When trying to load `sse_c` from `data` bucket with another key
+    Then `AccessDenied` GetObject error is returned
+
+  Scenario: Encryption: server-side master key
+    Given `rms.jpg` was uploaded to `data` bucket as `sse` with server-side encryption
+    When trying to load `sse` from `data` bucket
+    Then `rms.jpg` file is returned
+    And `rms.jpg` is stored encrypted as `data/sse`
+
+  Scenario: Encryption: range of encrypted file
+    Given `rms.jpg` was uploaded to `data` bucket as `sse_c` with customer encryption
+    When trying to load bytes 65530-65545 of `sse_c` from `data` bucket with customer key
+    Then bytes 65530-65545 of `rms.jpg` are returned
+
+  Scenario: Encryption: range of unencrypted file
+    Given `rms.jpg` was uploaded to `data` bucket as `plain`
+    When trying to load bytes 100-199 of `plain` from `data` bucket
+    Then bytes 100-199 of `rms.jpg` are returned
@@ -0,0 +1,150 @@
+//! Server-side encryption E2E (end-to-end) tests.
+
+use std::io;
+
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use cucumber::{given, then, when};
+use md5::{Digest as _, Md5};
+use rusoto_s3::{GetObjectRequest, PutObjectRequest, S3 as _};
+use tokio::io::AsyncReadExt as _;
+
+use super::{DATA_DIR, s3_client};
+use crate::{World, sample_file};
+
+/// Key of a customer to encrypt files with.
+const CUSTOMER_KEY: [u8; 32] = [0x42; 32];
+
+/// Another key of a customer, not matching the [`CUSTOMER_KEY`].
+const ANOTHER_KEY: [u8; 32] = [0x24; 32];
+
+#[given(regex = "^`(\\S+)` was uploaded to `(\\S+)` bucket as `(\\S+)` \
+                 with (customer|server-side) encryption$")]
+async fn encrypted_file_uploaded(
+    w: &mut World,
+    sample: String,
+    bucket: String,
+    key: String,
+    encryption: String,
+) {
+    let (algorithm, customer_key, customer_key_md5) =
+        customer_headers((encryption == "customer").then_some(&CUSTOMER_KEY));
+    let req = PutObjectRequest {
+        bucket,
+        key: w.unique.filename(key),
+        body: Some(sample_file(sample).to_owned().into()),
+        server_side_encryption: (encryption == "server-side")
+            .then(|| "AES256".into()),
+        sse_customer_algorithm: algorithm,
+        sse_customer_key: customer_key,
+        sse_customer_key_md5: customer_key_md5,
+        ..PutObjectRequest::default()
+    };
+
+    let resp = s3_client()
+        .put_object(req)
+        .await
+        .unwrap_or_else(|e| panic!("`PutObjectRequest` failed: {e}"));
+    if encryption == "customer" {
+        assert_eq!(resp.sse_customer_algorithm.as_deref(), Some("AES256"));
+    } else {
+        assert_eq!(resp.server_side_encryption.as_deref(), Some("AES256"));
+    }
+}
+
+#[when(regex = "^trying to load `(\\S+)` from `(\\S+)` bucket with \
+                (customer|another) key$")]
+async fn trying_to_load_with_key(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    which: String,
+) {
+    let customer_key =
+        if which == "customer" { &CUSTOMER_KEY } else { &ANOTHER_KEY };
+    w.get_object_response =
+        Some(get_object(w, bucket, key, None, Some(customer_key)).await);
+}
+
+#[when(regex = "^trying to load bytes (\\d+)-(\\d+) of `(\\S+)` from \
+                `(\\S+)` bucket( with customer key|)$")]
+async fn trying_to_load_range(
+    w: &mut World,
+    first: usize,
+    last: usize,
+    key: String,
+    bucket: String,
+    with_key: String,
+) {
+    let range = format!("bytes={first}-{last}");
+    let customer_key = (!with_key.is_empty()).then_some(&CUSTOMER_KEY);
+    w.get_object_response =
+        Some(get_object(w, bucket, key, Some(range), customer_key).await);
+}
+
+#[then(regex = r"^bytes (\d+)-(\d+) of `(\S+)` are returned$")]
+fn range_is_returned(w: &mut World, first: usize, last: usize, name: String) {
+    let bytes = w
+        .last_get_object_response()
+        .unwrap_or_else(|e| panic!("`GetObjectRequest` failed: {e}"));
+
+    assert!(bytes == sample_file(name)[first..=last], "Bytes don't match");
+}
+
+#[then(regex = r"^`(\S+)` is stored encrypted as `(\S+)`$")]
+async fn file_is_stored_encrypted(
+    w: &mut World,
+    sample: String,
+    path: String,
+) -> io::Result<()> {
+    let filename = w.unique.filename(path);
+    let stored = async_fs::read(format!("{DATA_DIR}/{filename}")).await?;
+
+    let sample = sample_file(sample);
+    assert!(stored.len() > sample.len(), "File is not encrypted");
+    assert!(
+        !stored.windows(64).any(|w| w == &sample[..64]),
+        "File contains plaintext",
+    );
+    Ok(())
+}
+
+/// Performs a [`GetObjectRequest`] with the provided `range` and the
+/// `customer_key` to decrypt the object with.
+async fn get_object(
+    w: &World,
+    bucket: String,
+    key: String,
+    range: Option<String>,
+    customer_key: Option<&[u8; 32]>,
+) -> super::GetObjectResponse {
+    let (algorithm, customer_key, customer_key_md5) =
+        customer_headers(customer_key);
+    let req = GetObjectRequest {
+        bucket,
+        key: w.unique.filename(key),
+        range,
+        sse_customer_algorithm: algorithm,
+        sse_customer_key: customer_key,
+        sse_customer_key_md5: customer_key_md5,
+        ..GetObjectRequest::default()
+    };
+
+    let resp = s3_client().get_object(req).await?;
+    let mut buf = Vec::new();
+    resp.body.unwrap().into_async_read().read_to_end(&mut buf).await.unwrap();
+    Ok(buf)
+}
+
+/// Returns the values of SSE-C headers for the provided `key`.
+fn customer_headers(
+    key: Option<&[u8; 32]>,
+) -> (Option<String>, Option<String>, Option<String>) {
+    let Some(key) = key else {
+        return (None, None, None);
+    };
+    (
+        Some("AES256".into()),
+        Some(BASE64_STANDARD.encode(key)),
+        Some(BASE64_STANDARD.encode(Md5::digest(key))),
+    )
+}
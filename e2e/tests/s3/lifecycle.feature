@@ -0,0 +1,14 @@
+Feature: Bucket lifecycle
+
+  Scenario: Bucket lifecycle: configuration is stored
+    When `logs` bucket is configured to expire `tmp/` objects after 3 days
+    Then lifecycle configuration of `logs` bucket expires `tmp/` objects after 3 days
+
+  Scenario: Bucket lifecycle: configuration is deleted
+    Given `logs` bucket expires `tmp/` objects after 3 days
+    When lifecycle configuration of `logs` bucket is deleted
+    Then `logs` bucket has no lifecycle configuration
+
+  Scenario: Bucket lifecycle: invalid configuration is rejected
+    When `logs` bucket is configured to expire `tmp/` objects after 0 days
+    Then `InvalidArgument` error is returned on lifecycle configuration
@@ -0,0 +1,127 @@
+//! [Lifecycle][0] configuration E2E (end-to-end) tests.
+//!
+//! [0]: https://docs.aws.amazon.com/AmazonS3/latest/userguide/lifecycle.html
+
+use cucumber::{given, then, when};
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+    BucketLifecycleConfiguration, DeleteBucketLifecycleRequest,
+    GetBucketLifecycleConfigurationRequest, LifecycleExpiration, LifecycleRule,
+    LifecycleRuleFilter, PutBucketLifecycleConfigurationRequest, S3 as _,
+};
+
+use super::s3_client;
+use crate::World;
+
+#[given(regex = r"^`(\S+)` bucket expires `(\S+)` objects after (\d+) days?$")]
+#[when(regex = "^`(\\S+)` bucket is configured to expire `(\\S+)` objects \
+                 after (\\d+) days?$")]
+async fn lifecycle_configured(
+    w: &mut World,
+    bucket: String,
+    prefix: String,
+    days: i64,
+) {
+    let req = PutBucketLifecycleConfigurationRequest {
+        bucket: w.unique.filename(bucket),
+        lifecycle_configuration: Some(BucketLifecycleConfiguration {
+            rules: vec![LifecycleRule {
+                id: Some("expire".into()),
+                filter: Some(LifecycleRuleFilter {
+                    prefix: Some(prefix),
+                    ..LifecycleRuleFilter::default()
+                }),
+                status: "Enabled".into(),
+                expiration: Some(LifecycleExpiration {
+                    days: Some(days),
+                    ..LifecycleExpiration::default()
+                }),
+                ..LifecycleRule::default()
+            }],
+        }),
+        ..PutBucketLifecycleConfigurationRequest::default()
+    };
+
+    w.lifecycle_error =
+        s3_client().put_bucket_lifecycle_configuration(req).await.err().map(
+            |e| match e {
+                RusotoError::Unknown(resp) => resp.body_as_str().to_owned(),
+                e => e.to_string(),
+            },
+        );
+}
+
+#[when(regex = r"^lifecycle configuration of `(\S+)` bucket is deleted$")]
+async fn lifecycle_deleted(w: &mut World, bucket: String) {
+    let req = DeleteBucketLifecycleRequest {
+        bucket: w.unique.filename(bucket),
+        ..DeleteBucketLifecycleRequest::default()
+    };
+
+    s3_client().delete_bucket_lifecycle(req).await.unwrap_or_else(|e| {
+        panic!("`DeleteBucketLifecycleRequest` failed: {e}")
+    });
+}
+
+#[then(regex = "^lifecycle configuration of `(\\S+)` bucket expires \
+                 `(\\S+)` objects after (\\d+) days?$")]
+async fn lifecycle_is_stored(
+    w: &mut World,
+    bucket: String,
+    prefix: String,
+    days: i64,
+) {
+    assert!(
+        w.lifecycle_error.is_none(),
+        "`PutBucketLifecycleConfigurationRequest` failed: {:?}",
+        w.lifecycle_error,
+    );
+
+    let req = GetBucketLifecycleConfigurationRequest {
+        bucket: w.unique.filename(bucket),
+        ..GetBucketLifecycleConfigurationRequest::default()
+    };
+
+    let rules = s3_client()
+        .get_bucket_lifecycle_configuration(req)
+        .await
+        .unwrap_or_else(|e| {
+            panic!("`GetBucketLifecycleConfigurationRequest` failed: {e}")
+        })
+        .rules
+        .unwrap_or_default();
+
+    assert_eq!(rules.len(), 1, "Unexpected lifecycle rules: {rules:#?}");
+    assert_eq!(rules[0].id.as_deref(), Some("expire"));
+    assert_eq!(rules[0].status, "Enabled");
+    assert_eq!(
+        rules[0].filter.as_ref().and_then(|f| f.prefix.clone()),
+        Some(prefix),
+    );
+    assert_eq!(rules[0].expiration.as_ref().and_then(|e| e.days), Some(days));
+}
+
+#[then(regex = r"^`(\S+)` bucket has no lifecycle configuration$")]
+async fn no_lifecycle_is_stored(w: &mut World, bucket: String) {
+    let req = GetBucketLifecycleConfigurationRequest {
+        bucket: w.unique.filename(bucket),
+        ..GetBucketLifecycleConfigurationRequest::default()
+    };
+
+    match s3_client().get_bucket_lifecycle_configuration(req).await {
+        Err(RusotoError::Unknown(resp))
+            if resp.body_as_str().contains("NoSuchLifecycleConfiguration") => {}
+        res => {
+            panic!("Expected `NoSuchLifecycleConfiguration`, got: {res:#?}")
+        }
+    }
+}
+
+#[then(regex = r"^`(\S+)` error is returned on lifecycle configuration$")]
+fn lifecycle_is_rejected(w: &mut World, code: String) {
+    let err = w
+        .lifecycle_error
+        .as_deref()
+        .expect("`PutBucketLifecycleConfigurationRequest` succeeded");
+    assert!(err.contains(&code), "Expected `{code}`, got: {err}");
+}
@@ -1,5 +1,17 @@
 //! S3 HTTP API E2E (end-to-end) tests.
 
+mod compression;
+mod confinement;
+mod cors;
+mod encryption;
+mod lifecycle;
+mod object_lock;
+mod post_object;
+mod publish;
+mod quota;
+mod symlink;
+mod versioning;
+
 use std::{collections::HashMap, io, mem};
 
 use baza::futures::{StreamExt as _, stream};
@@ -13,6 +25,10 @@ use rusoto_s3::{
 };
 use tokio::io::AsyncReadExt as _;
 
+pub(super) use self::{
+    cors::CorsResponse,
+    post_object::{PostObjectResponse, PostPolicy},
+};
 use super::{DATA_DIR, World, sample_file};
 
 /// URL of S3 HTTP API to run E2E tests against.
@@ -50,7 +66,13 @@ async fn symlink_is_uploaded(
     bucket: String,
     original: String,
 ) {
-    put_object(bucket, w.unique.filename(key), &[], Some(original)).await
+    put_object(
+        bucket,
+        w.unique.filename(key),
+        &[],
+        Some(w.unique.filename(original)),
+    )
+    .await
 }
 
 #[then(regex = r"^`(\S+)` is stored as `(\S+)`$")]
@@ -59,11 +81,7 @@ async fn file_is_stored(
     sample: String,
     path: String,
 ) -> io::Result<()> {
-    let filename = w.unique.filename(path);
-    let stored = async_fs::read(format!("{DATA_DIR}/{filename}")).await?;
-
-    assert!(sample_file(sample) == stored, "Bytes don't match");
-    Ok(())
+    assert_stored(sample, w.unique.filename(path)).await
 }
 
 #[then(regex = r"^`(\S+)` is accessible via `(\S+)`$")]
@@ -72,19 +90,11 @@ async fn file_is_accessible(
     sample: String,
     path: String,
 ) -> io::Result<()> {
-    // We are forced to handle symlinks manually, because Dockerized application
-    // has different absolute paths.
     let filename = w.unique.filename(path);
-    let src = async_fs::read_link(format!("{DATA_DIR}/{filename}"))
-        .await?
-        .display()
-        .to_string()
-        .split(DATA_DIR.trim_matches('.').trim_matches('/'))
-        .nth(1)
-        .unwrap()
-        .trim_matches('/')
-        .to_owned();
-    file_is_stored(w, sample, src).await
+    let target = async_fs::read_link(format!("{DATA_DIR}/{filename}")).await?;
+    assert!(target.is_relative(), "Symlink target is absolute: {target:?}");
+
+    assert_stored(sample, filename).await
 }
 
 #[when("trying to upload files with the following keys:")]
@@ -111,7 +121,7 @@ async fn invalid_argument_error(w: &mut World) {
 #[when(regex = r"^trying to load `(\S+)` from `(\S+)` bucket$")]
 async fn trying_to_load_file(w: &mut World, key: String, bucket: String) {
     w.get_object_response =
-        Some(try_get_object(bucket, w.unique.filename(key)).await);
+        Some(try_get_object(bucket, w.unique.filename(key), None).await);
 }
 
 #[then(regex = r"^`(\S+)` file is returned$")]
@@ -135,6 +145,30 @@ async fn error_is_returned(w: &mut World) {
     }
 }
 
+#[then(regex = r"^`(\S+)` GetObject error is returned$")]
+async fn get_object_error_is_returned(w: &mut World, code: String) {
+    let res = w.last_get_object_response();
+    match &res {
+        Err(RusotoError::Unknown(resp))
+            if resp.body_as_str().contains(&format!("<Code>{code}</Code>")) => {
+        }
+        _ => panic!("Expected `{code}` error, got: {res:#?}"),
+    }
+}
+
+/// Asserts the provided `sample` file to be stored as the provided `filename`
+/// in the [`DATA_DIR`].
+async fn assert_stored(
+    sample: impl AsRef<str>,
+    filename: impl AsRef<str>,
+) -> io::Result<()> {
+    let filename = filename.as_ref();
+    let stored = async_fs::read(format!("{DATA_DIR}/{filename}")).await?;
+
+    assert!(sample_file(sample) == stored, "Bytes don't match");
+    Ok(())
+}
+
 fn assert_invalid_argument(res: Result<(), RusotoError<PutObjectError>>) {
     match &res {
         Err(RusotoError::Unknown(resp))
@@ -182,10 +216,12 @@ async fn try_put_object(
 async fn try_get_object(
     bucket: impl ToString,
     key: impl ToString,
+    version_id: Option<String>,
 ) -> GetObjectResponse {
     let req = GetObjectRequest {
         bucket: bucket.to_string(),
         key: key.to_string(),
+        version_id,
         ..GetObjectRequest::default()
     };
 
@@ -0,0 +1,45 @@
+Feature: Object Lock
+
+  Scenario: Object Lock: configuration is stored
+    Given `lock` bucket has default GOVERNANCE retention of 3 days
+    Then `lock` bucket has default GOVERNANCE retention of 3 days
+
+  Scenario: Object Lock: governance retention prevents deletion
+    Given `lock` bucket has default GOVERNANCE retention of 1 day
+    And `rms.jpg` was put into `lock` bucket as `file`
+    When `file` is removed from `lock` bucket
+    Then request is rejected with `AccessDenied` error
+
+  Scenario: Object Lock: governance retention can be bypassed
+    Given `lock` bucket has default GOVERNANCE retention of 1 day
+    And `rms.jpg` was put into `lock` bucket as `file`
+    When `file` is removed from `lock` bucket bypassing governance
+    Then request succeeds
+    And `file` is absent in `lock` bucket
+
+  Scenario: Object Lock: compliance retention cannot be bypassed
+    Given `lock` bucket has default COMPLIANCE retention of 1 day
+    And `rms.jpg` was put into `lock` bucket as `file`
+    When retention of `file` in `lock` bucket is removed bypassing governance
+    Then request is rejected with `AccessDenied` error
+
+  Scenario: Object Lock: compliance retention can be extended
+    Given `lock` bucket has default COMPLIANCE retention of 1 day
+    And `rms.jpg` was put into `lock` bucket as `file`
+    When retention of `file` in `lock` bucket is extended
+    Then request succeeds
+
+  Scenario: Object Lock: legal hold prevents overwriting
+    Given `lock` bucket has Object Lock enabled
+    And `rms.jpg` was put into `lock` bucket as `file`
+    And legal hold was placed on `file` in `lock` bucket
+    When `file` is overwritten in `lock` bucket
+    Then request is rejected with `AccessDenied` error
+
+  Scenario: Object Lock: released legal hold allows overwriting
+    Given `lock` bucket has Object Lock enabled
+    And `rms.jpg` was put into `lock` bucket as `file`
+    And legal hold was placed on `file` in `lock` bucket
+    When legal hold is released from `file` in `lock` bucket
+    And `file` is overwritten in `lock` bucket
+    Then request succeeds
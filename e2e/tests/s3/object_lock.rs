@@ -0,0 +1,214 @@
+//! Object Lock E2E (end-to-end) tests.
+
+use std::time::{Duration, SystemTime};
+
+use cucumber::{given, then, when};
+use rusoto_core::RusotoError;
+use rusoto_s3::{
+    DefaultRetention, DeleteObjectRequest, GetObjectLockConfigurationRequest,
+    ObjectLockConfiguration, ObjectLockLegalHold, ObjectLockRetention,
+    ObjectLockRule, PutObjectLegalHoldRequest,
+    PutObjectLockConfigurationRequest, PutObjectRetentionRequest, S3 as _,
+};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use super::{s3_client, try_put_object};
+use crate::{World, sample_file};
+
+#[given(regex = r"^`(\S+)` bucket has Object Lock enabled$")]
+async fn object_lock_enabled(w: &mut World, bucket: String) {
+    put_object_lock_configuration(w, bucket, None).await;
+}
+
+#[given(regex = "^`(\\S+)` bucket has default (GOVERNANCE|COMPLIANCE) \
+                 retention of (\\d+) days?$")]
+async fn default_retention_configured(
+    w: &mut World,
+    bucket: String,
+    mode: String,
+    days: i64,
+) {
+    let rule = ObjectLockRule {
+        default_retention: Some(DefaultRetention {
+            mode: Some(mode),
+            days: Some(days),
+            years: None,
+        }),
+    };
+    put_object_lock_configuration(w, bucket, Some(rule)).await;
+}
+
+#[given(regex = "^legal hold was (placed on|released from) `(\\S+)` in \
+                  `(\\S+)` bucket$")]
+#[when(regex = "^legal hold is (placed on|released from) `(\\S+)` in \
+                  `(\\S+)` bucket$")]
+async fn legal_hold_is_set(
+    w: &mut World,
+    action: String,
+    key: String,
+    bucket: String,
+) {
+    let req = PutObjectLegalHoldRequest {
+        bucket: w.unique.filename(bucket),
+        key,
+        legal_hold: Some(ObjectLockLegalHold {
+            status: Some(
+                if action == "placed on" { "ON" } else { "OFF" }.into(),
+            ),
+        }),
+        ..PutObjectLegalHoldRequest::default()
+    };
+
+    _ = s3_client()
+        .put_object_legal_hold(req)
+        .await
+        .unwrap_or_else(|e| panic!("`PutObjectLegalHoldRequest` failed: {e}"));
+}
+
+#[when(regex = r"^`(\S+)` is overwritten in `(\S+)` bucket$")]
+async fn object_is_overwritten(w: &mut World, key: String, bucket: String) {
+    let res = try_put_object(
+        w.unique.filename(bucket),
+        key,
+        sample_file("ignucius.jpg"),
+        None::<String>,
+    )
+    .await;
+    w.object_lock_error = res.err().map(error_body);
+}
+
+#[when(regex = "^`(\\S+)` is removed from `(\\S+)` bucket( bypassing \
+                  governance|)$")]
+async fn object_is_deleted(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    bypass: String,
+) {
+    let req = DeleteObjectRequest {
+        bucket: w.unique.filename(bucket),
+        key,
+        bypass_governance_retention: (!bypass.is_empty()).then_some(true),
+        ..DeleteObjectRequest::default()
+    };
+
+    w.object_lock_error =
+        s3_client().delete_object(req).await.err().map(error_body);
+}
+
+#[when(regex = "^retention of `(\\S+)` in `(\\S+)` bucket is removed( \
+                  bypassing governance|)$")]
+async fn retention_is_removed(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    bypass: String,
+) {
+    let req = PutObjectRetentionRequest {
+        bucket: w.unique.filename(bucket),
+        key,
+        bypass_governance_retention: (!bypass.is_empty()).then_some(true),
+        retention: Some(ObjectLockRetention::default()),
+        ..PutObjectRetentionRequest::default()
+    };
+
+    w.object_lock_error =
+        s3_client().put_object_retention(req).await.err().map(error_body);
+}
+
+#[when(regex = r"^retention of `(\S+)` in `(\S+)` bucket is extended$")]
+async fn retention_is_extended(w: &mut World, key: String, bucket: String) {
+    let until = SystemTime::now() + Duration::from_secs(7 * 24 * 60 * 60);
+    let req = PutObjectRetentionRequest {
+        bucket: w.unique.filename(bucket),
+        key,
+        retention: Some(ObjectLockRetention {
+            mode: Some("COMPLIANCE".into()),
+            retain_until_date: Some(
+                OffsetDateTime::from(until).format(&Rfc3339).unwrap(),
+            ),
+        }),
+        ..PutObjectRetentionRequest::default()
+    };
+
+    w.object_lock_error =
+        s3_client().put_object_retention(req).await.err().map(error_body);
+}
+
+#[then("request succeeds")]
+fn request_succeeds(w: &mut World) {
+    assert!(
+        w.object_lock_error.is_none(),
+        "Request failed: {:?}",
+        w.object_lock_error,
+    );
+}
+
+#[then(regex = r"^request is rejected with `(\S+)` error$")]
+fn request_is_rejected(w: &mut World, code: String) {
+    let err = w.object_lock_error.as_deref().expect("Request succeeded");
+    assert!(
+        err.contains(&format!("<Code>{code}</Code>")),
+        "Expected `{code}`, got: {err}",
+    );
+}
+
+#[then(regex = "^`(\\S+)` bucket has default (GOVERNANCE|COMPLIANCE) \
+                retention of (\\d+) days?$")]
+async fn default_retention_is_returned(
+    w: &mut World,
+    bucket: String,
+    mode: String,
+    days: i64,
+) {
+    let req = GetObjectLockConfigurationRequest {
+        bucket: w.unique.filename(bucket),
+        ..GetObjectLockConfigurationRequest::default()
+    };
+
+    let config = s3_client()
+        .get_object_lock_configuration(req)
+        .await
+        .unwrap_or_else(|e| {
+            panic!("`GetObjectLockConfigurationRequest` failed: {e}")
+        })
+        .object_lock_configuration
+        .expect("No `ObjectLockConfiguration` returned");
+    assert_eq!(config.object_lock_enabled.as_deref(), Some("Enabled"));
+
+    let retention = config
+        .rule
+        .and_then(|r| r.default_retention)
+        .expect("No `DefaultRetention` returned");
+    assert_eq!(retention.mode, Some(mode));
+    assert_eq!(retention.days, Some(days));
+}
+
+/// Puts the [`ObjectLockConfiguration`] with the provided [`ObjectLockRule`]
+/// to the provided `bucket`.
+async fn put_object_lock_configuration(
+    w: &World,
+    bucket: String,
+    rule: Option<ObjectLockRule>,
+) {
+    let req = PutObjectLockConfigurationRequest {
+        bucket: w.unique.filename(bucket),
+        object_lock_configuration: Some(ObjectLockConfiguration {
+            object_lock_enabled: Some("Enabled".into()),
+            rule,
+        }),
+        ..PutObjectLockConfigurationRequest::default()
+    };
+
+    _ = s3_client().put_object_lock_configuration(req).await.unwrap_or_else(
+        |e| panic!("`PutObjectLockConfigurationRequest` failed: {e}"),
+    );
+}
+
+/// Extracts the response body out of the provided [`RusotoError`].
+fn error_body<E: std::error::Error + 'static>(e: RusotoError<E>) -> String {
+    match e {
+        RusotoError::Unknown(resp) => resp.body_as_str().to_owned(),
+        e => e.to_string(),
+    }
+}
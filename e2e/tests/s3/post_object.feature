@@ -0,0 +1,33 @@
+Feature: PostObject
+
+  Scenario: PostObject: form upload
+    When `rms.jpg` is uploaded to `data` bucket as `form/file` via POST form
+    Then `rms.jpg` is stored as `data/form/file`
+
+  Scenario: PostObject: `${filename}` substitution
+    When `rms.jpg` is uploaded to `data` bucket as `form/${filename}` via POST form
+    Then `rms.jpg` is stored as `data/form/rms.jpg`
+
+  Scenario: PostObject: key not matching policy prefix
+    Given POST policy allows only `form/` key prefix
+    When trying to upload `rms.jpg` to `data` bucket as `other/file` via POST form
+    Then `AccessDenied` POST error is returned
+    And nothing is stored as `data/other/file`
+
+  Scenario: PostObject: file exceeding content length range
+    Given POST policy limits file size to 1024 bytes
+    When trying to upload `rms.jpg` to `data` bucket as `form/file` via POST form
+    Then `EntityTooLarge` POST error is returned
+    And nothing is stored as `data/form/file`
+
+  Scenario: PostObject: expired policy
+    Given POST policy has expired
+    When trying to upload `rms.jpg` to `data` bucket as `form/file` via POST form
+    Then `AccessDenied` POST error is returned
+    And nothing is stored as `data/form/file`
+
+  Scenario: PostObject: invalid signature
+    Given POST policy is signed with a wrong secret key
+    When trying to upload `rms.jpg` to `data` bucket as `form/file` via POST form
+    Then `SignatureDoesNotMatch` POST error is returned
+    And nothing is stored as `data/form/file`
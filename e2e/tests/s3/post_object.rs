@@ -0,0 +1,212 @@
+//! [POST Object][0] E2E (end-to-end) tests.
+//!
+//! [0]: https://docs.aws.amazon.com/AmazonS3/latest/API/RESTObjectPOST.html
+
+use std::io;
+
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use cucumber::{given, then, when};
+use hmac::{Hmac, Mac as _};
+use hyper::{Body, Client, Method, Request, header};
+use sha2::Sha256;
+use time::{Duration, OffsetDateTime, macros::format_description};
+
+use super::API_URL;
+use crate::{DATA_DIR, World, sample_file};
+
+/// Boundary of `multipart/form-data` bodies sent in these tests.
+const BOUNDARY: &str = "baza-e2e-form-boundary";
+
+/// Restrictions of a POST policy to upload a form with.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PostPolicy {
+    /// Prefix the uploaded key must start with.
+    key_prefix: Option<String>,
+
+    /// Maximum allowed size of the uploaded file.
+    max_size: Option<u64>,
+
+    /// Indicator whether the policy has expired already.
+    expired: bool,
+
+    /// Indicator whether the policy is signed with a wrong secret key.
+    wrong_secret: bool,
+}
+
+/// Response to a POST form upload.
+#[derive(Clone, Debug)]
+pub(crate) struct PostObjectResponse {
+    /// HTTP status code of the response.
+    status: u16,
+
+    /// Body of the response.
+    body: String,
+}
+
+#[given(regex = r"^POST policy allows only `(\S+)` key prefix$")]
+async fn policy_allows_prefix(w: &mut World, prefix: String) {
+    w.post_policy.key_prefix = Some(prefix);
+}
+
+#[given(regex = r"^POST policy limits file size to (\d+) bytes$")]
+async fn policy_limits_size(w: &mut World, max: u64) {
+    w.post_policy.max_size = Some(max);
+}
+
+#[given("POST policy has expired")]
+async fn policy_expired(w: &mut World) {
+    w.post_policy.expired = true;
+}
+
+#[given("POST policy is signed with a wrong secret key")]
+async fn policy_wrong_secret(w: &mut World) {
+    w.post_policy.wrong_secret = true;
+}
+
+#[when(
+    regex = r"^`(\S+)` is uploaded to `(\S+)` bucket as `(\S+)` via POST form$"
+)]
+async fn file_posted(
+    w: &mut World,
+    sample: String,
+    bucket: String,
+    key: String,
+) {
+    let resp = post_object(w, &sample, &bucket, &key).await;
+    assert_eq!(resp.status, 204, "POST form upload failed: {}", resp.body);
+}
+
+#[when(regex = "^trying to upload `(\\S+)` to `(\\S+)` bucket as `(\\S+)` \
+                 via POST form$")]
+async fn trying_to_post_file(
+    w: &mut World,
+    sample: String,
+    bucket: String,
+    key: String,
+) {
+    w.post_object_response = Some(post_object(w, &sample, &bucket, &key).await);
+}
+
+#[then(regex = r"^`(\S+)` POST error is returned$")]
+async fn post_error_is_returned(w: &mut World, code: String) {
+    let resp = w.post_object_response.take().expect("No POST response");
+
+    assert!(resp.status >= 400, "Expected error, got: {resp:#?}");
+    assert!(
+        resp.body.contains(&format!("<Code>{code}</Code>")),
+        "Expected `{code}` error, got: {resp:#?}",
+    );
+}
+
+#[then(regex = r"^nothing is stored as `(\S+)`$")]
+async fn nothing_is_stored(w: &mut World, path: String) {
+    let filename = w.unique.filename(path);
+    let res = async_fs::metadata(format!("{DATA_DIR}/{filename}")).await;
+
+    assert!(
+        matches!(&res, Err(e) if e.kind() == io::ErrorKind::NotFound),
+        "Expected nothing to be stored, got: {res:#?}",
+    );
+}
+
+/// Uploads the provided `sample` file to the provided `bucket` under the
+/// provided `key` via POST form, signed according to the [`PostPolicy`] of
+/// the provided [`World`].
+async fn post_object(
+    w: &World,
+    sample: &str,
+    bucket: &str,
+    key: &str,
+) -> PostObjectResponse {
+    let now = OffsetDateTime::now_utc();
+    let date = now
+        .format(format_description!("[year][month][day]"))
+        .expect("Failed to format date");
+    let credential = format!("baza/{date}/us-east-1/s3/aws4_request");
+    let expiration = if w.post_policy.expired {
+        now - Duration::MINUTE
+    } else {
+        now + Duration::HOUR
+    };
+
+    let mut conditions = vec![
+        format!(r#"{{"bucket": "{bucket}"}}"#),
+        format!(r#"{{"x-amz-credential": "{credential}"}}"#),
+        r#"{"x-amz-algorithm": "AWS4-HMAC-SHA256"}"#.to_owned(),
+        format!(
+            r#"["starts-with", "$key", "{}"]"#,
+            w.post_policy.key_prefix.as_deref().unwrap_or_default(),
+        ),
+    ];
+    if let Some(max) = w.post_policy.max_size {
+        conditions.push(format!(r#"["content-length-range", 0, {max}]"#));
+    }
+    let policy = BASE64_STANDARD.encode(format!(
+        r#"{{"expiration": "{}", "conditions": [{}]}}"#,
+        expiration
+            .format(&time::format_description::well_known::Rfc3339)
+            .expect("Failed to format expiration"),
+        conditions.join(", "),
+    ));
+
+    let secret = if w.post_policy.wrong_secret { "wrong" } else { "baza" };
+    let signature = [date.as_str(), "us-east-1", "s3", "aws4_request", &policy]
+        .into_iter()
+        .fold(format!("AWS4{secret}").into_bytes(), |key, data| {
+            Hmac::<Sha256>::new_from_slice(&key)
+                .expect("HMAC accepts keys of any size")
+                .chain_update(data)
+                .finalize()
+                .into_bytes()
+                .to_vec()
+        });
+
+    let mut body = Vec::new();
+    for (name, value) in [
+        ("key", w.unique.filename(key)),
+        ("x-amz-algorithm", "AWS4-HMAC-SHA256".to_owned()),
+        ("x-amz-credential", credential),
+        ("x-amz-signature", hex::encode(signature)),
+        ("policy", policy),
+    ] {
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\n\
+                 Content-Disposition: form-data; name=\"{name}\"\r\n\r\n\
+                 {value}\r\n",
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{BOUNDARY}\r\n\
+             Content-Disposition: form-data; name=\"file\"; \
+                                  filename=\"{sample}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(sample_file(sample));
+    body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("{API_URL}/{bucket}"))
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .body(Body::from(body))
+        .expect("Failed to build POST form request");
+    let resp = Client::new()
+        .request(req)
+        .await
+        .unwrap_or_else(|e| panic!("POST form request failed: {e}"));
+
+    let status = resp.status().as_u16();
+    let body = hyper::body::to_bytes(resp.into_body())
+        .await
+        .unwrap_or_else(|e| panic!("Failed to read POST form response: {e}"));
+    PostObjectResponse { status, body: String::from_utf8_lossy(&body).into() }
+}
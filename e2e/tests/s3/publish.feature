@@ -0,0 +1,37 @@
+Feature: Symlinks publishing
+
+  Scenario: Publishing: symlinks are published
+    Given `rms.jpg` was uploaded to `data` bucket as `file1`
+    And `ignucius.jpg` was uploaded to `data` bucket as `file2`
+    When symlinks are published in `release` on `links` bucket:
+      | app.jpg        | data/file1 |
+      | assets/lib.jpg | data/file2 |
+    Then publishing succeeds
+    And `rms.jpg` is published as `app.jpg` in `release` on `links` bucket
+    And `ignucius.jpg` is published as `assets/lib.jpg` in `release` on `links` bucket
+
+  Scenario: Publishing: symlinks are republished
+    Given `rms.jpg` was uploaded to `data` bucket as `file1`
+    And `ignucius.jpg` was uploaded to `data` bucket as `file2`
+    When symlinks are published in `release` on `links` bucket:
+      | app.jpg | data/file1 |
+      | old.jpg | data/file1 |
+    And symlinks are published in `release` on `links` bucket:
+      | app.jpg | data/file2 |
+    Then publishing succeeds
+    And `ignucius.jpg` is published as `app.jpg` in `release` on `links` bucket
+    And `old.jpg` is not published in `release` on `links` bucket
+
+  Scenario: Publishing: regular file is not replaced
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    And `rms.jpg` was uploaded to `links` bucket as `release`
+    When symlinks are published in `release` on `links` bucket:
+      | app.jpg | data/file |
+    Then publishing is rejected with `PreconditionFailed` error
+
+  Scenario: Publishing: conflicting symlinks are rejected
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    When symlinks are published in `release` on `links` bucket:
+      | app     | data/file |
+      | app/lib | data/file |
+    Then publishing is rejected with `InvalidArgument` error
@@ -0,0 +1,125 @@
+//! Symlinks publishing E2E (end-to-end) tests.
+
+use cucumber::{gherkin::Step, then, when};
+use rusoto_core::{
+    DispatchSignedRequest as _, HttpClient, Region, RusotoError,
+    signature::SignedRequest,
+};
+use rusoto_credential::AwsCredentials;
+use rusoto_s3::{GetObjectError, GetObjectRequest, S3 as _};
+use tokio::io::AsyncReadExt as _;
+
+use super::{API_URL, s3_client};
+use crate::{World, sample_file};
+
+#[when(regex = r"^symlinks are published in `(\S+)` on `(\S+)` bucket:$")]
+async fn symlinks_are_published(
+    w: &mut World,
+    dir: String,
+    bucket: String,
+    step: &Step,
+) {
+    let manifest = step
+        .table()
+        .expect("No data table present in the step")
+        .rows
+        .iter()
+        .map(|row| {
+            format!(
+                "<Symlink><Key>{}</Key><Target>{}</Target></Symlink>",
+                row[0],
+                w.unique.filename(&row[1]),
+            )
+        })
+        .collect::<String>();
+
+    let region =
+        Region::Custom { name: "test".into(), endpoint: API_URL.into() };
+    let dir = w.unique.filename(dir);
+    let mut req =
+        SignedRequest::new("PUT", "s3", &region, &format!("/{bucket}/{dir}"));
+    req.add_param("symlinks", "");
+    req.set_payload(Some(format!(
+        "<SymlinkManifest>{manifest}</SymlinkManifest>",
+    )));
+    req.sign(&AwsCredentials::new("baza", "baza", None, None));
+
+    let resp = HttpClient::new()
+        .expect("Failed to initialize Rusoto HTTP client")
+        .dispatch(req, None)
+        .await
+        .expect("Failed to send request")
+        .buffer()
+        .await
+        .expect("Failed to read response");
+    w.publish_error =
+        (resp.status.as_u16() != 200).then(|| resp.body_as_str().to_owned());
+}
+
+#[then("publishing succeeds")]
+fn publishing_succeeds(w: &mut World) {
+    assert!(
+        w.publish_error.is_none(),
+        "Publishing failed: {:?}",
+        w.publish_error,
+    );
+}
+
+#[then(regex = r"^publishing is rejected with `(\S+)` error$")]
+fn publishing_is_rejected(w: &mut World, code: String) {
+    let err = w.publish_error.as_deref().expect("Publishing succeeded");
+    assert!(
+        err.contains(&format!("<Code>{code}</Code>")),
+        "Expected `{code}`, got: {err}",
+    );
+}
+
+#[then(regex = "^`(\\S+)` is published as `(\\S+)` in `(\\S+)` \
+                on `(\\S+)` bucket$")]
+async fn file_is_published(
+    w: &mut World,
+    sample: String,
+    key: String,
+    dir: String,
+    bucket: String,
+) {
+    let req = GetObjectRequest {
+        bucket,
+        key: format!("{}/{key}", w.unique.filename(dir)),
+        ..GetObjectRequest::default()
+    };
+
+    let resp = s3_client()
+        .get_object(req)
+        .await
+        .unwrap_or_else(|e| panic!("`GetObjectRequest` failed: {e}"));
+    let mut body = Vec::new();
+    _ = resp
+        .body
+        .expect("No body in `GetObject` response")
+        .into_async_read()
+        .read_to_end(&mut body)
+        .await
+        .expect("Failed to read `GetObject` response body");
+    assert!(sample_file(sample) == body, "Bytes don't match");
+}
+
+#[then(regex = r"^`(\S+)` is not published in `(\S+)` on `(\S+)` bucket$")]
+async fn file_is_not_published(
+    w: &mut World,
+    key: String,
+    dir: String,
+    bucket: String,
+) {
+    let req = GetObjectRequest {
+        bucket,
+        key: format!("{}/{key}", w.unique.filename(dir)),
+        ..GetObjectRequest::default()
+    };
+
+    let res = s3_client().get_object(req).await;
+    assert!(
+        matches!(res, Err(RusotoError::Service(GetObjectError::NoSuchKey(_)))),
+        "Expected `NoSuchKey` error, got: {res:#?}",
+    );
+}
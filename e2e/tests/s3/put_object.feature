@@ -21,6 +21,11 @@ Feature: PutObject
     When `link` symlink is created on `links` bucket pointing to `data/dir/file2`
     Then `ignucius.jpg` is accessible via `links/link`
 
+  Scenario: PutObject: under regular file is not a failed precondition
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    When trying to upload `ignucius.jpg` as `nested` under `file` to `data` bucket
+    Then upload is rejected with `InternalError` error
+
   Scenario: PubObject: invalid keys: root component
     When trying to upload files with the following keys:
       | /abc   |
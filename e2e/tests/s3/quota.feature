@@ -0,0 +1,30 @@
+Feature: Bucket quota
+
+  Scenario: Bucket quota: object count is limited
+    Given `quota` bucket is limited to 1 object
+    And `rms.jpg` was put into `quota` bucket as `file1`
+    When `ignucius.jpg` is put into `quota` bucket as `file2`
+    Then upload is rejected with `QuotaExceeded` error
+    And `file2` is absent in `quota` bucket
+
+  Scenario: Bucket quota: size is limited
+    Given `quota` bucket is limited to 130000 bytes
+    When `ignucius.jpg` is put into `quota` bucket as `file`
+    Then upload is rejected with `QuotaExceeded` error
+    And `file` is absent in `quota` bucket
+    And `quota` bucket uses 0 bytes in 0 objects
+
+  Scenario: Bucket quota: overwrite is tracked
+    Given `quota` bucket is limited to 1 object
+    And `rms.jpg` was put into `quota` bucket as `file`
+    When `ignucius.jpg` is put into `quota` bucket as `file`
+    Then upload succeeds
+    And `quota` bucket uses 135787 bytes in 1 object
+
+  Scenario: Bucket quota: deletion frees quota
+    Given `quota` bucket is limited to 1 object
+    And `rms.jpg` was put into `quota` bucket as `file1`
+    And `file1` was removed from `quota` bucket
+    When `ignucius.jpg` is put into `quota` bucket as `file2`
+    Then upload succeeds
+    And `quota` bucket uses 135787 bytes in 1 object
@@ -0,0 +1,130 @@
+//! Bucket quota E2E (end-to-end) tests.
+
+use cucumber::{given, then, when};
+use rusoto_core::{
+    DispatchSignedRequest as _, HttpClient, Region, RusotoError,
+    signature::SignedRequest,
+};
+use rusoto_credential::AwsCredentials;
+use rusoto_s3::{DeleteObjectRequest, GetObjectError, S3 as _};
+
+use super::{API_URL, s3_client, try_get_object, try_put_object};
+use crate::{World, sample_file};
+
+#[given(regex = r"^`(\S+)` bucket is limited to (\d+) (bytes|objects?)$")]
+async fn quota_configured(
+    w: &mut World,
+    bucket: String,
+    limit: u64,
+    unit: String,
+) {
+    let tag = if unit == "bytes" { "MaxBytes" } else { "MaxObjects" };
+    let body = format!(
+        "<QuotaConfiguration><{tag}>{limit}</{tag}></QuotaConfiguration>",
+    );
+
+    let (status, body) =
+        send_quota_request("PUT", &w.unique.filename(bucket), Some(body)).await;
+    assert_eq!(status, 200, "`PutBucketQuota` failed: {body}");
+}
+
+#[given(regex = r"^`(\S+)` was put into `(\S+)` bucket as `(\S+)`$")]
+#[when(regex = r"^`(\S+)` is put into `(\S+)` bucket as `(\S+)`$")]
+async fn object_is_put(
+    w: &mut World,
+    sample: String,
+    bucket: String,
+    key: String,
+) {
+    let res = try_put_object(
+        w.unique.filename(bucket),
+        key,
+        sample_file(sample),
+        None::<String>,
+    )
+    .await;
+    w.upload_error = res.err().map(|e| match e {
+        RusotoError::Unknown(resp) => resp.body_as_str().to_owned(),
+        e => e.to_string(),
+    });
+}
+
+#[given(regex = r"^`(\S+)` was removed from `(\S+)` bucket$")]
+async fn object_is_removed(w: &mut World, key: String, bucket: String) {
+    let req = DeleteObjectRequest {
+        bucket: w.unique.filename(bucket),
+        key,
+        ..DeleteObjectRequest::default()
+    };
+
+    _ = s3_client()
+        .delete_object(req)
+        .await
+        .unwrap_or_else(|e| panic!("`DeleteObjectRequest` failed: {e}"));
+}
+
+#[then("upload succeeds")]
+fn upload_succeeds(w: &mut World) {
+    assert!(w.upload_error.is_none(), "Upload failed: {:?}", w.upload_error);
+}
+
+#[then(regex = r"^upload is rejected with `(\S+)` error$")]
+fn upload_is_rejected(w: &mut World, code: String) {
+    let err = w.upload_error.as_deref().expect("Upload succeeded");
+    assert!(
+        err.contains(&format!("<Code>{code}</Code>")),
+        "Expected `{code}`, got: {err}",
+    );
+}
+
+#[then(regex = r"^`(\S+)` is absent in `(\S+)` bucket$")]
+async fn object_is_absent(w: &mut World, key: String, bucket: String) {
+    let res = try_get_object(w.unique.filename(bucket), key, None).await;
+    match res {
+        Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => {}
+        _ => panic!("Expected `NoSuchKey` error, got: {res:#?}"),
+    }
+}
+
+#[then(regex = r"^`(\S+)` bucket uses (\d+) bytes in (\d+) objects?$")]
+async fn usage_is_tracked(
+    w: &mut World,
+    bucket: String,
+    bytes: u64,
+    objects: u64,
+) {
+    let (status, body) =
+        send_quota_request("GET", &w.unique.filename(bucket), None).await;
+    assert_eq!(status, 200, "`GetBucketQuota` failed: {body}");
+
+    let usage =
+        format!("<Usage><Bytes>{bytes}</Bytes><Objects>{objects}</Objects>");
+    assert!(body.contains(&usage), "Unexpected usage: {body}");
+}
+
+/// Sends a signed request with the provided `method` to the `?quota`
+/// subresource of the provided `bucket`, returning the status code and the
+/// body of its response.
+async fn send_quota_request(
+    method: &str,
+    bucket: &str,
+    body: Option<String>,
+) -> (u16, String) {
+    let region =
+        Region::Custom { name: "test".into(), endpoint: API_URL.into() };
+    let mut req =
+        SignedRequest::new(method, "s3", &region, &format!("/{bucket}"));
+    req.add_param("quota", "");
+    req.set_payload(body);
+    req.sign(&AwsCredentials::new("baza", "baza", None, None));
+
+    let resp = HttpClient::new()
+        .expect("Failed to initialize Rusoto HTTP client")
+        .dispatch(req, None)
+        .await
+        .expect("Failed to send request")
+        .buffer()
+        .await
+        .expect("Failed to read response");
+    (resp.status.as_u16(), resp.body_as_str().to_owned())
+}
@@ -0,0 +1,135 @@
+Feature: Symlinks
+
+  Scenario: Symlink: target is reported
+    Given `rms.jpg` was uploaded to `data` bucket as `dir/file`
+    And `dir/link` symlink was created on `links` bucket pointing to `data/dir/file`
+    Then `dir/link` on `links` bucket is reported as symlink to `data/dir/file`
+    And `dir/file` on `data` bucket is reported as regular file
+
+  Scenario: Symlink: symlink itself is loaded
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    And `link` symlink was created on `links` bucket pointing to `data/file`
+    When `link` symlink itself is loaded from `links` bucket
+    Then symlink to `data/file` is returned
+
+  Scenario: Symlink: regular file is loaded as is
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    When `file` symlink itself is loaded from `data` bucket
+    Then `rms.jpg` file is returned
+
+  Scenario: Symlink: repoint with expected target
+    Given `rms.jpg` was uploaded to `data` bucket as `file1`
+    And `ignucius.jpg` was uploaded to `data` bucket as `file2`
+    And `link` symlink was created on `links` bucket pointing to `data/file1`
+    When `link` symlink is repointed on `links` bucket to `data/file2` expecting `data/file1`
+    Then symlink is accepted
+    And `ignucius.jpg` is accessible via `links/link`
+
+  Scenario: Symlink: repoint with outdated expected target
+    Given `rms.jpg` was uploaded to `data` bucket as `file1`
+    And `ignucius.jpg` was uploaded to `data` bucket as `file2`
+    And `link` symlink was created on `links` bucket pointing to `data/file2`
+    When `link` symlink is repointed on `links` bucket to `data/file1` expecting `data/file0`
+    Then symlink is rejected with `PreconditionFailed` error
+    And `ignucius.jpg` is accessible via `links/link`
+
+  Scenario: Symlink: repoint of absent symlink
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    When `link` symlink is repointed on `links` bucket to `data/file` expecting `data/file`
+    Then symlink is rejected with `PreconditionFailed` error
+
+  Scenario: Symlink: dangling symlink is rejected
+    When trying to create `link` symlink on `links` bucket pointing to `data/file`
+    Then symlink is rejected with `InvalidArgument` error
+
+  Scenario: Symlink: dangling symlink is allowed explicitly
+    When trying to create `link` symlink on `links` bucket pointing to `data/file` as dangling
+    Then symlink is accepted
+
+  Scenario: Symlink: symlink chain is resolved
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    And `link1` symlink was created on `links` bucket pointing to `data/file`
+    When trying to create `link2` symlink on `links` bucket pointing to `links/link1`
+    Then symlink is accepted
+    And `rms.jpg` is accessible via `links/link2`
+
+  Scenario: Symlink: symlink cycle is rejected
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    And `link1` symlink was created on `links` bucket pointing to `data/file`
+    And `link2` symlink was created on `links` bucket pointing to `links/link1`
+    When trying to create `link1` symlink on `links` bucket pointing to `links/link2`
+    Then symlink is rejected with `InvalidArgument` error
+    And `rms.jpg` is accessible via `links/link1`
+
+  Scenario: Symlink: symlinks pointing to an object are listed
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    And `ignucius.jpg` was uploaded to `data` bucket as `other`
+    And `link1` symlink was created on `links` bucket pointing to `data/file`
+    And `link2` symlink was created on `links` bucket pointing to `data/file`
+    And `link2` symlink was created on `links` bucket pointing to `data/other`
+    When symlinks pointing to `data/file` are listed
+    Then `links/link1` symlink is listed
+    And `links/link2` symlink is not listed
+
+  Scenario: Symlink: target relative to symlink directory
+    Given `rms.jpg` was uploaded to `links` bucket as `dir/v2/file`
+    When `dir/link` symlink is created on `links` bucket pointing to `./v2/file`
+    Then `rms.jpg` is accessible via `links/dir/link`
+
+  Scenario: Symlink: target relative to parent directory
+    Given `rms.jpg` was uploaded to `links` bucket as `file`
+    When `dir/link` symlink is created on `links` bucket pointing to `../file`
+    Then `rms.jpg` is accessible via `links/dir/link`
+
+  Scenario: Symlink: target relative to symlink bucket
+    Given `rms.jpg` was uploaded to `links` bucket as `file`
+    When `dir/sub/link` symlink is created on `links` bucket pointing to `/file`
+    Then `rms.jpg` is accessible via `links/dir/sub/link`
+
+  Scenario: Symlink: relative target escaping bucket is rejected
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    When trying to create `link` symlink on `links` bucket pointing to `../data/file`
+    Then symlink is rejected with `InvalidArgument` error
+
+  Scenario: Symlink: hard link is created
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    When trying to create `link` symlink on `links` bucket pointing to `data/file` as hard link
+    Then symlink is accepted
+    And `links/link` is a hard link to `data/file`
+    When `ignucius.jpg` is uploaded to `data` bucket as `file`
+    Then `rms.jpg` is stored as `links/link`
+
+  Scenario: Symlink: hard link to absent file is rejected
+    When trying to create `link` symlink on `links` bucket pointing to `data/file` as hard link
+    Then symlink is rejected with `InvalidArgument` error
+
+  Scenario: Symlink: rollback to previous target
+    Given `rms.jpg` was uploaded to `data` bucket as `file1`
+    And `ignucius.jpg` was uploaded to `data` bucket as `file2`
+    And `link` symlink was created on `links` bucket pointing to `data/file1`
+    And `link` symlink was created on `links` bucket pointing to `data/file2`
+    Then `data/file1` is the previous target of `links/link` symlink
+    When `link` symlink is rolled back on `links` bucket
+    Then symlink is accepted
+    And `rms.jpg` is accessible via `links/link`
+    When `link` symlink is rolled back on `links` bucket
+    Then symlink is rejected with `InvalidArgument` error
+
+  Scenario: Symlink: directory alias resolves files beneath
+    When trying to upload `rms.jpg` as `file` under `releases` to `data` bucket
+    And trying to create `current` directory alias on `links` bucket pointing to `data/releases`
+    Then symlink is accepted
+    When trying to load `file` under `current` from `links` bucket
+    Then `rms.jpg` file is returned
+    And `current` has 1 version and 0 delete markers in `links` bucket
+
+  Scenario: Symlink: writing through directory alias is rejected
+    When trying to upload `rms.jpg` as `file` under `releases` to `data` bucket
+    And trying to create `current` directory alias on `links` bucket pointing to `data/releases`
+    And trying to upload `ignucius.jpg` as `file` through `current` alias to `links` bucket
+    Then upload is rejected with `AccessDenied` error
+
+  Scenario: Symlink: directory alias to file is rejected
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    When trying to create `current` directory alias on `links` bucket pointing to `data/file`
+    Then symlink is rejected with `InvalidArgument` error
@@ -0,0 +1,319 @@
+//! Symlinks E2E (end-to-end) tests.
+
+use std::{collections::HashMap, io, os::unix::fs::MetadataExt as _};
+
+use baza_api_s3 as s3;
+use cucumber::{then, when};
+use rusoto_core::{
+    DispatchSignedRequest as _, HttpClient, Region, RusotoError,
+    request::BufferedHttpResponse, signature::SignedRequest,
+};
+use rusoto_credential::AwsCredentials;
+use rusoto_s3::{
+    GetObjectRequest, HeadObjectRequest, PutObjectRequest, S3 as _,
+};
+
+use super::{API_URL, s3_client};
+use crate::{DATA_DIR, World, sample_file};
+
+#[when(regex = r"^`(\S+)` symlink itself is loaded from `(\S+)` bucket$")]
+async fn symlink_itself_is_loaded(w: &mut World, key: String, bucket: String) {
+    let region =
+        Region::Custom { name: "test".into(), endpoint: API_URL.into() };
+    let key = w.unique.filename(key);
+    let mut req =
+        SignedRequest::new("GET", "s3", &region, &format!("/{bucket}/{key}"));
+    req.add_param("symlink", "");
+    req.sign(&AwsCredentials::new("baza", "baza", None, None));
+
+    let resp = HttpClient::new()
+        .expect("Failed to initialize Rusoto HTTP client")
+        .dispatch(req, None)
+        .await
+        .expect("Failed to send request")
+        .buffer()
+        .await
+        .expect("Failed to read response");
+    assert_eq!(
+        resp.status.as_u16(),
+        200,
+        "`GetObject` failed: {}",
+        resp.body_as_str(),
+    );
+
+    w.symlink_to = resp
+        .headers
+        .get(format!("x-amz-meta-{}", s3::SYMLINK_META_KEY).as_str())
+        .cloned();
+    w.get_object_response = Some(Ok(resp.body.to_vec()));
+}
+
+#[then(regex = r"^symlink to `(\S+)` is returned$")]
+fn symlink_is_returned(w: &mut World, target: String) {
+    let body = w
+        .last_get_object_response()
+        .unwrap_or_else(|e| panic!("`GetObjectRequest` failed: {e}"));
+
+    assert!(body.is_empty(), "Symlink has contents");
+    assert_eq!(w.symlink_to, Some(w.unique.filename(target)));
+}
+
+#[then(regex = "^`(\\S+)` on `(\\S+)` bucket is reported as \
+                (regular file|symlink to `\\S+`)$")]
+async fn symlink_is_reported(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    kind: String,
+) {
+    let key = w.unique.filename(key);
+    let expected = kind
+        .strip_prefix("symlink to `")
+        .map(|t| w.unique.filename(t.trim_end_matches('`')));
+
+    let head = s3_client()
+        .head_object(HeadObjectRequest {
+            bucket: bucket.clone(),
+            key: key.clone(),
+            ..HeadObjectRequest::default()
+        })
+        .await
+        .unwrap_or_else(|e| panic!("`HeadObjectRequest` failed: {e}"));
+    let get = s3_client()
+        .get_object(GetObjectRequest {
+            bucket,
+            key,
+            ..GetObjectRequest::default()
+        })
+        .await
+        .unwrap_or_else(|e| panic!("`GetObjectRequest` failed: {e}"));
+
+    for metadata in [head.metadata, get.metadata] {
+        assert_eq!(
+            metadata.and_then(|mut m| m.remove(s3::SYMLINK_META_KEY)),
+            expected,
+        );
+    }
+}
+
+#[when(regex = "^`(\\S+)` symlink is repointed on `(\\S+)` bucket \
+                to `(\\S+)` expecting `(\\S+)`$")]
+async fn symlink_is_repointed(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    target: String,
+    expected: String,
+) {
+    let meta = HashMap::from([
+        (s3::SYMLINK_META_KEY.into(), w.unique.filename(target)),
+        (s3::SYMLINK_EXPECT_META_KEY.into(), w.unique.filename(expected)),
+    ]);
+    w.symlink_error =
+        try_put_symlink(bucket, w.unique.filename(key), meta).await;
+}
+
+#[when(regex = "^trying to create `(\\S+)` symlink on `(\\S+)` bucket \
+                pointing to `(\\S+)`( as dangling| as hard link|)$")]
+async fn symlink_is_tried(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    target: String,
+    mode: String,
+) {
+    let mut meta = HashMap::from([(
+        s3::SYMLINK_META_KEY.into(),
+        w.unique.filename(target),
+    )]);
+    match mode.as_str() {
+        " as dangling" => {
+            _ = meta.insert(
+                s3::SYMLINK_ALLOW_DANGLING_META_KEY.into(),
+                "true".into(),
+            );
+        }
+        " as hard link" => {
+            _ = meta.insert(s3::LINK_MODE_META_KEY.into(), "hard".into());
+        }
+        _ => {}
+    }
+    w.symlink_error =
+        try_put_symlink(bucket, w.unique.filename(key), meta).await;
+}
+
+#[when(regex = "^trying to create `(\\S+)` directory alias on `(\\S+)` \
+                bucket pointing to `(\\S+)`$")]
+async fn directory_alias_is_tried(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    target: String,
+) {
+    let meta = HashMap::from([(
+        s3::SYMLINK_META_KEY.into(),
+        format!("{}/", w.unique.filename(target)),
+    )]);
+    w.symlink_error =
+        try_put_symlink(bucket, format!("{}/", w.unique.filename(key)), meta)
+            .await;
+}
+
+#[when(regex = "^trying to upload `(\\S+)` as `(\\S+)` through `(\\S+)` \
+                alias to `(\\S+)` bucket$")]
+async fn upload_through_alias_is_tried(
+    w: &mut World,
+    sample: String,
+    key: String,
+    alias: String,
+    bucket: String,
+) {
+    let req = PutObjectRequest {
+        bucket,
+        key: format!("{}/{key}", w.unique.filename(alias)),
+        body: Some(sample_file(sample).to_owned().into()),
+        ..PutObjectRequest::default()
+    };
+    w.upload_error = s3_client().put_object(req).await.err().map(|e| match e {
+        RusotoError::Unknown(resp) => resp.body_as_str().to_owned(),
+        e => e.to_string(),
+    });
+}
+
+#[then(regex = r"^`(\S+)` is a hard link to `(\S+)`$")]
+async fn file_is_hard_linked(
+    w: &mut World,
+    path: String,
+    original: String,
+) -> io::Result<()> {
+    let link = async_fs::symlink_metadata(format!(
+        "{DATA_DIR}/{}",
+        w.unique.filename(path),
+    ))
+    .await?;
+    let original = async_fs::symlink_metadata(format!(
+        "{DATA_DIR}/{}",
+        w.unique.filename(original),
+    ))
+    .await?;
+
+    assert!(!link.is_symlink(), "Hard link is a symlink");
+    assert_eq!(link.ino(), original.ino(), "Hard link points elsewhere");
+    Ok(())
+}
+
+#[then("symlink is accepted")]
+fn symlink_is_accepted(w: &mut World) {
+    assert!(w.symlink_error.is_none(), "Symlink failed: {:?}", w.symlink_error);
+}
+
+#[then(regex = r"^symlink is rejected with `(\S+)` error$")]
+fn symlink_is_rejected(w: &mut World, code: String) {
+    let err = w.symlink_error.as_deref().expect("Symlink succeeded");
+    assert!(
+        err.contains(&format!("<Code>{code}</Code>")),
+        "Expected `{code}`, got: {err}",
+    );
+}
+
+#[when(regex = r"^symlinks pointing to `(\S+)` are listed$")]
+async fn symlinks_are_listed(w: &mut World, target: String) {
+    let resp =
+        dispatch_subresource("GET", &w.unique.filename(target), "links").await;
+    assert_eq!(
+        resp.status.as_u16(),
+        200,
+        "`ListSymlinks` failed: {}",
+        resp.body_as_str(),
+    );
+
+    w.listed_symlinks = Some(resp.body_as_str().to_owned());
+}
+
+#[then(regex = r"^`(\S+)` symlink is( not|) listed$")]
+fn symlink_is_listed(w: &mut World, path: String, not: String) {
+    let body = w.listed_symlinks.as_deref().expect("No symlinks listed");
+    let path = w.unique.filename(path);
+    let (bucket, key) = path.split_once('/').expect("No bucket in path");
+    let entry = format!("<Bucket>{bucket}</Bucket><Key>{key}</Key>");
+    assert_eq!(
+        body.contains(&entry),
+        not.is_empty(),
+        "Unexpected `ListSymlinks` response: {body}",
+    );
+}
+
+#[when(regex = r"^`(\S+)` symlink is rolled back on `(\S+)` bucket$")]
+async fn symlink_is_rolled_back(w: &mut World, key: String, bucket: String) {
+    let path = format!("{bucket}/{}", w.unique.filename(key));
+    let resp = dispatch_subresource("POST", &path, "symlink-rollback").await;
+    w.symlink_error =
+        (resp.status.as_u16() != 200).then(|| resp.body_as_str().to_owned());
+}
+
+#[then(regex = r"^`(\S+)` is the previous target of `(\S+)` symlink$")]
+async fn symlink_previous_target(w: &mut World, target: String, path: String) {
+    let resp = dispatch_subresource(
+        "GET",
+        &w.unique.filename(path),
+        "symlink-history",
+    )
+    .await;
+    let body = resp.body_as_str();
+    assert_eq!(resp.status.as_u16(), 200, "`GetSymlinkHistory` failed: {body}");
+
+    // The latest entry goes first.
+    let latest = format!(
+        "<SymlinkHistory><Entry><Target>{}</Target>",
+        w.unique.filename(target),
+    );
+    assert!(
+        body.contains(&latest),
+        "Unexpected `GetSymlinkHistory` response: {body}",
+    );
+}
+
+/// Dispatches a signed request with the provided `method` to the provided
+/// `subresource` of the object at the provided `path`.
+async fn dispatch_subresource(
+    method: &str,
+    path: &str,
+    subresource: &str,
+) -> BufferedHttpResponse {
+    let region =
+        Region::Custom { name: "test".into(), endpoint: API_URL.into() };
+    let mut req =
+        SignedRequest::new(method, "s3", &region, &format!("/{path}"));
+    req.add_param(subresource, "");
+    req.sign(&AwsCredentials::new("baza", "baza", None, None));
+
+    HttpClient::new()
+        .expect("Failed to initialize Rusoto HTTP client")
+        .dispatch(req, None)
+        .await
+        .expect("Failed to send request")
+        .buffer()
+        .await
+        .expect("Failed to read response")
+}
+
+/// Puts a symlink with the provided `meta`data as the provided `key` into the
+/// provided `bucket`, returning the error response body, if any.
+async fn try_put_symlink(
+    bucket: String,
+    key: String,
+    meta: HashMap<String, String>,
+) -> Option<String> {
+    let req = PutObjectRequest {
+        bucket,
+        key,
+        body: Some(Vec::new().into()),
+        metadata: Some(meta),
+        ..PutObjectRequest::default()
+    };
+
+    s3_client().put_object(req).await.err().map(|e| match e {
+        RusotoError::Unknown(resp) => resp.body_as_str().to_owned(),
+        e => e.to_string(),
+    })
+}
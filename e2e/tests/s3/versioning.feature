@@ -0,0 +1,83 @@
+Feature: Versioning
+
+  Scenario: Versioning: overwrite keeps previous version
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `versioned` bucket as `file`
+    When `ignucius.jpg` is uploaded to `versioned` bucket as `file`
+    Then `file` has 2 versions and 0 delete markers in `versioned` bucket
+
+  Scenario: Versioning: current version is returned by default
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `versioned` bucket as `file`
+    And `ignucius.jpg` was uploaded to `versioned` bucket as `file`
+    When trying to load `file` from `versioned` bucket
+    Then `ignucius.jpg` file is returned
+
+  Scenario: Versioning: previous version is returned by its ID
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `versioned` bucket as `file`
+    And `ignucius.jpg` was uploaded to `versioned` bucket as `file`
+    When trying to load version 1 of `file` from `versioned` bucket
+    Then `rms.jpg` file is returned
+
+  Scenario: Versioning: non-existing version
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `versioned` bucket as `file`
+    When trying to load `file` from `versioned` bucket with `unknown` version ID
+    Then `NoSuchVersion` GetObject error is returned
+
+  Scenario: Versioning: deletion leaves delete marker
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `versioned` bucket as `file`
+    When `file` is deleted from `versioned` bucket
+    Then `file` has 1 version and 1 delete marker in `versioned` bucket
+
+  Scenario: Versioning: deleted file is not returned
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `versioned` bucket as `file`
+    And `file` was deleted from `versioned` bucket
+    When trying to load `file` from `versioned` bucket
+    Then `NoSuchKey` error is returned
+
+  Scenario: Versioning: deleted file is still accessible by version ID
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `versioned` bucket as `file`
+    And `file` was deleted from `versioned` bucket
+    When trying to load version 1 of `file` from `versioned` bucket
+    Then `rms.jpg` file is returned
+
+  Scenario: Versioning: removing delete marker restores file
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `versioned` bucket as `file`
+    And `file` was deleted from `versioned` bucket
+    When delete marker of `file` is deleted from `versioned` bucket
+    And trying to load `file` from `versioned` bucket
+    Then `rms.jpg` file is returned
+
+  Scenario: Versioning: deleting latest version restores previous one
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `versioned` bucket as `file`
+    And `ignucius.jpg` was uploaded to `versioned` bucket as `file`
+    When version 2 of `file` is deleted from `versioned` bucket
+    And trying to load `file` from `versioned` bucket
+    Then `rms.jpg` file is returned
+
+  Scenario: Versioning: symlink repoint keeps previous target
+    Given versioning is enabled on `versioned` bucket
+    And `rms.jpg` was uploaded to `data` bucket as `file1`
+    And `ignucius.jpg` was uploaded to `data` bucket as `file2`
+    And `link` symlink was created on `versioned` bucket pointing to `data/file1`
+    When `link` symlink is created on `versioned` bucket pointing to `data/file2`
+    And trying to load version 1 of `link` from `versioned` bucket
+    Then `rms.jpg` file is returned
+
+  Scenario: Versioning: suspended versioning overwrites `null` version
+    Given versioning is suspended on `suspended` bucket
+    And `rms.jpg` was uploaded to `suspended` bucket as `file`
+    When `ignucius.jpg` is uploaded to `suspended` bucket as `file`
+    Then `file` has 1 version and 0 delete markers in `suspended` bucket
+
+  Scenario: Versioning: unversioned bucket deletes file completely
+    Given `rms.jpg` was uploaded to `data` bucket as `file`
+    When `file` is deleted from `data` bucket
+    Then `file` has 0 versions and 0 delete markers in `data` bucket
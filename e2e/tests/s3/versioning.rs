@@ -0,0 +1,161 @@
+//! [Versioning][0] E2E (end-to-end) tests.
+//!
+//! [0]: https://docs.aws.amazon.com/AmazonS3/latest/userguide/Versioning.html
+
+use cucumber::{given, then, when};
+use rusoto_s3::{
+    DeleteObjectRequest, ListObjectVersionsOutput, ListObjectVersionsRequest,
+    PutBucketVersioningRequest, S3 as _, VersioningConfiguration,
+};
+
+use super::{s3_client, try_get_object};
+use crate::World;
+
+#[given(regex = r"^versioning is (enabled|suspended) on `(\S+)` bucket$")]
+async fn versioning_is_set(_: &mut World, status: String, bucket: String) {
+    let req = PutBucketVersioningRequest {
+        bucket,
+        versioning_configuration: VersioningConfiguration {
+            status: Some(if status == "enabled" {
+                "Enabled".into()
+            } else {
+                "Suspended".into()
+            }),
+            ..VersioningConfiguration::default()
+        },
+        ..PutBucketVersioningRequest::default()
+    };
+
+    s3_client()
+        .put_bucket_versioning(req)
+        .await
+        .unwrap_or_else(|e| panic!("`PutBucketVersioningRequest` failed: {e}"));
+}
+
+#[given(regex = r"^`(\S+)` was deleted from `(\S+)` bucket$")]
+#[when(regex = r"^`(\S+)` is deleted from `(\S+)` bucket$")]
+async fn object_is_deleted(w: &mut World, key: String, bucket: String) {
+    delete_object(bucket, w.unique.filename(key), None).await;
+}
+
+#[when(regex = r"^version (\d+) of `(\S+)` is deleted from `(\S+)` bucket$")]
+async fn version_is_deleted(
+    w: &mut World,
+    n: usize,
+    key: String,
+    bucket: String,
+) {
+    let key = w.unique.filename(key);
+    let id = version_ids(&bucket, &key).await.remove(n - 1);
+    delete_object(bucket, key, Some(id)).await;
+}
+
+#[when(regex = r"^delete marker of `(\S+)` is deleted from `(\S+)` bucket$")]
+async fn delete_marker_is_deleted(w: &mut World, key: String, bucket: String) {
+    let key = w.unique.filename(key);
+    let id = list_versions(&bucket, &key)
+        .await
+        .delete_markers
+        .unwrap_or_default()
+        .into_iter()
+        .find_map(|m| m.version_id)
+        .expect("No delete marker");
+    delete_object(bucket, key, Some(id)).await;
+}
+
+#[when(
+    regex = r"^trying to load version (\d+) of `(\S+)` from `(\S+)` bucket$"
+)]
+async fn trying_to_load_version(
+    w: &mut World,
+    n: usize,
+    key: String,
+    bucket: String,
+) {
+    let key = w.unique.filename(key);
+    let id = version_ids(&bucket, &key).await.remove(n - 1);
+    w.get_object_response = Some(try_get_object(bucket, key, Some(id)).await);
+}
+
+#[when(regex = "^trying to load `(\\S+)` from `(\\S+)` bucket \
+                 with `(\\S+)` version ID$")]
+async fn trying_to_load_version_id(
+    w: &mut World,
+    key: String,
+    bucket: String,
+    id: String,
+) {
+    w.get_object_response =
+        Some(try_get_object(bucket, w.unique.filename(key), Some(id)).await);
+}
+
+#[then(regex = "^`(\\S+)` has (\\d+) versions? and (\\d+) delete markers? \
+                 in `(\\S+)` bucket$")]
+async fn versions_are_listed(
+    w: &mut World,
+    key: String,
+    versions: usize,
+    markers: usize,
+    bucket: String,
+) {
+    let out = list_versions(&bucket, &w.unique.filename(key)).await;
+
+    assert_eq!(
+        out.versions.as_ref().map_or(0, Vec::len),
+        versions,
+        "Wrong number of versions: {out:#?}",
+    );
+    assert_eq!(
+        out.delete_markers.as_ref().map_or(0, Vec::len),
+        markers,
+        "Wrong number of delete markers: {out:#?}",
+    );
+}
+
+/// Deletes the provided `key` (or its version) from the provided `bucket`.
+async fn delete_object(
+    bucket: String,
+    key: String,
+    version_id: Option<String>,
+) {
+    let req = DeleteObjectRequest {
+        bucket,
+        key,
+        version_id,
+        ..DeleteObjectRequest::default()
+    };
+
+    _ = s3_client()
+        .delete_object(req)
+        .await
+        .unwrap_or_else(|e| panic!("`DeleteObjectRequest` failed: {e}"));
+}
+
+/// Lists all the versions of the provided `key` in the provided `bucket`.
+async fn list_versions(bucket: &str, key: &str) -> ListObjectVersionsOutput {
+    let req = ListObjectVersionsRequest {
+        bucket: bucket.into(),
+        prefix: Some(key.into()),
+        ..ListObjectVersionsRequest::default()
+    };
+
+    s3_client()
+        .list_object_versions(req)
+        .await
+        .unwrap_or_else(|e| panic!("`ListObjectVersionsRequest` failed: {e}"))
+}
+
+/// Returns IDs of all the versions (excluding delete markers) of the provided
+/// `key` in the provided `bucket`, from the oldest to the latest one.
+async fn version_ids(bucket: &str, key: &str) -> Vec<String> {
+    let mut ids = list_versions(bucket, key)
+        .await
+        .versions
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|v| v.key.as_deref() == Some(key))
+        .filter_map(|v| v.version_id)
+        .collect::<Vec<_>>();
+    ids.reverse();
+    ids
+}
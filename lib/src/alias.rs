@@ -0,0 +1,270 @@
+//! Directory aliases, being symlinks to whole directories.
+
+use std::{
+    io,
+    path::{Component, Path},
+};
+
+use tracerr::Traced;
+
+use crate::{AccessDeniedError, RelativePath, Storage, list_files};
+
+impl Storage {
+    /// Allows or disallows writing files through directory aliases, which
+    /// places them into the aliased directories.
+    ///
+    /// Disallowed by default.
+    #[must_use]
+    pub const fn with_alias_writes(mut self, allow: bool) -> Self {
+        self.alias_writes = allow;
+        self
+    }
+
+    /// Ensures the file at the provided `path` is not written through a
+    /// directory alias, unless the [`Storage`] allows it.
+    ///
+    /// Files are never written through directories of published symlinks,
+    /// as these are only replaced as a whole.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::PermissionDenied`], if any of the parent
+    ///   directories of the `path` is an alias, or the `path` resolves into
+    ///   a directory of published symlinks.
+    /// - If any of the parent directories fails to be read.
+    pub(crate) async fn ensure_unaliased(
+        &self,
+        path: &RelativePath,
+    ) -> Result<(), Traced<io::Error>> {
+        let live = self.live_path(path).await.map_err(tracerr::wrap!())?;
+        if live.starts_with(self.releases_root()) {
+            return Err(tracerr::new!(AccessDeniedError::io(format!(
+                "`{path}` cannot be written through published symlinks",
+            ))));
+        }
+        if self.alias_writes {
+            return Ok(());
+        }
+
+        let parents = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        let mut dir = self.data_dir.clone();
+        for c in parents.components() {
+            let Component::Normal(name) = c else {
+                continue;
+            };
+            dir.push(name);
+            match async_fs::symlink_metadata(&dir).await {
+                Ok(meta) if meta.is_symlink() => {
+                    return Err(tracerr::new!(AccessDeniedError::io(format!(
+                        "`{path}` cannot be written through a directory alias",
+                    ))));
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+                Err(e) => return Err(tracerr::new!(e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves the directory aliases of the provided `path`, returning the
+    /// [`RelativePath`] of the file it actually refers to.
+    ///
+    /// All the metadata of a file (its versions, locks, checksum, etc.) is
+    /// stored under the resolved [`RelativePath`], so writing it through an
+    /// alias doesn't bypass any of them.
+    ///
+    /// # Errors
+    ///
+    /// If the live path of the file fails to be resolved.
+    pub(crate) async fn resolve_alias(
+        &self,
+        path: &RelativePath,
+    ) -> Result<RelativePath, Traced<io::Error>> {
+        let live = self.live_path(path).await.map_err(tracerr::wrap!())?;
+        Ok(self.relative_path(&live).unwrap_or_else(|| path.clone()))
+    }
+
+    /// Lists all the files of the directory aliased by the symlink at the
+    /// provided `path`, relative to it.
+    ///
+    /// Returns [`None`] if the `path` is not a directory alias. Symlinks
+    /// failing to be resolved (like dangling or escaping ones) are not
+    /// considered aliases.
+    ///
+    /// # Errors
+    ///
+    /// If the aliased directory fails to be read.
+    pub(crate) async fn list_aliased(
+        &self,
+        path: &Path,
+    ) -> io::Result<Option<Vec<String>>> {
+        match async_fs::symlink_metadata(path).await {
+            Ok(meta) if meta.is_symlink() => {}
+            Ok(_) => return Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let Ok(dir) = self.resolve_beneath(path, true).await else {
+            return Ok(None);
+        };
+        match async_fs::metadata(&dir).await {
+            Ok(meta) if meta.is_dir() => Ok(Some(list_files(&dir).await?)),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod alias_spec {
+    use std::{io, time::SystemTime};
+
+    use futures::executor::block_on;
+
+    use crate::{
+        CreateSymlink, DeleteFile, Exec as _, GetBucketUsage, Lifecycle,
+        LifecycleRule, ListVersions, ObjectLock, SetBucketConfig,
+        SetObjectLegalHold, Storage, Usage,
+        testing::{TempDir, create_file, path},
+    };
+
+    fn create_alias(src: &str, dest: &str) -> CreateSymlink {
+        CreateSymlink {
+            src: path(src),
+            dest: path(dest),
+            expected_src: None,
+            allow_dangling: false,
+            directory: true,
+        }
+    }
+
+    #[test]
+    fn does_not_expire_aliased_files() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            storage.exec(create_file("data/dir/file", b"x")).await.unwrap();
+            storage.exec(create_file("links/own", b"x")).await.unwrap();
+            storage
+                .exec(create_alias("data/dir", "links/current"))
+                .await
+                .unwrap();
+
+            let versions = storage
+                .exec(ListVersions { bucket: path("links"), prefix: None })
+                .await
+                .unwrap();
+            let lifecycle = Lifecycle {
+                rules: vec![LifecycleRule {
+                    enabled: true,
+                    expiration_days: Some(0),
+                    ..LifecycleRule::default()
+                }],
+            };
+            let expired: Vec<_> = lifecycle
+                .expired(&path("links"), &versions, SystemTime::now())
+                .into_iter()
+                .map(|op| op.path.to_string())
+                .collect();
+
+            assert!(
+                versions.iter().any(|v| v.key == "current/file" && v.aliased),
+                "Aliased file is not listed",
+            );
+            assert_eq!(expired, ["links/own"]);
+        });
+    }
+
+    #[test]
+    fn charges_aliased_writes_to_aliased_bucket() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage =
+                Storage::new(&*root).await.unwrap().with_alias_writes(true);
+            storage.exec(create_file("data/dir/file", b"x")).await.unwrap();
+            storage
+                .exec(create_alias("data/dir", "links/current"))
+                .await
+                .unwrap();
+            let usage = async |bucket| {
+                storage
+                    .exec(GetBucketUsage { bucket: path(bucket) })
+                    .await
+                    .unwrap()
+            };
+
+            storage
+                .exec(create_file("links/current/new", b"abc"))
+                .await
+                .unwrap();
+            assert_eq!(usage("data").await, Usage { bytes: 4, objects: 2 });
+            assert_eq!(usage("links").await, Usage { bytes: 0, objects: 1 });
+
+            storage
+                .exec(DeleteFile {
+                    path: path("links/current/new"),
+                    version_id: None,
+                    bypass_governance: false,
+                })
+                .await
+                .unwrap();
+            assert_eq!(usage("data").await, Usage { bytes: 1, objects: 1 });
+            assert_eq!(usage("links").await, Usage { bytes: 0, objects: 1 });
+        });
+    }
+
+    #[test]
+    fn does_not_bypass_legal_holds_through_aliases() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage =
+                Storage::new(&*root).await.unwrap().with_alias_writes(true);
+            storage
+                .exec(SetBucketConfig {
+                    bucket: path("data"),
+                    config: Some(ObjectLock::default()),
+                })
+                .await
+                .unwrap();
+            storage.exec(create_file("data/dir/file", b"x")).await.unwrap();
+            storage
+                .exec(SetObjectLegalHold {
+                    path: path("data/dir/file"),
+                    version_id: None,
+                    enabled: true,
+                })
+                .await
+                .unwrap();
+            storage
+                .exec(create_alias("data/dir", "links/current"))
+                .await
+                .unwrap();
+
+            let written = storage
+                .exec(create_file("links/current/file", b"y"))
+                .await
+                .unwrap_err();
+            let deleted = storage
+                .exec(DeleteFile {
+                    path: path("links/current/file"),
+                    version_id: None,
+                    bypass_governance: false,
+                })
+                .await
+                .unwrap_err();
+
+            assert_eq!(
+                written.as_ref().kind(),
+                io::ErrorKind::PermissionDenied,
+            );
+            assert_eq!(
+                deleted.as_ref().kind(),
+                io::ErrorKind::PermissionDenied,
+            );
+            let file = storage.data_dir.join("data/dir/file");
+            assert_eq!(async_fs::read(file).await.unwrap(), b"x");
+        });
+    }
+}
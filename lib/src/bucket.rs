@@ -0,0 +1,165 @@
+//! Configurations of buckets.
+
+use std::{fmt, io, marker::PhantomData, path::PathBuf};
+
+use serde::{Serialize, de::DeserializeOwned};
+use tracerr::Traced;
+
+use crate::{
+    Exec, RelativePath, Storage, async_trait, list_files,
+    versioning::list_dirs, write_atomically,
+};
+
+/// Configuration of a bucket, persisted by [`Storage`].
+pub trait BucketConfig:
+    Serialize + DeserializeOwned + fmt::Debug + Send + Sync + 'static
+{
+    /// Unique name of this [`BucketConfig`] to persist it under.
+    const NAME: &'static str;
+}
+
+/// Operation for getting a [`BucketConfig`] of a bucket.
+#[derive(Clone, Debug)]
+pub struct GetBucketConfig<T> {
+    /// [`RelativePath`] of the bucket.
+    pub bucket: RelativePath,
+
+    /// Type of the [`BucketConfig`] to get.
+    _config: PhantomData<fn() -> T>,
+}
+
+impl<T> GetBucketConfig<T> {
+    /// Creates a new [`GetBucketConfig`] operation for the provided `bucket`.
+    #[must_use]
+    pub const fn new(bucket: RelativePath) -> Self {
+        Self { bucket, _config: PhantomData }
+    }
+}
+
+#[async_trait]
+impl<T: BucketConfig> Exec<GetBucketConfig<T>> for Storage {
+    type Ok = Option<T>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(
+        &self,
+        op: GetBucketConfig<T>,
+    ) -> Result<Self::Ok, Self::Err> {
+        let path = self.bucket_config_path::<T>(&op.bucket);
+
+        match async_fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|e| tracerr::new!(io::Error::from(e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(tracerr::new!(e)),
+        }
+    }
+}
+
+/// Operation of a [`BucketConfig`] update.
+#[derive(Clone, Debug)]
+pub struct SetBucketConfig<T> {
+    /// [`RelativePath`] of the bucket.
+    pub bucket: RelativePath,
+
+    /// New [`BucketConfig`] of the bucket.
+    ///
+    /// [`None`] removes the existing one.
+    pub config: Option<T>,
+}
+
+#[async_trait]
+impl<T: BucketConfig> Exec<SetBucketConfig<T>> for Storage {
+    type Ok = ();
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(
+        &self,
+        op: SetBucketConfig<T>,
+    ) -> Result<Self::Ok, Self::Err> {
+        let path = self.bucket_config_path::<T>(&op.bucket);
+
+        let Some(config) = op.config else {
+            return async_fs::remove_file(&path).await.or_else(|e| {
+                (e.kind() == io::ErrorKind::NotFound)
+                    .then_some(())
+                    .ok_or_else(|| tracerr::new!(e))
+            });
+        };
+
+        let bytes = serde_json::to_vec_pretty(&config)
+            .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+        write_atomically(&self.tmp_dir, &path, &bytes)
+            .await
+            .map_err(tracerr::wrap!())
+    }
+}
+
+/// Operation of listing all the existing buckets.
+#[derive(Clone, Copy, Debug)]
+pub struct ListBuckets;
+
+#[async_trait]
+impl Exec<ListBuckets> for Storage {
+    type Ok = Vec<RelativePath>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, _: ListBuckets) -> Result<Self::Ok, Self::Err> {
+        Ok(list_dirs(&self.data_dir)
+            .await
+            .map_err(tracerr::wrap!())?
+            .into_iter()
+            .filter_map(|dir| {
+                let name = dir.file_name()?.to_str()?.to_owned();
+                RelativePath::try_from(name).ok()
+            })
+            .collect())
+    }
+}
+
+/// Operation of listing the keys of all the files (including symlinks) in a
+/// bucket, in their order.
+#[derive(Clone, Debug)]
+pub struct ListFiles {
+    /// [`RelativePath`] of the bucket.
+    pub bucket: RelativePath,
+}
+
+#[async_trait]
+impl Exec<ListFiles> for Storage {
+    type Ok = Vec<String>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: ListFiles) -> Result<Self::Ok, Self::Err> {
+        let dir = self
+            .resolve_beneath(&self.data_dir.join(&op.bucket), true)
+            .await
+            .map_err(tracerr::wrap!())?;
+        let mut keys = list_files(&dir).await.map_err(tracerr::wrap!())?;
+        keys.sort_unstable();
+        Ok(keys)
+    }
+}
+
+impl Storage {
+    /// Returns the [`PathBuf`] where the [`BucketConfig`] of the provided
+    /// `bucket` is persisted.
+    fn bucket_config_path<T: BucketConfig>(
+        &self,
+        bucket: &RelativePath,
+    ) -> PathBuf {
+        self.meta_dir
+            .join("buckets")
+            .join(bucket)
+            .join(format!("{}.json", T::NAME))
+    }
+}
@@ -0,0 +1,446 @@
+//! Compression of files at rest.
+//!
+//! # Format
+//!
+//! A compressed file starts with [`MAGIC`] bytes, followed by a single
+//! [Zstandard][0] frame of the file contents, and ends with the size of the
+//! decompressed contents (big-endian [`u64`]), so it can be reported without
+//! decompressing the whole file.
+//!
+//! [0]: https://facebook.github.io/zstd
+
+use std::{
+    io::{self, Write as _},
+    mem,
+    ops::RangeInclusive,
+    pin::Pin,
+    task::{self, Poll},
+};
+
+use async_fs::File;
+use futures::{AsyncRead, AsyncReadExt as _, AsyncSeek, AsyncSeekExt as _};
+use serde::{Deserialize, Serialize};
+use zstd::stream::{
+    raw::{self, InBuffer, Operation as _, OutBuffer},
+    write,
+};
+
+use crate::BucketConfig;
+
+/// Magic bytes a compressed file starts with.
+pub(crate) const MAGIC: &[u8; 8] = b"BAZAZST\x01";
+
+/// Size of the trailer of a compressed file, containing the size of its
+/// decompressed contents.
+const TRAILER_LEN: u64 = 8;
+
+/// Size of a buffer compressed bytes are read into.
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Content types of already compressed data, not worth compressing again.
+const COMPRESSED_TYPES: &[&str] = &[
+    "application/gzip",
+    "application/vnd.rar",
+    "application/x-7z-compressed",
+    "application/x-bzip2",
+    "application/x-gzip",
+    "application/x-rar-compressed",
+    "application/x-xz",
+    "application/zip",
+    "application/zstd",
+];
+
+/// Compression of files stored in a bucket.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Compression {
+    /// [Zstandard][0] compression level.
+    ///
+    /// [0]: https://facebook.github.io/zstd
+    pub level: i32,
+}
+
+impl BucketConfig for Compression {
+    const NAME: &'static str = "compression";
+}
+
+impl Compression {
+    /// Range of the supported [`Compression::level`]s.
+    pub const LEVELS: RangeInclusive<i32> = 1..=22;
+
+    /// Indicates whether contents of the provided `content_type` are worth
+    /// being compressed.
+    ///
+    /// Images, audio and video (except SVG) and archives are considered
+    /// compressed already.
+    #[must_use]
+    pub fn is_compressible(content_type: &str) -> bool {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if mime == "image/svg+xml" {
+            return true;
+        }
+        let media = mime.split('/').next().unwrap_or_default();
+        !matches!(media, "image" | "audio" | "video")
+            && !COMPRESSED_TYPES.contains(&mime.as_str())
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self { level: zstd::DEFAULT_COMPRESSION_LEVEL }
+    }
+}
+
+/// Compressor of file contents.
+pub(crate) struct Compressor {
+    /// Encoder writing the compressed bytes into a buffer.
+    encoder: write::Encoder<'static, Vec<u8>>,
+
+    /// Number of the bytes compressed so far.
+    size: u64,
+}
+
+impl Compressor {
+    /// Creates a new [`Compressor`] with the provided [`Compression`].
+    ///
+    /// # Errors
+    ///
+    /// If the [`Compression::level`] is not supported.
+    pub(crate) fn new(compression: Compression) -> io::Result<Self> {
+        Ok(Self {
+            encoder: write::Encoder::new(MAGIC.to_vec(), compression.level)?,
+            size: 0,
+        })
+    }
+
+    /// Compresses the provided `data`, returning the compressed bytes ready
+    /// to be written.
+    ///
+    /// # Errors
+    ///
+    /// If compression fails.
+    pub(crate) fn update(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        self.encoder.write_all(data)?;
+        self.size += data.len() as u64;
+        Ok(mem::take(self.encoder.get_mut()))
+    }
+
+    /// Compresses the rest of the data, returning the last compressed bytes
+    /// to be written.
+    ///
+    /// # Errors
+    ///
+    /// If compression fails.
+    pub(crate) fn finish(self) -> io::Result<Vec<u8>> {
+        let mut out = self.encoder.finish()?;
+        out.extend_from_slice(&self.size.to_be_bytes());
+        Ok(out)
+    }
+}
+
+/// Reads the [`MAGIC`] bytes of the provided `file`, returning a
+/// [`Decompressor`] of its contents, if it's compressed.
+///
+/// Rewinds the `file` to its start, if it's not compressed.
+///
+/// # Errors
+///
+/// If the `file` fails to be read.
+pub(crate) async fn decompressor(
+    file: &mut File,
+) -> io::Result<Option<Decompressor>> {
+    let Some(size) = decompressed_size(file).await? else {
+        _ = file.seek(io::SeekFrom::Start(0)).await?;
+        return Ok(None);
+    };
+    let start = file.seek(io::SeekFrom::Start(MAGIC.len() as u64)).await?;
+    let end = file.metadata().await?.len() - TRAILER_LEN;
+
+    Ok(Some(Decompressor {
+        decoder: raw::Decoder::new()?,
+        size,
+        raw_pos: start,
+        end,
+        input: Vec::new(),
+        consumed: 0,
+        pos: 0,
+        target: 0,
+        scratch: Vec::new(),
+        rewinding: false,
+    }))
+}
+
+/// Reads the size of the decompressed contents of the provided `file`, if
+/// it's compressed.
+///
+/// # Errors
+///
+/// If the `file` fails to be read.
+pub(crate) async fn decompressed_size(
+    file: &mut File,
+) -> io::Result<Option<u64>> {
+    _ = file.seek(io::SeekFrom::Start(0)).await?;
+    let mut magic = Vec::with_capacity(MAGIC.len());
+    _ = file.take(MAGIC.len() as u64).read_to_end(&mut magic).await?;
+    if magic != MAGIC {
+        return Ok(None);
+    }
+    if file.metadata().await?.len() < MAGIC.len() as u64 + TRAILER_LEN {
+        return Err(invalid_data("Compressed file is truncated"));
+    }
+
+    let mut trailer = [0; TRAILER_LEN as usize];
+    _ = file.seek(io::SeekFrom::End(-(TRAILER_LEN as i64))).await?;
+    file.read_exact(&mut trailer).await?;
+    Ok(Some(u64::from_be_bytes(trailer)))
+}
+
+/// Creates a new [`io::ErrorKind::InvalidData`] error of a corrupted
+/// compressed file.
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Decompressor of file contents.
+///
+/// Seeking forward skips the decompressed bytes, while seeking backward
+/// restarts decompression from the start of the file.
+pub(crate) struct Decompressor {
+    /// Streaming decoder of the compressed bytes.
+    decoder: raw::Decoder<'static>,
+
+    /// Size of the decompressed contents.
+    size: u64,
+
+    /// Position of the compressed bytes read from the file.
+    raw_pos: u64,
+
+    /// Position of the end of the compressed bytes in the file.
+    end: u64,
+
+    /// Compressed bytes read from the file.
+    input: Vec<u8>,
+
+    /// Number of the `input` bytes consumed by the `decoder` already.
+    consumed: usize,
+
+    /// Position of the decompressed bytes produced by the `decoder`.
+    pos: u64,
+
+    /// Position in the decompressed contents requested to be read.
+    target: u64,
+
+    /// Buffer of the decompressed bytes skipped while seeking.
+    scratch: Vec<u8>,
+
+    /// Indicator whether decompression must be restarted from the start of
+    /// the file.
+    rewinding: bool,
+}
+
+impl Decompressor {
+    /// Returns the size of the decompressed contents.
+    pub(crate) const fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Reads the decompressed contents from the provided `file` into the
+    /// provided `buf`fer.
+    pub(crate) fn poll_read(
+        &mut self,
+        mut file: Pin<&mut File>,
+        cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            if self.rewinding {
+                let start = MAGIC.len() as u64;
+                match file.as_mut().poll_seek(cx, io::SeekFrom::Start(start)) {
+                    Poll::Ready(res) => _ = res?,
+                    Poll::Pending => return Poll::Pending,
+                }
+                self.decoder.reinit()?;
+                self.raw_pos = start;
+                self.input.clear();
+                self.consumed = 0;
+                self.pos = 0;
+                self.rewinding = false;
+            }
+            if buf.is_empty() || self.target >= self.size {
+                return Poll::Ready(Ok(0));
+            }
+
+            if self.consumed == self.input.len() && self.raw_pos < self.end {
+                let len = (self.end - self.raw_pos).min(BUF_SIZE as u64);
+                self.input.resize(len as usize, 0);
+                self.consumed = 0;
+                let n = match file.as_mut().poll_read(cx, &mut self.input) {
+                    Poll::Ready(Ok(0)) => {
+                        return Poll::Ready(Err(invalid_data(
+                            "Compressed file is truncated",
+                        )));
+                    }
+                    Poll::Ready(Ok(n)) => n,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => {
+                        self.input.clear();
+                        return Poll::Pending;
+                    }
+                };
+                self.input.truncate(n);
+                self.raw_pos += n as u64;
+            }
+
+            let skip = self.target - self.pos;
+            let out = if skip > 0 {
+                let len = skip.min(BUF_SIZE as u64) as usize;
+                self.scratch.resize(len, 0);
+                &mut self.scratch[..]
+            } else {
+                &mut *buf
+            };
+            let mut input = InBuffer::around(&self.input);
+            input.pos = self.consumed;
+            let mut output = OutBuffer::around(out);
+            _ = self.decoder.run(&mut input, &mut output)?;
+            let (consumed, n) = (input.pos, output.pos());
+
+            self.consumed = consumed;
+            self.pos += n as u64;
+            if skip == 0 && n > 0 {
+                self.target = self.pos;
+                return Poll::Ready(Ok(n));
+            }
+            if n == 0
+                && self.consumed == self.input.len()
+                && self.raw_pos >= self.end
+            {
+                return Poll::Ready(Err(invalid_data(
+                    "Compressed file is truncated",
+                )));
+            }
+        }
+    }
+
+    /// Seeks to the provided position in the decompressed contents.
+    ///
+    /// # Errors
+    ///
+    /// With [`io::ErrorKind::InvalidInput`], if seeking before the start.
+    pub(crate) fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            io::SeekFrom::Start(n) => Some(n),
+            io::SeekFrom::End(n) => self.size.checked_add_signed(n),
+            io::SeekFrom::Current(n) => self.target.checked_add_signed(n),
+        };
+        self.target = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+        if self.target < self.pos {
+            self.rewinding = true;
+        }
+        Ok(self.target)
+    }
+}
+
+#[cfg(test)]
+mod compression_spec {
+    use std::{io, time::SystemTime};
+
+    use async_fs::File;
+    use futures::{AsyncReadExt as _, AsyncSeekExt as _, executor::block_on};
+
+    use super::{Compression, Compressor, decompressor};
+    use crate::{ReadOnlyFile, testing::TempDir};
+
+    /// Stores the provided `data` compressed and opens it.
+    async fn roundtrip(data: &[u8]) -> io::Result<ReadOnlyFile> {
+        let mut compressor = Compressor::new(Compression::default())?;
+        let mut bytes = Vec::new();
+        for part in data.chunks(1000) {
+            bytes.extend(compressor.update(part)?);
+        }
+        bytes.extend(compressor.finish()?);
+        assert!(bytes.len() < data.len() / 2, "data is not compressed");
+
+        let dir = TempDir::new();
+        let path = dir.join("file");
+        async_fs::write(&path, bytes).await?;
+        let mut file = File::open(&path).await?;
+        Ok(ReadOnlyFile {
+            decompressor: decompressor(&mut file).await?,
+            file,
+            version_id: None,
+            last_modified: SystemTime::UNIX_EPOCH,
+            symlink_target: None,
+            decryptor: None,
+        })
+    }
+
+    #[test]
+    fn reads_ranges() {
+        let data = (0..300_000).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        block_on(async {
+            let mut file = roundtrip(&data).await.unwrap();
+            assert_eq!(file.size().await.unwrap(), data.len() as u64);
+
+            let mut all = Vec::new();
+            _ = file.read_to_end(&mut all).await.unwrap();
+            assert!(all == data, "decompressed contents differ");
+
+            _ = file.seek(io::SeekFrom::Start(200_003)).await.unwrap();
+            let mut range = vec![0; 10];
+            file.read_exact(&mut range).await.unwrap();
+            assert_eq!(range, data[200_003..][..10]);
+
+            _ = file.seek(io::SeekFrom::Start(5)).await.unwrap();
+            file.read_exact(&mut range).await.unwrap();
+            assert_eq!(range, data[5..][..10]);
+
+            _ = file.seek(io::SeekFrom::End(-3)).await.unwrap();
+            let mut tail = Vec::new();
+            _ = file.read_to_end(&mut tail).await.unwrap();
+            assert_eq!(tail, data[data.len() - 3..]);
+        });
+    }
+
+    #[test]
+    fn reads_empty_file() {
+        block_on(async {
+            let compressor = Compressor::new(Compression::default()).unwrap();
+            let bytes = compressor.finish().unwrap();
+            let dir = TempDir::new();
+            let path = dir.join("file");
+            async_fs::write(&path, bytes).await.unwrap();
+
+            let mut file = File::open(&path).await.unwrap();
+            let decompressor = decompressor(&mut file).await.unwrap();
+            assert_eq!(decompressor.map(|d| d.size()), Some(0));
+        });
+    }
+
+    #[test]
+    fn skips_compressed_content_types() {
+        for ct in
+            ["text/plain", "application/json", "image/svg+xml", "Text/CSV"]
+        {
+            assert!(Compression::is_compressible(ct), "{ct}");
+        }
+        for ct in [
+            "image/jpeg",
+            "video/mp4",
+            "audio/ogg",
+            "application/zip",
+            "application/gzip; charset=binary",
+        ] {
+            assert!(!Compression::is_compressible(ct), "{ct}");
+        }
+    }
+}
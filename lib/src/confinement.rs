@@ -0,0 +1,293 @@
+//! Confinement of filesystem access beneath the data directory.
+//!
+//! Paths are resolved by walking their symlinks in userspace, so the
+//! confinement holds only at the moment of resolving: a symlink swapped in
+//! between the walk and a later use of the resolved path is followed by the
+//! kernel unchecked, and may escape the data directory. Files are read via
+//! [`Storage::open_beneath()`], which closes this gap on Linux with
+//! [`openat2(RESOLVE_BENEATH)`][0] (where available), while other operations
+//! rely on the data directory not being writable by anyone else than the
+//! [`Storage`] itself.
+//!
+//! [0]: https://man7.org/linux/man-pages/man2/openat2.2.html
+
+use std::{
+    ffi::OsString,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use async_fs::File;
+
+use crate::{AccessDeniedError, RelativePath, Storage};
+
+/// Maximum number of symlinks followed while resolving a single path, the
+/// same as Linux does.
+const MAX_HOPS: usize = 40;
+
+impl Storage {
+    /// Returns the [`PathBuf`] of the file at the provided [`RelativePath`],
+    /// with all its parent directories resolved beneath the data directory.
+    ///
+    /// The file itself is not followed, even if it's a symlink, so the
+    /// returned path may be used to create, replace or remove it.
+    ///
+    /// # Errors
+    ///
+    /// See [`Storage::resolve_beneath()`].
+    pub(crate) async fn live_path(
+        &self,
+        path: &RelativePath,
+    ) -> io::Result<PathBuf> {
+        self.resolve_beneath(&self.data_dir.join(path), false).await
+    }
+
+    /// Resolves all the symlinks of the provided absolute `path` component by
+    /// component, ensuring none of them escapes the data directory (or a
+    /// directory of published symlinks), in the same manner as
+    /// [`openat2(RESOLVE_BENEATH)`][0] does.
+    ///
+    /// The last component is followed only if `follow_last` is `true`.
+    /// Non-existent components are left as is.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::PermissionDenied`], if the `path` or any of
+    ///   its symlinks escapes the data directory.
+    /// - With [`io::ErrorKind::TooManyLinks`], if resolving the `path`
+    ///   requires following more than [`MAX_HOPS`] symlinks.
+    /// - If any of the symlinks fails to be read.
+    ///
+    /// [0]: https://man7.org/linux/man-pages/man2/openat2.2.html
+    pub(crate) async fn resolve_beneath(
+        &self,
+        path: &Path,
+        follow_last: bool,
+    ) -> io::Result<PathBuf> {
+        let path = normalize(path);
+        let (mut resolved, rest) =
+            self.confinement_of(&path).ok_or_else(|| escape_error(&path))?;
+        let mut pending = components(rest);
+
+        let mut hops = 0;
+        while let Some(name) = pending.pop() {
+            let next = resolved.join(&name);
+            if pending.is_empty() && !follow_last {
+                resolved = next;
+                break;
+            }
+
+            let target = match async_fs::read_link(&next).await {
+                Ok(target) => target,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::InvalidInput
+                            | io::ErrorKind::NotFound
+                            | io::ErrorKind::NotADirectory,
+                    ) =>
+                {
+                    resolved = next;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            hops += 1;
+            if hops > MAX_HOPS {
+                return Err(io::Error::new(
+                    io::ErrorKind::TooManyLinks,
+                    "Too many levels of symbolic links",
+                ));
+            }
+
+            // Parent directories are resolved already, so the target may be
+            // normalized lexically.
+            let target = normalize(&resolved.join(target));
+            let (base, rest) = self
+                .confinement_of(&target)
+                .ok_or_else(|| escape_error(&target))?;
+            resolved = base;
+            pending.extend(components(rest));
+        }
+        Ok(resolved)
+    }
+
+    /// Opens the file at the provided absolute `path` (already resolved via
+    /// [`Storage::resolve_beneath()`]) for reading.
+    ///
+    /// On Linux, the file is opened via [`openat2(RESOLVE_BENEATH)`][0], so a
+    /// symlink swapped in after the `path` has been resolved cannot escape the
+    /// directory it's confined in. On kernels lacking [`openat2`][0] and on
+    /// other platforms, the file is opened as is, the same as the files
+    /// outside the data directory (like stored versions) are.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::PermissionDenied`], if a symlink swapped in
+    ///   escapes the data directory.
+    /// - If the file fails to be opened.
+    ///
+    /// [0]: https://man7.org/linux/man-pages/man2/openat2.2.html
+    pub(crate) async fn open_beneath(&self, path: &Path) -> io::Result<File> {
+        #[cfg(target_os = "linux")]
+        {
+            use rustix::{
+                fs::{Mode, OFlags, ResolveFlags},
+                io::Errno,
+            };
+
+            let normalized = normalize(path);
+            if let Some((base, rest)) = self.confinement_of(&normalized) {
+                let dir = rustix::fs::open(
+                    &base,
+                    OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC,
+                    Mode::empty(),
+                )?;
+                match rustix::fs::openat2(
+                    &dir,
+                    rest,
+                    OFlags::RDONLY | OFlags::CLOEXEC,
+                    Mode::empty(),
+                    ResolveFlags::BENEATH | ResolveFlags::NO_MAGICLINKS,
+                ) {
+                    Ok(fd) => return Ok(std::fs::File::from(fd).into()),
+                    Err(Errno::XDEV) => return Err(escape_error(&normalized)),
+                    Err(Errno::NOSYS) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        File::open(path).await
+    }
+
+    /// Splits the provided normalized absolute `path` into the directory it's
+    /// confined in and the rest of it, if it's confined at all.
+    fn confinement_of<'p>(
+        &self,
+        path: &'p Path,
+    ) -> Option<(PathBuf, &'p Path)> {
+        let releases = self.releases_root();
+        [&self.data_dir, &releases].into_iter().find_map(|base| {
+            Some((base.clone(), path.strip_prefix(base).ok()?))
+        })
+    }
+}
+
+/// Normalizes the provided `path` lexically, resolving all its `.` and `..`
+/// components.
+///
+/// Doesn't follow any symlinks.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::ParentDir => _ = normalized.pop(),
+            Component::CurDir => {}
+            c => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+/// Returns the names of all the components of the provided normalized
+/// relative `path`, in the reversed order (so they can be popped).
+fn components(path: &Path) -> Vec<OsString> {
+    path.components().rev().map(|c| c.as_os_str().to_owned()).collect()
+}
+
+/// Constructs an [`io::Error`] of the provided `path` escaping the data
+/// directory.
+fn escape_error(path: &Path) -> io::Error {
+    tracing::warn!("Path `{}` escapes the data directory", path.display());
+    AccessDeniedError::io("Path escapes the data directory")
+}
+
+#[cfg(test)]
+mod normalize_spec {
+    use std::path::Path;
+
+    use super::normalize;
+
+    #[test]
+    fn resolves_dots() {
+        assert_eq!(normalize(Path::new("/a/./b/../c")), Path::new("/a/c"));
+        assert_eq!(normalize(Path::new("/a/b/../../..")), Path::new("/"));
+    }
+}
+
+#[cfg(test)]
+mod resolve_beneath_spec {
+    use std::io;
+
+    use futures::executor::block_on;
+
+    use crate::{
+        Storage,
+        testing::{TempDir, path},
+    };
+
+    #[test]
+    fn follows_symlinks_inside() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            let data = storage.data_dir.clone();
+            async_fs::create_dir_all(data.join("a/dir")).await.unwrap();
+            async_fs::unix::symlink("../a/dir", data.join("a/link"))
+                .await
+                .unwrap();
+
+            let live = storage.live_path(&path("a/link/file")).await;
+            assert_eq!(live.unwrap(), data.join("a/dir/file"));
+        });
+    }
+
+    #[test]
+    fn rejects_symlinks_escaping() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            let data = storage.data_dir.clone();
+            async_fs::create_dir_all(data.join("a")).await.unwrap();
+            async_fs::unix::symlink("/etc", data.join("a/abs")).await.unwrap();
+            async_fs::unix::symlink("../../meta", data.join("a/rel"))
+                .await
+                .unwrap();
+
+            let abs = storage.live_path(&path("a/abs/passwd")).await;
+            let rel = storage.live_path(&path("a/rel/file")).await;
+            let last = storage.resolve_beneath(&data.join("a/abs"), true).await;
+            for res in [abs, rel, last] {
+                assert_eq!(
+                    res.unwrap_err().kind(),
+                    io::ErrorKind::PermissionDenied,
+                );
+            }
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn rejects_opening_through_swapped_symlinks() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            let data = storage.data_dir.clone();
+            async_fs::create_dir_all(data.join("a/dir")).await.unwrap();
+            async_fs::write(data.join("a/dir/file"), b"x").await.unwrap();
+            let live = storage.live_path(&path("a/dir/file")).await.unwrap();
+
+            let opened = storage.open_beneath(&live).await;
+            async_fs::remove_dir_all(data.join("a/dir")).await.unwrap();
+            async_fs::unix::symlink("/etc", data.join("a/dir")).await.unwrap();
+            let swapped =
+                storage.open_beneath(&data.join("a/dir/passwd")).await;
+
+            assert!(opened.is_ok(), "{opened:?}");
+            assert_eq!(
+                swapped.unwrap_err().kind(),
+                io::ErrorKind::PermissionDenied,
+            );
+        });
+    }
+}
@@ -0,0 +1,376 @@
+//! Sweeping of dangling symlinks.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use tracerr::Traced;
+
+use crate::{
+    DeleteFile, Exec, RelativePath, Storage, async_trait, list_files,
+    remove_existing_file, versioning::symlink_target, write_atomically,
+};
+
+/// Operation of sweeping the dangling symlinks of a bucket, whose targets
+/// don't exist (or resolve through a cycle).
+///
+/// The moment a symlink is detected dangling for the first time is persisted
+/// along with its target, so the `grace_period` is counted from it, rather
+/// than from the symlink creation. A symlink, whose target appears again, is
+/// not considered dangling anymore, and a symlink repointed to another missing
+/// target is considered dangling since the repointing is detected.
+///
+/// Dangling symlinks are deleted via the [`DeleteFile`] operation, so they're
+/// kept as versions if the bucket has [`Versioning`] configured, and are not
+/// deleted at all if being locked or protected. A symlink is deleted only if
+/// it still points to the same missing target under the lock of its key, so
+/// concurrent repointing is never undone.
+///
+/// [`Versioning`]: crate::Versioning
+#[derive(Clone, Debug)]
+pub struct SweepDanglingSymlinks {
+    /// [`RelativePath`] of the bucket to sweep.
+    pub bucket: RelativePath,
+
+    /// Period a symlink should stay dangling for, before being deleted.
+    ///
+    /// If [`None`], then dangling symlinks are only reported.
+    pub grace_period: Option<Duration>,
+}
+
+/// Dangling symlink, as reported by the [`SweepDanglingSymlinks`] operation.
+#[derive(Clone, Debug)]
+pub struct DanglingSymlink {
+    /// [`RelativePath`] of the symlink itself.
+    pub path: RelativePath,
+
+    /// [`RelativePath`] of the missing file the symlink points to.
+    pub target: Option<RelativePath>,
+
+    /// Time when this [`DanglingSymlink`] was detected for the first time.
+    pub dangling_since: SystemTime,
+
+    /// Indicator whether this [`DanglingSymlink`] has been deleted.
+    pub deleted: bool,
+}
+
+#[async_trait]
+impl Exec<SweepDanglingSymlinks> for Storage {
+    type Ok = Vec<DanglingSymlink>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(
+        &self,
+        op: SweepDanglingSymlinks,
+    ) -> Result<Self::Ok, Self::Err> {
+        let now = SystemTime::now();
+        let state = self.dangling_path(&op.bucket);
+        let mut detected: BTreeMap<String, Detection> =
+            match async_fs::read(&state).await {
+                Ok(bytes) => serde_json::from_slice(&bytes)
+                    .map_err(|e| tracerr::new!(io::Error::from(e)))?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    BTreeMap::new()
+                }
+                Err(e) => return Err(tracerr::new!(e)),
+            };
+
+        let dir = self
+            .resolve_beneath(&self.data_dir.join(&op.bucket), true)
+            .await
+            .map_err(tracerr::wrap!())?;
+        let mut dangling = Vec::new();
+        let mut still_detected = BTreeMap::new();
+        for key in list_files(&dir).await.map_err(tracerr::wrap!())? {
+            let Ok(rel) = RelativePath::try_from(key.clone()) else {
+                continue;
+            };
+            let path = op.bucket.clone().join(rel);
+            let live = dir.join(&key);
+            let Some(target) =
+                symlink_target(&live, &live).await.map_err(tracerr::wrap!())?
+            else {
+                continue;
+            };
+            match self.resolves_to_nothing(&live).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    tracing::warn!(%path, "Failed to resolve symlink: {e}");
+                    continue;
+                }
+            }
+
+            let since = detected
+                .remove(&key)
+                .filter(|d| d.target == target)
+                .map_or(now, |d| d.since);
+            let expired = op.grace_period.is_some_and(|grace| {
+                now.duration_since(since).is_ok_and(|d| d >= grace)
+            });
+            let mut deleted = false;
+            if expired {
+                match self.delete_dangling(&path, &live, &target).await {
+                    Ok(true) => deleted = true,
+                    // Has been repointed or removed concurrently, so will be
+                    // detected anew by the next sweep, if dangling still.
+                    Ok(false) => continue,
+                    Err(e) => tracing::warn!(
+                        %path,
+                        "Failed to delete dangling symlink: {e}",
+                    ),
+                }
+            }
+            if !deleted {
+                _ = still_detected
+                    .insert(key, Detection { target: target.clone(), since });
+            }
+            dangling.push(DanglingSymlink {
+                path,
+                target: self.relative_path(&target),
+                dangling_since: since,
+                deleted,
+            });
+        }
+
+        if still_detected.is_empty() {
+            remove_existing_file(&state).await.map_err(tracerr::wrap!())?;
+        } else {
+            let bytes = serde_json::to_vec_pretty(&still_detected)
+                .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+            write_atomically(&self.tmp_dir, &state, &bytes)
+                .await
+                .map_err(tracerr::wrap!())?;
+        }
+
+        Ok(dangling)
+    }
+}
+
+/// Dangling symlink detection, persisted by [`Storage`].
+#[derive(Debug, Deserialize, Serialize)]
+struct Detection {
+    /// Missing target the symlink has been detected pointing to.
+    target: PathBuf,
+
+    /// Time when the symlink has been detected pointing to the `target` for
+    /// the first time.
+    since: SystemTime,
+}
+
+impl Storage {
+    /// Deletes the symlink at the provided `path` via the [`DeleteFile`]
+    /// operation, if it still points to the provided missing `target` under
+    /// the lock of its key.
+    ///
+    /// Returns `false` if the symlink doesn't point to the missing `target`
+    /// anymore, so is not deleted.
+    ///
+    /// # Errors
+    ///
+    /// If the symlink fails to be resolved or deleted (like when being locked
+    /// or protected).
+    async fn delete_dangling(
+        &self,
+        path: &RelativePath,
+        live: &Path,
+        target: &Path,
+    ) -> Result<bool, Traced<io::Error>> {
+        let _key = self.lock_key(path).await?;
+        let current =
+            symlink_target(live, live).await.map_err(tracerr::wrap!())?;
+        if current.as_deref() != Some(target)
+            || !self
+                .resolves_to_nothing(live)
+                .await
+                .map_err(tracerr::wrap!())?
+        {
+            return Ok(false);
+        }
+
+        let delete = DeleteFile {
+            path: path.clone(),
+            version_id: None,
+            bypass_governance: false,
+        };
+        self.delete_file(delete).await.map(|_| true)
+    }
+
+    /// Checks whether the symlink at the provided `live` path resolves to no
+    /// existing file, either because of a missing target or a cycle.
+    ///
+    /// # Errors
+    ///
+    /// If the symlink fails to be resolved for another reason (like escaping
+    /// the data directory).
+    async fn resolves_to_nothing(&self, live: &Path) -> io::Result<bool> {
+        let resolved = match self.resolve_beneath(live, true).await {
+            Ok(resolved) => resolved,
+            Err(e) if e.kind() == io::ErrorKind::TooManyLinks => {
+                return Ok(true);
+            }
+            Err(e) => return Err(e),
+        };
+        match async_fs::metadata(resolved).await {
+            Ok(_) => Ok(false),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::NotADirectory,
+                ) =>
+            {
+                Ok(true)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the [`PathBuf`] where the dangling symlinks detected in the
+    /// provided `bucket` are persisted.
+    fn dangling_path(&self, bucket: &RelativePath) -> PathBuf {
+        self.meta_dir.join("buckets").join(bucket).join("dangling.json")
+    }
+}
+
+#[cfg(test)]
+mod sweep_spec {
+    use std::{path::Path, thread, time::Duration};
+
+    use futures::executor::block_on;
+
+    use crate::{
+        CreateSymlink, DeleteFile, Exec as _, Storage, SweepDanglingSymlinks,
+        testing::{TempDir, create_file, path},
+    };
+
+    async fn prepare(root: &Path) -> Storage {
+        let storage = Storage::new(root).await.unwrap();
+        for file in ["b/file", "b/gone"] {
+            storage.exec(create_file(file, b"data")).await.unwrap();
+        }
+        for (src, dest) in [("b/file", "b/alive"), ("b/gone", "b/dead")] {
+            storage
+                .exec(CreateSymlink {
+                    src: path(src),
+                    dest: path(dest),
+                    expected_src: None,
+                    allow_dangling: false,
+                    directory: false,
+                })
+                .await
+                .unwrap();
+        }
+        storage
+            .exec(DeleteFile {
+                path: path("b/gone"),
+                version_id: None,
+                bypass_governance: false,
+            })
+            .await
+            .unwrap();
+        storage
+    }
+
+    fn repoint(src: &str) -> CreateSymlink {
+        CreateSymlink {
+            src: path(src),
+            dest: path("b/dead"),
+            expected_src: None,
+            allow_dangling: true,
+            directory: false,
+        }
+    }
+
+    fn sweep(grace_period: Option<Duration>) -> SweepDanglingSymlinks {
+        SweepDanglingSymlinks { bucket: path("b"), grace_period }
+    }
+
+    #[test]
+    fn reports_dangling_symlinks_only() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = prepare(&root).await;
+
+            let first = storage.exec(sweep(None)).await.unwrap();
+            let second = storage.exec(sweep(None)).await.unwrap();
+
+            assert_eq!(first.len(), 1, "{first:?}");
+            assert_eq!(first[0].path.to_string(), "b/dead");
+            assert_eq!(
+                first[0].target.as_ref().map(ToString::to_string).as_deref(),
+                Some("b/gone"),
+            );
+            assert!(!first[0].deleted);
+            assert_eq!(second[0].dangling_since, first[0].dangling_since);
+        });
+    }
+
+    #[test]
+    fn deletes_after_grace_period() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = prepare(&root).await;
+
+            let kept = storage
+                .exec(sweep(Some(Duration::from_secs(3600))))
+                .await
+                .unwrap();
+            let deleted =
+                storage.exec(sweep(Some(Duration::ZERO))).await.unwrap();
+            let after = storage.exec(sweep(None)).await.unwrap();
+
+            assert!(!kept[0].deleted);
+            assert!(deleted[0].deleted);
+            assert!(after.is_empty(), "{after:?}");
+        });
+    }
+
+    #[test]
+    fn resets_grace_period_on_repointing() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = prepare(&root).await;
+
+            let first = storage.exec(sweep(None)).await.unwrap();
+            thread::sleep(Duration::from_millis(10));
+            storage.exec(repoint("b/lost")).await.unwrap();
+            let second = storage.exec(sweep(None)).await.unwrap();
+
+            assert_eq!(
+                second[0].target.as_ref().map(ToString::to_string).as_deref(),
+                Some("b/lost"),
+            );
+            assert!(
+                second[0].dangling_since > first[0].dangling_since,
+                "{second:?}",
+            );
+        });
+    }
+
+    #[test]
+    fn does_not_delete_repointed_symlinks() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = prepare(&root).await;
+            let live = storage.data_dir.join("b/dead");
+            let observed = storage.data_dir.join("b/gone");
+
+            storage.exec(repoint("b/lost")).await.unwrap();
+            let deleted = storage
+                .delete_dangling(&path("b/dead"), &live, &observed)
+                .await
+                .unwrap();
+            let after = storage.exec(sweep(None)).await.unwrap();
+
+            assert!(!deleted);
+            assert_eq!(after.len(), 1, "{after:?}");
+            assert!(!after[0].deleted);
+        });
+    }
+}
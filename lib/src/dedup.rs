@@ -0,0 +1,201 @@
+//! Content-addressed deduplication of stored files.
+//!
+//! Once deduplication is enabled, the stored contents of every created file
+//! are kept once as a blob named after their SHA-256 digest, while the file
+//! itself becomes a hard link to this blob. This way, the number of hard links
+//! to a blob serves as its reference count, being naturally decremented once
+//! a file referencing it is overwritten or deleted (including its versions).
+
+use std::{io, os::unix::fs::MetadataExt as _, path::Path};
+
+use tracerr::Traced;
+use uuid::Uuid;
+
+use crate::{Exec, Storage, async_trait, list_files, remove_existing_file};
+
+/// Operation of removing blobs not referenced by any file anymore.
+///
+/// Returns the number of removed blobs.
+#[derive(Clone, Copy, Debug)]
+pub struct CollectBlobs;
+
+#[async_trait]
+impl Exec<CollectBlobs> for Storage {
+    type Ok = usize;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, _: CollectBlobs) -> Result<Self::Ok, Self::Err> {
+        let mut removed = 0;
+        for name in
+            list_files(&self.blobs_dir).await.map_err(tracerr::wrap!())?
+        {
+            let blob = self.blobs_dir.join(name);
+
+            // Holding the lock guarantees no new reference to the blob is
+            // being created concurrently.
+            let _lock = self.blobs_lock.lock().await;
+            let meta = match async_fs::symlink_metadata(&blob).await {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(tracerr::new!(e)),
+            };
+            if meta.nlink() <= 1 {
+                remove_existing_file(&blob).await.map_err(tracerr::wrap!())?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+impl Storage {
+    /// Enables or disables content-addressed deduplication of created files.
+    ///
+    /// Blobs are not removed once unreferenced, so [`CollectBlobs`] should be
+    /// executed periodically to free the space they occupy.
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub const fn with_deduplication(mut self, enabled: bool) -> Self {
+        self.deduplicate = enabled;
+        self
+    }
+
+    /// Replaces the temporary file at the provided `tmp` path with a hard
+    /// link to the blob of the provided SHA-256 `digest` of its contents,
+    /// storing the file as this blob if there is none yet.
+    ///
+    /// # Errors
+    ///
+    /// If any of filesystem operations fails.
+    pub(crate) async fn deduplicate(
+        &self,
+        tmp: &Path,
+        digest: &[u8],
+    ) -> Result<(), Traced<io::Error>> {
+        let digest = hex::encode(digest);
+        let dir = self.blobs_dir.join(&digest[..2]);
+        async_fs::create_dir_all(&dir).await.map_err(tracerr::wrap!())?;
+        let blob = dir.join(&digest);
+
+        let _lock = self.blobs_lock.lock().await;
+        match async_fs::hard_link(tmp, &blob).await {
+            Ok(()) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(tracerr::new!(e)),
+        }
+
+        // The link is created aside and renamed over, so the `tmp` file is
+        // never missing.
+        let link = self.tmp_dir.join(Uuid::new_v4().to_string());
+        async_fs::hard_link(&blob, &link).await.map_err(tracerr::wrap!())?;
+        if let Err(e) = async_fs::rename(&link, tmp).await {
+            _ = async_fs::remove_file(&link).await;
+            return Err(tracerr::new!(e));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod dedup_spec {
+    use std::{
+        fs,
+        os::unix::fs::MetadataExt as _,
+        time::{Duration, SystemTime},
+    };
+
+    use futures::executor::block_on;
+
+    use crate::{
+        CollectBlobs, DeleteFile, Exec as _, Lifecycle, LifecycleRule,
+        ListVersions, Storage,
+        testing::{TempDir, create_file, path},
+    };
+
+    #[test]
+    fn stores_identical_contents_once() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage =
+                Storage::new(&*root).await.unwrap().with_deduplication(true);
+            storage.exec(create_file("b/one", b"data")).await.unwrap();
+            storage.exec(create_file("c/two", b"data")).await.unwrap();
+
+            let one = async_fs::metadata(root.join("data/b/one")).await;
+            let two = async_fs::metadata(root.join("data/c/two")).await;
+            let collected = storage.exec(CollectBlobs).await;
+
+            let (one, two) = (one.unwrap(), two.unwrap());
+            assert_eq!(one.ino(), two.ino(), "Contents are stored twice");
+            assert_eq!(one.nlink(), 3, "Blob is not referenced");
+            assert_eq!(collected.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn collects_unreferenced_blobs_only() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage =
+                Storage::new(&*root).await.unwrap().with_deduplication(true);
+            storage.exec(create_file("b/one", b"data")).await.unwrap();
+            storage.exec(create_file("b/two", b"data")).await.unwrap();
+            storage.exec(create_file("b/one", b"other")).await.unwrap();
+
+            let referenced = storage.exec(CollectBlobs).await;
+            storage
+                .exec(DeleteFile {
+                    path: path("b/two"),
+                    version_id: None,
+                    bypass_governance: false,
+                })
+                .await
+                .unwrap();
+            let unreferenced = storage.exec(CollectBlobs).await;
+            let one = async_fs::read(root.join("data/b/one")).await;
+
+            assert_eq!(referenced.unwrap(), 0);
+            assert_eq!(unreferenced.unwrap(), 1);
+            assert_eq!(one.unwrap(), b"other");
+        });
+    }
+
+    #[test]
+    fn does_not_expire_new_files_sharing_old_blobs() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage =
+                Storage::new(&*root).await.unwrap().with_deduplication(true);
+            storage.exec(create_file("b/old", b"data")).await.unwrap();
+            let week_ago = SystemTime::now() - Duration::from_secs(7 * 86400);
+            fs::File::options()
+                .write(true)
+                .open(root.join("data/b/old"))
+                .and_then(|f| f.set_modified(week_ago))
+                .unwrap();
+            storage.exec(create_file("b/new", b"data")).await.unwrap();
+
+            let versions = storage
+                .exec(ListVersions { bucket: path("b"), prefix: None })
+                .await
+                .unwrap();
+            let lifecycle = Lifecycle {
+                rules: vec![LifecycleRule {
+                    enabled: true,
+                    expiration_days: Some(1),
+                    ..LifecycleRule::default()
+                }],
+            };
+            let expired = lifecycle
+                .expired(&path("b"), &versions, SystemTime::now())
+                .into_iter()
+                .map(|op| op.path.to_string())
+                .collect::<Vec<_>>();
+
+            assert_eq!(expired, ["b/old"]);
+        });
+    }
+}
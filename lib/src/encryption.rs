@@ -0,0 +1,646 @@
+//! Encryption of files at rest.
+//!
+//! # Format
+//!
+//! An encrypted file starts with a header, containing a random data key of
+//! the file, wrapped with either the master key of the [`Storage`] or the key
+//! provided by a customer. The header is followed by the file contents,
+//! split into [`CHUNK_SIZE`] chunks, each one encrypted and authenticated
+//! separately with the data key, so any byte range of the file can be read
+//! without decrypting the whole file.
+
+use std::{
+    fmt, io, mem,
+    pin::Pin,
+    str::FromStr,
+    task::{self, Poll},
+};
+
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead as _, AeadCore as _, KeyInit as _, OsRng, Payload},
+};
+use async_fs::File;
+use derive_more::with_trait::{Display, Error};
+use futures::{AsyncRead, AsyncReadExt as _, AsyncSeek, AsyncSeekExt as _};
+
+use crate::{AccessDeniedError, Storage};
+
+/// Magic bytes an encrypted file starts with.
+pub(crate) const MAGIC: &[u8; 8] = b"BAZAENC\x01";
+
+/// Size of a plaintext chunk an encrypted file is split into.
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Size of an authentication tag appended to every encrypted chunk.
+const TAG_LEN: u64 = 16;
+
+/// Size of an [`EncryptionKey`].
+const KEY_LEN: usize = 32;
+
+/// Size of a nonce used for wrapping data keys.
+const NONCE_LEN: usize = 12;
+
+/// Size of the header of an encrypted file.
+const HEADER_LEN: u64 =
+    (MAGIC.len() + 1 + NONCE_LEN + KEY_LEN) as u64 + TAG_LEN;
+
+/// 256-bit key to encrypt files with.
+#[derive(Clone)]
+pub struct EncryptionKey([u8; KEY_LEN]);
+
+impl EncryptionKey {
+    /// Returns the bytes of this [`EncryptionKey`].
+    #[must_use]
+    pub const fn as_bytes(&self) -> &[u8; KEY_LEN] {
+        &self.0
+    }
+
+    /// Creates a new [`Aes256Gcm`] cipher out of this [`EncryptionKey`].
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.0))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+impl TryFrom<&[u8]> for EncryptionKey {
+    type Error = InvalidEncryptionKeyError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        bytes.try_into().map(Self).map_err(|_| InvalidEncryptionKeyError)
+    }
+}
+
+impl FromStr for EncryptionKey {
+    type Err = InvalidEncryptionKeyError;
+
+    /// Parses an [`EncryptionKey`] out of its hex representation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = hex::decode(s).map_err(|_| InvalidEncryptionKeyError)?;
+        Self::try_from(bytes.as_slice())
+    }
+}
+
+/// Error of parsing an [`EncryptionKey`].
+#[derive(Debug, Display, Error)]
+#[display("Invalid `EncryptionKey`: 256-bit key is expected")]
+pub struct InvalidEncryptionKeyError;
+
+/// Encryption of a file at rest.
+#[derive(Clone, Debug)]
+pub enum Encryption {
+    /// Encryption with the master key of the [`Storage`] (SSE-S3).
+    Managed,
+
+    /// Encryption with the provided key of a customer (SSE-C).
+    ///
+    /// The key is never stored, so must be provided to read the file.
+    Customer(EncryptionKey),
+}
+
+impl Encryption {
+    /// Returns the byte identifying this [`Encryption`] in the header of an
+    /// encrypted file.
+    const fn tag(&self) -> u8 {
+        match self {
+            Self::Managed => 1,
+            Self::Customer(_) => 2,
+        }
+    }
+}
+
+impl Storage {
+    /// Sets the master [`EncryptionKey`] of this [`Storage`], used for
+    /// [`Encryption::Managed`].
+    #[must_use]
+    pub fn with_master_key(mut self, key: EncryptionKey) -> Self {
+        self.master_key = Some(key);
+        self
+    }
+
+    /// Creates a new [`Encryptor`] for the provided [`Encryption`].
+    ///
+    /// # Errors
+    ///
+    /// With [`io::ErrorKind::InvalidInput`], if [`Encryption::Managed`] is
+    /// requested, while this [`Storage`] has no master key.
+    pub(crate) fn encryptor(
+        &self,
+        encryption: &Encryption,
+    ) -> io::Result<Encryptor> {
+        let kek = match encryption {
+            Encryption::Managed => {
+                self.master_key.as_ref().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Server-side encryption is not configured",
+                    )
+                })?
+            }
+            Encryption::Customer(key) => key,
+        };
+        Encryptor::new(kek, encryption.tag())
+    }
+
+    /// Reads the header of the provided `file`, returning a [`Decryptor`] of
+    /// its contents, if it's encrypted.
+    ///
+    /// Rewinds the `file` to its start, if it's not encrypted.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::InvalidInput`], if the `file` is encrypted with
+    ///   a customer key, but no `key` is provided, or a `key` is provided for
+    ///   a file not encrypted with a customer key.
+    /// - With [`io::ErrorKind::PermissionDenied`], if the `file` is encrypted
+    ///   with another key.
+    /// - If the `file` fails to be read.
+    pub(crate) async fn decryptor(
+        &self,
+        file: &mut File,
+        key: Option<&EncryptionKey>,
+    ) -> io::Result<Option<Decryptor>> {
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        _ = file.take(HEADER_LEN).read_to_end(&mut header).await?;
+        if !header.starts_with(MAGIC) {
+            if key.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "File is not encrypted with a customer-provided key",
+                ));
+            }
+            _ = file.seek(io::SeekFrom::Start(0)).await?;
+            return Ok(None);
+        }
+        if header.len() < HEADER_LEN as usize {
+            return Err(invalid_data("Truncated encryption header"));
+        }
+
+        let encryption = match (header[MAGIC.len()], key) {
+            (1, None) => Encryption::Managed,
+            (2, Some(key)) => Encryption::Customer(key.clone()),
+            (1, Some(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "File is not encrypted with a customer-provided key",
+                ));
+            }
+            (2, None) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "File is encrypted with a customer-provided key, which \
+                     must be provided to read it",
+                ));
+            }
+            _ => return Err(invalid_data("Unknown encryption type")),
+        };
+        let kek = match &encryption {
+            Encryption::Managed => {
+                self.master_key.as_ref().ok_or_else(|| {
+                    io::Error::other("Server-side encryption is not configured")
+                })?
+            }
+            Encryption::Customer(key) => key,
+        };
+        let (aad, rest) = header.split_at(MAGIC.len() + 1);
+        let (nonce, wrapped) = rest.split_at(NONCE_LEN);
+        let dek = kek
+            .cipher()
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: wrapped, aad })
+            .map_err(|_| {
+                AccessDeniedError::io(
+                    "Provided encryption key doesn't match the one the file \
+                     is encrypted with",
+                )
+            })?;
+
+        let (size, chunks) = content_size(file.metadata().await?.len())
+            .ok_or_else(|| invalid_data("Encrypted file has invalid size"))?;
+        Ok(Some(Decryptor {
+            encryption,
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&dek)),
+            size,
+            chunks,
+            pos: 0,
+            chunk: None,
+            state: State::Idle,
+        }))
+    }
+}
+
+/// Calculates the size of the decrypted contents and the number of chunks of
+/// an encrypted file of the provided size (`len`).
+///
+/// Returns [`None`] if the `len` is not a valid size of an encrypted file.
+pub(crate) const fn content_size(len: u64) -> Option<(u64, u64)> {
+    let Some(body) = len.checked_sub(HEADER_LEN) else {
+        return None;
+    };
+    let (full, rem) =
+        (body / (CHUNK_SIZE + TAG_LEN), body % (CHUNK_SIZE + TAG_LEN));
+    if rem == 0 && full > 0 {
+        Some((full * CHUNK_SIZE, full))
+    } else if rem >= TAG_LEN {
+        Some((full * CHUNK_SIZE + rem - TAG_LEN, full + 1))
+    } else {
+        None
+    }
+}
+
+/// Returns the nonce of the chunk with the provided `index`.
+///
+/// The last chunk is distinguished, so an encrypted file cannot be truncated
+/// at a chunk boundary unnoticed.
+fn chunk_nonce(index: u64, is_last: bool) -> [u8; NONCE_LEN] {
+    let mut nonce = [0; NONCE_LEN];
+    nonce[0] = u8::from(is_last);
+    nonce[4..].copy_from_slice(&index.to_be_bytes());
+    nonce
+}
+
+/// Creates a new [`io::ErrorKind::InvalidData`] error of a corrupted
+/// encrypted file.
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Encryptor of file contents.
+pub(crate) struct Encryptor {
+    /// Cipher of the data key.
+    cipher: Aes256Gcm,
+
+    /// Plaintext not encrypted yet.
+    buf: Vec<u8>,
+
+    /// Index of the next chunk to be encrypted.
+    index: u64,
+
+    /// Header of the encrypted file, not returned yet.
+    header: Option<Vec<u8>>,
+}
+
+impl Encryptor {
+    /// Creates a new [`Encryptor`] with a random data key, wrapped with the
+    /// provided `kek` (key encryption key).
+    fn new(kek: &EncryptionKey, tag: u8) -> io::Result<Self> {
+        let dek = Aes256Gcm::generate_key(OsRng);
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(MAGIC);
+        header.push(tag);
+        let wrapped = kek
+            .cipher()
+            .encrypt(&nonce, Payload { msg: dek.as_slice(), aad: &header })
+            .map_err(|_| io::Error::other("Failed to wrap data key"))?;
+        header.extend_from_slice(nonce.as_slice());
+        header.extend_from_slice(&wrapped);
+
+        Ok(Self {
+            cipher: Aes256Gcm::new(&dek),
+            buf: Vec::with_capacity(CHUNK_SIZE as usize),
+            index: 0,
+            header: Some(header),
+        })
+    }
+
+    /// Encrypts the provided `data`, returning the encrypted bytes ready to be
+    /// written.
+    ///
+    /// # Errors
+    ///
+    /// If encryption fails.
+    pub(crate) fn update(&mut self, mut data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = self.header.take().unwrap_or_default();
+        while !data.is_empty() {
+            // A full chunk is encrypted only once more data follows it, as
+            // the last chunk must be distinguished.
+            if self.buf.len() == CHUNK_SIZE as usize {
+                out.extend(self.seal(false)?);
+            }
+            let n = data.len().min(CHUNK_SIZE as usize - self.buf.len());
+            self.buf.extend_from_slice(&data[..n]);
+            data = &data[n..];
+        }
+        Ok(out)
+    }
+
+    /// Encrypts the rest of the data, returning the last encrypted bytes to
+    /// be written.
+    ///
+    /// # Errors
+    ///
+    /// If encryption fails.
+    pub(crate) fn finish(mut self) -> io::Result<Vec<u8>> {
+        let mut out = self.header.take().unwrap_or_default();
+        out.extend(self.seal(true)?);
+        Ok(out)
+    }
+
+    /// Encrypts the buffered plaintext as the next chunk.
+    fn seal(&mut self, is_last: bool) -> io::Result<Vec<u8>> {
+        let nonce = chunk_nonce(self.index, is_last);
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), self.buf.as_slice())
+            .map_err(|_| io::Error::other("Failed to encrypt chunk"))?;
+        self.buf.clear();
+        self.index += 1;
+        Ok(sealed)
+    }
+}
+
+/// Decryptor of file contents, supporting random access.
+pub(crate) struct Decryptor {
+    /// [`Encryption`] of the file.
+    encryption: Encryption,
+
+    /// Cipher of the data key.
+    cipher: Aes256Gcm,
+
+    /// Size of the decrypted contents.
+    size: u64,
+
+    /// Number of chunks of the file.
+    chunks: u64,
+
+    /// Current position in the decrypted contents.
+    pos: u64,
+
+    /// Index and decrypted bytes of the latest read chunk.
+    chunk: Option<(u64, Vec<u8>)>,
+
+    /// [`State`] of reading the next chunk.
+    state: State,
+}
+
+/// State of reading a chunk by a [`Decryptor`].
+enum State {
+    /// No chunk is being read.
+    Idle,
+
+    /// Seeking to the chunk with the specified index.
+    Seeking(u64),
+
+    /// Reading the encrypted bytes of the chunk with the specified index.
+    Reading {
+        /// Index of the chunk.
+        index: u64,
+
+        /// Encrypted bytes of the chunk.
+        raw: Vec<u8>,
+
+        /// Number of the encrypted bytes read already.
+        filled: usize,
+    },
+}
+
+impl Decryptor {
+    /// Returns the [`Encryption`] of the decrypted file.
+    pub(crate) const fn encryption(&self) -> &Encryption {
+        &self.encryption
+    }
+
+    /// Returns the size of the decrypted contents.
+    pub(crate) const fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Decrypts the provided `raw` bytes of the chunk with the provided
+    /// `index`.
+    fn open(&self, index: u64, raw: &[u8]) -> io::Result<Vec<u8>> {
+        let nonce = chunk_nonce(index, index + 1 == self.chunks);
+        self.cipher
+            .decrypt(Nonce::from_slice(&nonce), raw)
+            .map_err(|_| invalid_data("Encrypted chunk is corrupted"))
+    }
+
+    /// Returns the length of the encrypted chunk with the provided `index`.
+    fn raw_len(&self, index: u64) -> usize {
+        let plain = (self.size - index * CHUNK_SIZE).min(CHUNK_SIZE);
+        (plain + TAG_LEN) as usize
+    }
+
+    /// Reads the decrypted contents from the provided `file` into the
+    /// provided `buf`fer.
+    pub(crate) fn poll_read(
+        &mut self,
+        mut file: Pin<&mut File>,
+        cx: &mut task::Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            if buf.is_empty() || self.pos >= self.size {
+                return Poll::Ready(Ok(0));
+            }
+            let index = self.pos / CHUNK_SIZE;
+            if let Some((_, plain)) =
+                self.chunk.as_ref().filter(|(i, _)| *i == index)
+            {
+                let offset = (self.pos - index * CHUNK_SIZE) as usize;
+                let n = buf.len().min(plain.len() - offset);
+                buf[..n].copy_from_slice(&plain[offset..offset + n]);
+                self.pos += n as u64;
+                return Poll::Ready(Ok(n));
+            }
+
+            match mem::replace(&mut self.state, State::Idle) {
+                State::Idle => self.state = State::Seeking(index),
+                State::Seeking(index) => {
+                    let offset = HEADER_LEN + index * (CHUNK_SIZE + TAG_LEN);
+                    match file
+                        .as_mut()
+                        .poll_seek(cx, io::SeekFrom::Start(offset))
+                    {
+                        Poll::Ready(res) => _ = res?,
+                        Poll::Pending => {
+                            self.state = State::Seeking(index);
+                            return Poll::Pending;
+                        }
+                    }
+                    self.state = State::Reading {
+                        index,
+                        raw: vec![0; self.raw_len(index)],
+                        filled: 0,
+                    };
+                }
+                State::Reading { index, mut raw, mut filled } => {
+                    while filled < raw.len() {
+                        match file.as_mut().poll_read(cx, &mut raw[filled..]) {
+                            Poll::Ready(Ok(0)) => {
+                                return Poll::Ready(Err(invalid_data(
+                                    "Encrypted file is truncated",
+                                )));
+                            }
+                            Poll::Ready(Ok(n)) => filled += n,
+                            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                            Poll::Pending => {
+                                self.state =
+                                    State::Reading { index, raw, filled };
+                                return Poll::Pending;
+                            }
+                        }
+                    }
+                    self.chunk = Some((index, self.open(index, &raw)?));
+                }
+            }
+        }
+    }
+
+    /// Seeks to the provided position in the decrypted contents.
+    ///
+    /// # Errors
+    ///
+    /// With [`io::ErrorKind::InvalidInput`], if seeking before the start.
+    pub(crate) fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            io::SeekFrom::Start(n) => Some(n),
+            io::SeekFrom::End(n) => self.size.checked_add_signed(n),
+            io::SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        };
+        self.pos = pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod encryption_spec {
+    use std::{io, time::SystemTime};
+
+    use async_fs::File;
+    use futures::{AsyncReadExt as _, AsyncSeekExt as _, executor::block_on};
+
+    use super::{
+        CHUNK_SIZE, Encryption, EncryptionKey, Encryptor, HEADER_LEN, TAG_LEN,
+        content_size,
+    };
+    use crate::{ReadOnlyFile, Storage, testing::TempDir};
+
+    fn encrypt(enc: Encryptor, data: &[u8], chunk: usize) -> Vec<u8> {
+        let mut enc = enc;
+        let mut out = Vec::new();
+        for part in data.chunks(chunk) {
+            out.extend(enc.update(part).unwrap());
+        }
+        out.extend(enc.finish().unwrap());
+        out
+    }
+
+    /// Stores the provided `data` encrypted with the provided [`Encryption`]
+    /// and opens it with the provided `key`.
+    async fn roundtrip(
+        data: &[u8],
+        encryption: &Encryption,
+        key: Option<&EncryptionKey>,
+    ) -> io::Result<ReadOnlyFile> {
+        let root = TempDir::new();
+        let storage = Storage::new(&*root)
+            .await
+            .unwrap()
+            .with_master_key(EncryptionKey([1; 32]));
+        let path = root.join("file");
+        let bytes = encrypt(storage.encryptor(encryption)?, data, 1000);
+        async_fs::write(&path, bytes).await?;
+
+        let mut file = File::open(&path).await?;
+        let res = storage.decryptor(&mut file, key).await;
+        Ok(ReadOnlyFile {
+            file,
+            version_id: None,
+            last_modified: SystemTime::UNIX_EPOCH,
+            symlink_target: None,
+            decryptor: res?,
+            decompressor: None,
+        })
+    }
+
+    #[test]
+    fn calculates_content_size() {
+        let chunk = CHUNK_SIZE as usize;
+        for len in [0, 1, chunk - 1, chunk, chunk + 1, 3 * chunk] {
+            let enc = Encryptor::new(&EncryptionKey([7; 32]), 1).unwrap();
+            let encrypted = encrypt(enc, &vec![1; len], 1000);
+
+            let (size, chunks) = content_size(encrypted.len() as u64).unwrap();
+            assert_eq!(size, len as u64, "len: {len}");
+            assert_eq!(chunks, (len as u64).div_ceil(CHUNK_SIZE).max(1));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_size() {
+        assert!(content_size(HEADER_LEN - 1).is_none());
+        assert!(content_size(HEADER_LEN).is_none());
+        assert!(content_size(HEADER_LEN + TAG_LEN - 1).is_none());
+    }
+
+    #[test]
+    fn reads_ranges() {
+        let data = (0..3 * CHUNK_SIZE as usize + 10)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        block_on(async {
+            let mut file =
+                roundtrip(&data, &Encryption::Managed, None).await.unwrap();
+            assert_eq!(file.size().await.unwrap(), data.len() as u64);
+
+            let mut all = Vec::new();
+            _ = file.read_to_end(&mut all).await.unwrap();
+            assert!(all == data, "decrypted contents differ");
+
+            let start = CHUNK_SIZE - 5;
+            _ = file.seek(io::SeekFrom::Start(start)).await.unwrap();
+            let mut range = vec![0; 10];
+            file.read_exact(&mut range).await.unwrap();
+            assert_eq!(range, data[start as usize..][..10]);
+
+            _ = file.seek(io::SeekFrom::End(-3)).await.unwrap();
+            let mut tail = Vec::new();
+            _ = file.read_to_end(&mut tail).await.unwrap();
+            assert_eq!(tail, data[data.len() - 3..]);
+        });
+    }
+
+    #[test]
+    fn requires_matching_customer_key() {
+        let key = EncryptionKey([2; 32]);
+        let customer = Encryption::Customer(key.clone());
+        block_on(async {
+            let missing = roundtrip(b"data", &customer, None).await;
+            assert_eq!(
+                missing.err().map(|e| e.kind()),
+                Some(io::ErrorKind::InvalidInput),
+            );
+
+            let wrong = EncryptionKey([3; 32]);
+            let mismatch = roundtrip(b"data", &customer, Some(&wrong)).await;
+            assert_eq!(
+                mismatch.err().map(|e| e.kind()),
+                Some(io::ErrorKind::PermissionDenied),
+            );
+
+            let mut file =
+                roundtrip(b"data", &customer, Some(&key)).await.unwrap();
+            let mut out = Vec::new();
+            _ = file.read_to_end(&mut out).await.unwrap();
+            assert_eq!(out, b"data");
+        });
+    }
+
+    #[test]
+    fn parses_hex_key() {
+        assert!("00".repeat(32).parse::<EncryptionKey>().is_ok());
+        assert!("00".repeat(31).parse::<EncryptionKey>().is_err());
+        assert!("zz".repeat(32).parse::<EncryptionKey>().is_err());
+    }
+}
@@ -0,0 +1,319 @@
+//! History of symlink targets.
+
+use std::{io, path::PathBuf, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tracerr::Traced;
+use uuid::Uuid;
+
+use crate::{
+    Exec, PreconditionError, RelativePath, Storage, async_trait,
+    relative_target, remove_existing_file, write_atomically,
+};
+
+/// Operation of getting the history of previous targets of a symlink.
+///
+/// Only a limited number of the latest targets is kept, as configured via
+/// [`Storage::with_symlink_history_len()`].
+#[derive(Clone, Debug)]
+pub struct GetSymlinkHistory {
+    /// [`RelativePath`] of the symlink.
+    pub path: RelativePath,
+}
+
+/// Previous target of a symlink, as returned by the [`GetSymlinkHistory`]
+/// operation.
+#[derive(Clone, Debug)]
+pub struct SymlinkHistoryEntry {
+    /// [`RelativePath`] of the original source file the symlink pointed to.
+    pub target: RelativePath,
+
+    /// Time when the symlink was repointed from the `target`.
+    pub replaced_at: SystemTime,
+}
+
+#[async_trait]
+impl Exec<GetSymlinkHistory> for Storage {
+    type Ok = Vec<SymlinkHistoryEntry>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: GetSymlinkHistory) -> Result<Self::Ok, Self::Err> {
+        let history =
+            self.read_history(&op.path).await.map_err(tracerr::wrap!())?;
+        Ok(history
+            .entries
+            .into_iter()
+            .rev()
+            .filter_map(|e| {
+                Some(SymlinkHistoryEntry {
+                    target: RelativePath::try_from(e.target).ok()?,
+                    replaced_at: e.replaced_at,
+                })
+            })
+            .collect())
+    }
+}
+
+/// Operation of rolling back a symlink to its previous target.
+///
+/// The symlink is repointed atomically, only if it hasn't been repointed
+/// concurrently, and the previous target is removed from its history, so
+/// consecutive rollbacks go further back.
+///
+/// Returns the [`RelativePath`] of the original source file the symlink points
+/// to now.
+#[derive(Clone, Debug)]
+pub struct RollbackSymlink {
+    /// [`RelativePath`] of the symlink to roll back.
+    pub path: RelativePath,
+}
+
+#[async_trait]
+impl Exec<RollbackSymlink> for Storage {
+    type Ok = RelativePath;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: RollbackSymlink) -> Result<Self::Ok, Self::Err> {
+        let _key = self.lock_key(&op.path).await?;
+        let current = self
+            .current_target(&op.path)
+            .await
+            .map_err(tracerr::wrap!())?
+            .ok_or_else(|| {
+                tracerr::new!(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("`{}` is not a symlink", op.path),
+                ))
+            })?;
+        let previous = self
+            .read_history(&op.path)
+            .await
+            .map_err(tracerr::wrap!())?
+            .entries
+            .pop()
+            .and_then(|e| RelativePath::try_from(e.target).ok())
+            .ok_or_else(|| {
+                tracerr::new!(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Symlink `{}` has no previous targets", op.path),
+                ))
+            })?;
+
+        let dest = self.live_path(&op.path).await.map_err(tracerr::wrap!())?;
+        let src = self.live_path(&previous).await.map_err(tracerr::wrap!())?;
+        // Directory aliases are rolled back to directories only.
+        let directory =
+            async_fs::metadata(&dest).await.is_ok_and(|m| m.is_dir());
+        self.ensure_resolvable(&dest, &src, directory)
+            .await
+            .map_err(tracerr::wrap!())?;
+
+        let tmp = self.tmp_dir.join(Uuid::new_v4().to_string());
+        async_fs::unix::symlink(relative_target(&dest, &src), &tmp)
+            .await
+            .map_err(tracerr::wrap!())?;
+
+        let res = self.commit(&tmp, &op.path, Some(&current), true).await;
+        if res.is_err() {
+            _ = async_fs::remove_file(&tmp).await;
+        }
+        res.map(|_| previous)
+    }
+}
+
+/// History of previous targets of a symlink, persisted by [`Storage`].
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct History {
+    /// [`RelativePath`] of the symlink.
+    path: String,
+
+    /// Previous targets of the symlink, from the oldest to the latest one.
+    entries: Vec<Entry>,
+}
+
+/// Single previous target in a [`History`].
+#[derive(Debug, Deserialize, Serialize)]
+struct Entry {
+    /// [`RelativePath`] of the original source file the symlink pointed to.
+    target: String,
+
+    /// Time when the symlink was repointed from the `target`.
+    replaced_at: SystemTime,
+}
+
+impl Storage {
+    /// Sets the maximum number of previous targets kept in the history of
+    /// every symlink.
+    ///
+    /// [`Storage::DEFAULT_SYMLINK_HISTORY_LEN`] is used by default, while `0`
+    /// disables the history completely.
+    #[must_use]
+    pub const fn with_symlink_history_len(mut self, len: usize) -> Self {
+        self.symlink_history_len = len;
+        self
+    }
+
+    /// Checks whether the symlink at the provided `path` may be rolled back to
+    /// the provided `target`, being the latest one in its history.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::AlreadyExists`], if the history has been
+    ///   changed concurrently.
+    /// - If the history fails to be read.
+    pub(crate) async fn ensure_rollback(
+        &self,
+        path: &RelativePath,
+        target: Option<&RelativePath>,
+    ) -> Result<(), Traced<io::Error>> {
+        let history =
+            self.read_history(path).await.map_err(tracerr::wrap!())?;
+        let latest = history.entries.last().map(|e| e.target.as_str());
+        if latest.is_none()
+            || latest != target.map(ToString::to_string).as_deref()
+        {
+            return Err(tracerr::new!(PreconditionError::io(format!(
+                "History of symlink `{path}` has changed",
+            ))));
+        }
+        Ok(())
+    }
+
+    /// Updates the history of the symlink at the provided `path`, once it's
+    /// repointed from the `before` target to the `after` one.
+    ///
+    /// On `rollback`, the latest target is removed from the history instead
+    /// of adding the `before` one.
+    ///
+    /// # Errors
+    ///
+    /// If the history fails to be read or written.
+    pub(crate) async fn update_history(
+        &self,
+        path: &RelativePath,
+        before: Option<&RelativePath>,
+        after: Option<&RelativePath>,
+        rollback: bool,
+    ) -> Result<(), Traced<io::Error>> {
+        if !rollback
+            && (self.symlink_history_len == 0
+                || before.is_none()
+                || before.map(ToString::to_string)
+                    == after.map(ToString::to_string))
+        {
+            return Ok(());
+        }
+
+        let mut history =
+            self.read_history(path).await.map_err(tracerr::wrap!())?;
+        if rollback {
+            _ = history.entries.pop();
+        } else if let Some(target) = before {
+            history.entries.push(Entry {
+                target: target.to_string(),
+                replaced_at: SystemTime::now(),
+            });
+            let excess =
+                history.entries.len().saturating_sub(self.symlink_history_len);
+            _ = history.entries.drain(..excess);
+        }
+
+        let file = self.history_path(path);
+        if history.entries.is_empty() {
+            return remove_existing_file(file).await.map_err(tracerr::wrap!());
+        }
+        let bytes = serde_json::to_vec_pretty(&history)
+            .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+        write_atomically(&self.tmp_dir, &file, &bytes)
+            .await
+            .map_err(tracerr::wrap!())
+    }
+
+    /// Reads the [`History`] of the symlink at the provided `path`.
+    ///
+    /// # Errors
+    ///
+    /// If the [`History`] fails to be read or deserialized.
+    async fn read_history(&self, path: &RelativePath) -> io::Result<History> {
+        match async_fs::read(self.history_path(path)).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Ok(History { path: path.to_string(), ..History::default() })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the [`PathBuf`] where the [`History`] of the symlink at the
+    /// provided `path` is stored.
+    fn history_path(&self, path: &RelativePath) -> PathBuf {
+        self.meta_dir.join("history").join(path.bucket()).join(format!(
+            "{}.json",
+            hex::encode(Sha256::digest(path.to_string())),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod rollback_spec {
+    use std::io;
+
+    use futures::executor::block_on;
+
+    use crate::{
+        CreateSymlink, Exec as _, GetSymlinkHistory, RollbackSymlink, Storage,
+        testing::{TempDir, create_file, path},
+    };
+
+    #[test]
+    fn goes_back_through_history() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage =
+                Storage::new(&*root).await.unwrap().with_symlink_history_len(2);
+            for n in 1..=4 {
+                storage
+                    .exec(create_file(&format!("b/v{n}"), &[n]))
+                    .await
+                    .unwrap();
+                storage
+                    .exec(CreateSymlink {
+                        src: path(&format!("b/v{n}")),
+                        dest: path("b/link"),
+                        expected_src: None,
+                        allow_dangling: false,
+                        directory: false,
+                    })
+                    .await
+                    .unwrap();
+            }
+
+            let history =
+                storage.exec(GetSymlinkHistory { path: path("b/link") }).await;
+            let rollback = || RollbackSymlink { path: path("b/link") };
+            let first = storage.exec(rollback()).await;
+            let second = storage.exec(rollback()).await;
+            let third = storage.exec(rollback()).await;
+
+            assert_eq!(
+                history
+                    .unwrap()
+                    .iter()
+                    .map(|e| e.target.to_string())
+                    .collect::<Vec<_>>(),
+                ["b/v3", "b/v2"],
+            );
+            assert_eq!(first.unwrap().to_string(), "b/v3");
+            assert_eq!(second.unwrap().to_string(), "b/v2");
+            assert_eq!(
+                third.unwrap_err().as_ref().kind(),
+                io::ErrorKind::NotFound,
+            );
+        });
+    }
+}
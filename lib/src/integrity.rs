@@ -0,0 +1,576 @@
+//! Integrity checks of stored files.
+//!
+//! Every created file has the SHA-256 checksum of its stored contents
+//! persisted, so that silent corruption of the contents is detected once they
+//! are re-read and checksummed again by a [`ScrubFiles`] operation.
+
+use std::{
+    fs::Metadata,
+    io,
+    os::unix::fs::MetadataExt as _,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use async_fs::File;
+use futures::AsyncReadExt as _;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tracerr::Traced;
+
+use crate::{
+    Exec, RelativePath, Storage, async_trait, list_files, remove_existing_file,
+    write_atomically,
+};
+
+/// Operation of re-reading the files of a bucket and verifying their contents
+/// against the persisted checksums.
+///
+/// The whole bucket may be scrubbed at the desired rate, by listing its files
+/// once via a [`ListFiles`] operation and scrubbing them in batches.
+///
+/// Files having no checksum (like symlinks or files created before checksums
+/// were persisted) are skipped, as well as the absent ones.
+///
+/// [`ListFiles`]: crate::ListFiles
+#[derive(Clone, Debug)]
+pub struct ScrubFiles {
+    /// [`RelativePath`] of the bucket to scrub the files of.
+    pub bucket: RelativePath,
+
+    /// Keys of the files to scrub.
+    pub keys: Vec<String>,
+
+    /// Indicator whether the corrupt files should be quarantined, so they
+    /// cannot be read anymore, until being overwritten or deleted.
+    pub quarantine: bool,
+}
+
+/// Outcome of a [`ScrubFiles`] operation.
+#[derive(Clone, Debug)]
+pub struct ScrubbedFiles {
+    /// Number of files whose contents have been verified.
+    pub checked: usize,
+
+    /// Files whose contents don't match their checksums.
+    pub corrupt: Vec<CorruptFile>,
+}
+
+/// File whose stored contents don't match its persisted checksum.
+#[derive(Clone, Debug)]
+pub struct CorruptFile {
+    /// [`RelativePath`] of the file.
+    pub path: RelativePath,
+
+    /// Hex-encoded SHA-256 checksum persisted when the file was created.
+    pub expected: String,
+
+    /// Hex-encoded SHA-256 checksum of the actually stored contents.
+    pub actual: String,
+
+    /// Time when the corruption was detected first.
+    pub detected_at: SystemTime,
+
+    /// Indicator whether the file is quarantined, so cannot be read.
+    pub quarantined: bool,
+}
+
+#[async_trait]
+impl Exec<ScrubFiles> for Storage {
+    type Ok = ScrubbedFiles;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: ScrubFiles) -> Result<Self::Ok, Self::Err> {
+        let mut scrubbed = ScrubbedFiles { checked: 0, corrupt: Vec::new() };
+        for key in op.keys {
+            let Ok(path) =
+                RelativePath::try_from(format!("{}/{key}", op.bucket))
+            else {
+                continue;
+            };
+            let file = match self.live_path(&path).await {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                    continue;
+                }
+                Err(e) => return Err(tracerr::new!(e)),
+            };
+            let meta = match async_fs::symlink_metadata(&file).await {
+                Ok(meta) if meta.is_file() => meta,
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(tracerr::new!(e)),
+            };
+            let Some(checksum) = self
+                .read_checksum(&path)
+                .await
+                .map_err(tracerr::wrap!())?
+                .filter(|c| c.describes(&meta))
+            else {
+                continue;
+            };
+            let known =
+                self.read_corruption(&path).await.map_err(tracerr::wrap!())?;
+            if known.as_ref().is_some_and(|c| c.quarantined) {
+                continue;
+            }
+
+            let actual = match checksum_of(&file).await {
+                Ok(digest) => hex::encode(digest),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(tracerr::new!(e)),
+            };
+
+            // The file may be overwritten while being checked, so the result
+            // is recorded only if it's still the checked one.
+            let _key = self.key_locks.lock(&file).await;
+            match async_fs::symlink_metadata(&file).await {
+                Ok(meta) if checksum.describes(&meta) => {}
+                Ok(_) => continue,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(tracerr::new!(e)),
+            }
+            scrubbed.checked += 1;
+            if actual == checksum.sha256 {
+                if known.is_some() {
+                    remove_existing_file(self.corruption_path(&path))
+                        .await
+                        .map_err(tracerr::wrap!())?;
+                }
+                continue;
+            }
+
+            let corrupt = Corruption {
+                path: path.to_string(),
+                expected: checksum.sha256,
+                actual,
+                detected_at: known
+                    .map_or_else(SystemTime::now, |c| c.detected_at),
+                quarantined: op.quarantine,
+            };
+            let bytes = serde_json::to_vec_pretty(&corrupt)
+                .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+            write_atomically(
+                &self.tmp_dir,
+                &self.corruption_path(&path),
+                &bytes,
+            )
+            .await
+            .map_err(tracerr::wrap!())?;
+            scrubbed.corrupt.push(corrupt.into_corrupt_file(path));
+        }
+        Ok(scrubbed)
+    }
+}
+
+/// Operation of listing the [`CorruptFile`]s of a bucket detected by
+/// [`ScrubFiles`] operations.
+#[derive(Clone, Debug)]
+pub struct ListCorruptFiles {
+    /// [`RelativePath`] of the bucket.
+    pub bucket: RelativePath,
+}
+
+#[async_trait]
+impl Exec<ListCorruptFiles> for Storage {
+    type Ok = Vec<CorruptFile>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: ListCorruptFiles) -> Result<Self::Ok, Self::Err> {
+        let dir = self.meta_dir.join("corrupt").join(&op.bucket);
+        let mut files = Vec::new();
+        for name in list_files(&dir).await.map_err(tracerr::wrap!())? {
+            let bytes = match async_fs::read(dir.join(name)).await {
+                Ok(bytes) => bytes,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(tracerr::new!(e)),
+            };
+            let corrupt = serde_json::from_slice::<Corruption>(&bytes)
+                .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+            if let Ok(path) = RelativePath::try_from(corrupt.path.clone()) {
+                files.push(corrupt.into_corrupt_file(path));
+            }
+        }
+        files.sort_by(|a, b| a.path.as_ref().cmp(b.path.as_ref()));
+        Ok(files)
+    }
+}
+
+/// Checksum of the stored contents of a file, persisted by [`Storage`].
+#[derive(Debug, Deserialize, Serialize)]
+struct Checksum {
+    /// [`RelativePath`] of the file.
+    path: String,
+
+    /// Hex-encoded SHA-256 digest of the stored contents.
+    sha256: String,
+
+    /// Inode number of the checksummed file.
+    ino: u64,
+
+    /// Size of the stored contents in bytes.
+    len: u64,
+
+    /// Last modification time of the checksummed file.
+    modified: SystemTime,
+
+    /// Time when the checksummed file was created.
+    ///
+    /// Deduplicated files share the [`Checksum::modified`] time of their
+    /// blob, so it cannot be used as the creation time.
+    created_at: SystemTime,
+}
+
+impl Checksum {
+    /// Checks whether this [`Checksum`] has been computed for the file with
+    /// the provided [`Metadata`], rather than for the one it has replaced.
+    fn describes(&self, meta: &Metadata) -> bool {
+        meta.ino() == self.ino
+            && meta.len() == self.len
+            && meta.modified().is_ok_and(|t| t == self.modified)
+    }
+}
+
+/// [`CorruptFile`] persisted by [`Storage`].
+#[derive(Debug, Deserialize, Serialize)]
+struct Corruption {
+    /// [`RelativePath`] of the file.
+    path: String,
+
+    /// Hex-encoded SHA-256 checksum persisted when the file was created.
+    expected: String,
+
+    /// Hex-encoded SHA-256 checksum of the actually stored contents.
+    actual: String,
+
+    /// Time when the corruption was detected first.
+    detected_at: SystemTime,
+
+    /// Indicator whether the file is quarantined, so cannot be read.
+    quarantined: bool,
+}
+
+impl Corruption {
+    /// Converts this [`Corruption`] into a [`CorruptFile`] with the provided
+    /// parsed [`RelativePath`].
+    fn into_corrupt_file(self, path: RelativePath) -> CorruptFile {
+        CorruptFile {
+            path,
+            expected: self.expected,
+            actual: self.actual,
+            detected_at: self.detected_at,
+            quarantined: self.quarantined,
+        }
+    }
+}
+
+impl Storage {
+    /// Persists the provided SHA-256 `digest` of the stored contents of the
+    /// file at the provided `path`, committed with the provided [`Metadata`].
+    ///
+    /// # Errors
+    ///
+    /// If the checksum fails to be written.
+    pub(crate) async fn persist_checksum(
+        &self,
+        path: &RelativePath,
+        meta: &Metadata,
+        digest: &[u8],
+    ) -> io::Result<()> {
+        let checksum = Checksum {
+            path: path.to_string(),
+            sha256: hex::encode(digest),
+            ino: meta.ino(),
+            len: meta.len(),
+            modified: meta.modified()?,
+            created_at: SystemTime::now(),
+        };
+        let bytes = serde_json::to_vec_pretty(&checksum)?;
+        write_atomically(&self.tmp_dir, &self.checksum_path(path), &bytes).await
+    }
+
+    /// Ensures the current file at the provided `path` is not quarantined.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::InvalidData`], if the file is quarantined.
+    /// - If the quarantine fails to be checked.
+    pub(crate) async fn ensure_not_quarantined(
+        &self,
+        path: &RelativePath,
+    ) -> Result<(), Traced<io::Error>> {
+        let corrupt =
+            self.read_corruption(path).await.map_err(tracerr::wrap!())?;
+        if corrupt.is_some_and(|c| c.quarantined) {
+            return Err(tracerr::new!(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("`{path}` is quarantined as corrupted"),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Forgets the detected corruption of the file at the provided `path`,
+    /// once it's overwritten or deleted.
+    ///
+    /// # Errors
+    ///
+    /// If the [`CorruptFile`] fails to be removed.
+    pub(crate) async fn forget_corruption(
+        &self,
+        path: &RelativePath,
+    ) -> Result<(), Traced<io::Error>> {
+        remove_existing_file(self.corruption_path(path))
+            .await
+            .map_err(tracerr::wrap!())
+    }
+
+    /// Returns the time when the file at the provided absolute `file` path
+    /// with the provided [`Metadata`] was created.
+    ///
+    /// Falls back to the modification time of the file, if it has no
+    /// [`Checksum`] describing it.
+    ///
+    /// # Errors
+    ///
+    /// If the `file` fails to be resolved, or its [`Checksum`] fails to be
+    /// read.
+    pub(crate) async fn created_at(
+        &self,
+        file: &Path,
+        meta: &Metadata,
+    ) -> io::Result<SystemTime> {
+        let path = match self.relative_path(file) {
+            Some(path) => self.relative_path(&self.live_path(&path).await?),
+            None => None,
+        };
+        let checksum = match path {
+            Some(path) => self.read_checksum(&path).await?,
+            None => None,
+        };
+        Ok(match checksum.filter(|c| c.describes(meta)) {
+            Some(c) => c.created_at,
+            None => meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        })
+    }
+
+    /// Reads the persisted [`Checksum`] of the file at the provided `path`, if
+    /// any.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Checksum`] fails to be read or deserialized.
+    async fn read_checksum(
+        &self,
+        path: &RelativePath,
+    ) -> io::Result<Option<Checksum>> {
+        match async_fs::read(self.checksum_path(path)).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Reads the persisted [`Corruption`] of the file at the provided `path`,
+    /// if any.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Corruption`] fails to be read or deserialized.
+    async fn read_corruption(
+        &self,
+        path: &RelativePath,
+    ) -> io::Result<Option<Corruption>> {
+        match async_fs::read(self.corruption_path(path)).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the [`PathBuf`] where the [`Checksum`] of the file at the
+    /// provided `path` is stored.
+    fn checksum_path(&self, path: &RelativePath) -> PathBuf {
+        self.meta_dir.join("checksums").join(path.bucket()).join(format!(
+            "{}.json",
+            hex::encode(Sha256::digest(path.to_string())),
+        ))
+    }
+
+    /// Returns the [`PathBuf`] where the [`Corruption`] of the file at the
+    /// provided `path` is stored.
+    fn corruption_path(&self, path: &RelativePath) -> PathBuf {
+        self.meta_dir.join("corrupt").join(path.bucket()).join(format!(
+            "{}.json",
+            hex::encode(Sha256::digest(path.to_string())),
+        ))
+    }
+}
+
+/// Computes the SHA-256 digest of the contents of the file at the provided
+/// `path`.
+///
+/// # Errors
+///
+/// If the file fails to be read.
+async fn checksum_of(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(hasher.finalize().to_vec());
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+#[cfg(test)]
+mod scrub_spec {
+    use std::{fs, io, io::Write as _, path::Path};
+
+    use futures::executor::block_on;
+
+    use crate::{
+        Exec as _, GetFile, ListCorruptFiles, ListFiles, Quota, ScrubFiles,
+        SetBucketConfig, Storage,
+        testing::{TempDir, create_file, path},
+    };
+
+    /// Corrupts the contents of the file at the provided `path` silently,
+    /// preserving its modification time.
+    fn corrupt(path: &Path) {
+        let mut file = fs::OpenOptions::new().write(true).open(path).unwrap();
+        let modified = file.metadata().unwrap().modified().unwrap();
+        file.write_all(b"bad!").unwrap();
+        file.set_modified(modified).unwrap();
+    }
+
+    fn scrub(keys: &[&str], quarantine: bool) -> ScrubFiles {
+        ScrubFiles {
+            bucket: path("b"),
+            keys: keys.iter().map(ToString::to_string).collect(),
+            quarantine,
+        }
+    }
+
+    #[test]
+    fn detects_corrupt_files_in_batches() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            for key in ["b/1", "b/2", "b/3"] {
+                storage.exec(create_file(key, b"data")).await.unwrap();
+            }
+            corrupt(&root.join("data/b/3"));
+
+            let keys = storage.exec(ListFiles { bucket: path("b") }).await;
+            let first = storage.exec(scrub(&["1", "2"], false)).await.unwrap();
+            let second = storage.exec(scrub(&["3", "4"], false)).await.unwrap();
+            let listed =
+                storage.exec(ListCorruptFiles { bucket: path("b") }).await;
+
+            assert_eq!(keys.unwrap(), ["1", "2", "3"]);
+            assert_eq!(first.checked, 2);
+            assert!(first.corrupt.is_empty(), "{:?}", first.corrupt);
+            assert_eq!(second.checked, 1);
+            assert_eq!(
+                second
+                    .corrupt
+                    .iter()
+                    .map(|c| c.path.to_string())
+                    .collect::<Vec<_>>(),
+                ["b/3"],
+            );
+            assert_eq!(listed.unwrap().len(), 1);
+        });
+    }
+
+    #[test]
+    fn quarantines_corrupt_files_until_overwritten() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            storage.exec(create_file("b/1", b"data")).await.unwrap();
+            corrupt(&root.join("data/b/1"));
+            let get = || GetFile {
+                path: path("b/1"),
+                version_id: None,
+                encryption_key: None,
+            };
+
+            let scrubbed = storage.exec(scrub(&["1"], true)).await.unwrap();
+            let quarantined = storage.exec(get()).await.map(drop);
+            storage.exec(create_file("b/1", b"data")).await.unwrap();
+            let overwritten = storage.exec(get()).await.map(drop);
+            let listed =
+                storage.exec(ListCorruptFiles { bucket: path("b") }).await;
+
+            assert!(scrubbed.corrupt[0].quarantined, "Not quarantined");
+            assert_eq!(
+                quarantined.unwrap_err().as_ref().kind(),
+                io::ErrorKind::InvalidData,
+            );
+            assert!(overwritten.is_ok(), "{overwritten:?}");
+            assert!(listed.unwrap().is_empty(), "Corruption is not forgotten");
+        });
+    }
+
+    #[test]
+    fn keeps_scrubbing_files_after_rejected_overwrite() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            storage.exec(create_file("b/1", b"data")).await.unwrap();
+            storage
+                .exec(SetBucketConfig {
+                    bucket: path("b"),
+                    config: Some(Quota {
+                        max_bytes: Some(4),
+                        max_objects: None,
+                    }),
+                })
+                .await
+                .unwrap();
+
+            let rejected = storage.exec(create_file("b/1", b"more data")).await;
+            corrupt(&root.join("data/b/1"));
+            let scrubbed = storage.exec(scrub(&["1"], false)).await.unwrap();
+
+            assert_eq!(
+                rejected.unwrap_err().as_ref().kind(),
+                io::ErrorKind::QuotaExceeded,
+            );
+            assert_eq!(
+                scrubbed
+                    .corrupt
+                    .iter()
+                    .map(|c| c.path.to_string())
+                    .collect::<Vec<_>>(),
+                ["b/1"],
+            );
+        });
+    }
+
+    #[test]
+    fn does_not_list_files_outside_data_dir() {
+        block_on(async {
+            let (root, outside) = (TempDir::new(), TempDir::new());
+            let storage = Storage::new(&*root).await.unwrap();
+            fs::write(outside.join("file"), b"data").unwrap();
+            std::os::unix::fs::symlink(&*outside, root.join("data/b")).unwrap();
+
+            let listed = storage.exec(ListFiles { bucket: path("b") }).await;
+
+            assert_eq!(
+                listed.unwrap_err().as_ref().kind(),
+                io::ErrorKind::PermissionDenied,
+            );
+        });
+    }
+}
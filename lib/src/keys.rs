@@ -0,0 +1,181 @@
+//! Serialization of mutations to the same keys.
+//!
+//! Concurrent mutations of the same key (like two uploads, or an upload racing
+//! a symlink creation) are serialized via [`KeyLocks`], so each of them
+//! commits all its effects (the file, its checksum, its version, etc.) before
+//! the next one starts, and the last one to lock the key wins. Mutations of
+//! unrelated keys don't wait for each other.
+//!
+//! Keys are locked by their live paths, so the same file written via different
+//! keys (like through a directory alias) is locked as a single one.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    io,
+    path::{Path, PathBuf},
+    sync::{self, Arc, PoisonError},
+};
+
+use futures::lock::{Mutex, OwnedMutexGuard};
+use tracerr::Traced;
+
+use crate::{RelativePath, Storage};
+
+impl Storage {
+    /// Locks the key of the file at the provided `path` by its live path,
+    /// waiting until it's unlocked by other holders.
+    ///
+    /// # Errors
+    ///
+    /// If the live path of the file fails to be resolved.
+    pub(crate) async fn lock_key(
+        &self,
+        path: &RelativePath,
+    ) -> Result<KeyGuard<'_>, Traced<io::Error>> {
+        let live = self.live_path(path).await.map_err(tracerr::wrap!())?;
+        Ok(self.key_locks.lock(&live).await)
+    }
+}
+
+/// Locks of the keys being mutated at the moment.
+///
+/// A lock exists only while being held or awaited, so the number of locks
+/// doesn't grow with the number of keys ever mutated.
+#[derive(Debug, Default)]
+pub(crate) struct KeyLocks {
+    /// Locks of the keys, by their [`Path`]s.
+    locks: sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>,
+}
+
+impl KeyLocks {
+    /// Locks the provided key, waiting until it's unlocked by other holders.
+    pub(crate) async fn lock(&self, key: &Path) -> KeyGuard<'_> {
+        let key = key.to_owned();
+        let lock = Arc::clone(
+            self.locks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(key.clone())
+                .or_default(),
+        );
+        let held = Arc::clone(&lock).lock_owned().await;
+        KeyGuard { locks: self, key, lock: Some((lock, held)) }
+    }
+
+    /// Locks all the provided keys, waiting until they're unlocked by other
+    /// holders.
+    ///
+    /// Keys are locked in their order, so holders of intersecting sets of
+    /// keys don't deadlock each other.
+    pub(crate) async fn lock_all(
+        &self,
+        keys: impl IntoIterator<Item = PathBuf>,
+    ) -> Vec<KeyGuard<'_>> {
+        let mut guards = Vec::new();
+        for key in keys.into_iter().collect::<BTreeSet<_>>() {
+            guards.push(self.lock(&key).await);
+        }
+        guards
+    }
+}
+
+/// Guard of a key locked by [`KeyLocks`], unlocking it once dropped.
+#[derive(Debug)]
+pub(crate) struct KeyGuard<'l> {
+    /// [`KeyLocks`] the key is locked in.
+    locks: &'l KeyLocks,
+
+    /// Locked key.
+    key: PathBuf,
+
+    /// Lock of the key along with its held guard.
+    lock: Option<(Arc<Mutex<()>>, OwnedMutexGuard<()>)>,
+}
+
+impl Drop for KeyGuard<'_> {
+    fn drop(&mut self) {
+        let mut locks =
+            self.locks.locks.lock().unwrap_or_else(PoisonError::into_inner);
+        drop(self.lock.take());
+        // Locks are cloned out of the map only while it's locked, so no one
+        // else holds or awaits the lock, if only the map refers to it.
+        if locks.get(&self.key).is_some_and(|l| Arc::strong_count(l) == 1) {
+            drop(locks.remove(&self.key));
+        }
+    }
+}
+
+#[cfg(test)]
+mod key_locks_spec {
+    use std::{path::Path, pin::pin};
+
+    use futures::{FutureExt as _, executor::block_on, poll};
+
+    use super::KeyLocks;
+    use crate::{
+        Storage,
+        testing::{TempDir, path},
+    };
+
+    #[test]
+    fn serializes_same_keys_only() {
+        block_on(async {
+            let locks = KeyLocks::default();
+
+            let held = locks.lock(Path::new("/b/key")).await;
+            let mut same = pin!(locks.lock(Path::new("/b/key")));
+            assert!(poll!(&mut same).is_pending(), "Same key is not locked");
+            assert!(
+                locks.lock(Path::new("/b/other")).now_or_never().is_some(),
+                "Other key is locked",
+            );
+
+            drop(held);
+            assert!(poll!(&mut same).is_ready(), "Same key is not unlocked");
+            assert!(
+                locks.locks.lock().unwrap().is_empty(),
+                "Unheld locks are kept",
+            );
+        });
+    }
+
+    #[test]
+    fn locks_all_keys_in_order() {
+        block_on(async {
+            let locks = KeyLocks::default();
+
+            let held = locks.lock_all(["/b/2", "/b/1"].map(Into::into)).await;
+            let mut reversed =
+                pin!(locks.lock_all(["/b/3", "/b/2"].map(Into::into)));
+            assert!(poll!(&mut reversed).is_pending(), "Key is not locked");
+            assert!(
+                locks.lock(Path::new("/b/3")).now_or_never().is_some(),
+                "Key is locked while waiting for the previous one",
+            );
+
+            drop(held);
+            assert!(poll!(&mut reversed).is_ready(), "Keys are not unlocked");
+        });
+    }
+
+    #[test]
+    fn locks_keys_by_live_paths() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            let data = storage.data_dir.clone();
+            async_fs::create_dir_all(data.join("a/dir")).await.unwrap();
+            async_fs::unix::symlink("a/dir", data.join("b")).await.unwrap();
+
+            let _held = storage.lock_key(&path("b/key")).await.unwrap();
+            assert!(
+                storage
+                    .key_locks
+                    .lock(&data.join("a/dir/key"))
+                    .now_or_never()
+                    .is_none(),
+                "Key is not locked through alias",
+            );
+        });
+    }
+}
@@ -1,18 +1,80 @@
+mod alias;
+mod bucket;
+mod compression;
+mod confinement;
+mod dangling;
+mod dedup;
+mod encryption;
+mod history;
+mod integrity;
+mod keys;
+mod lifecycle;
+mod links;
+mod object_lock;
+mod publish;
+mod quota;
+mod space;
+#[cfg(test)]
+mod testing;
+mod tmp;
+mod versioning;
+
 use std::{
-    fmt, io,
-    path::{Path, PathBuf},
+    borrow::Cow,
+    fmt,
+    fs::Metadata,
+    io, iter,
+    path::{Component, Path, PathBuf},
     pin::Pin,
+    sync::{Arc, atomic::AtomicBool},
     task,
+    time::SystemTime,
 };
 
 use async_fs::File;
 pub use async_trait::async_trait;
 use derive_more::with_trait::{Display, Error};
 pub use futures;
-use futures::{AsyncRead, AsyncWriteExt as _, Stream, StreamExt as _, pin_mut};
+use futures::{
+    AsyncRead, AsyncReadExt as _, AsyncSeek, AsyncWriteExt as _, Stream,
+    StreamExt as _, lock::Mutex, pin_mut,
+};
+use sha2::{Digest as _, Sha256};
 use tracerr::Traced;
 use uuid::Uuid;
 
+pub use self::{
+    bucket::{
+        BucketConfig, GetBucketConfig, ListBuckets, ListFiles, SetBucketConfig,
+    },
+    compression::Compression,
+    dangling::{DanglingSymlink, SweepDanglingSymlinks},
+    dedup::CollectBlobs,
+    encryption::{Encryption, EncryptionKey, InvalidEncryptionKeyError},
+    history::{GetSymlinkHistory, RollbackSymlink, SymlinkHistoryEntry},
+    integrity::{CorruptFile, ListCorruptFiles, ScrubFiles, ScrubbedFiles},
+    lifecycle::{Lifecycle, LifecycleRule},
+    links::ListSymlinks,
+    object_lock::{
+        DefaultRetention, GetObjectLock, ObjectLock, ObjectLockStatus,
+        Retention, RetentionMode, SetObjectLegalHold, SetObjectRetention,
+    },
+    publish::PublishSymlinks,
+    quota::{GetBucketUsage, GetQuotaAllowance, Quota, Usage},
+    space::{FreeSpace, GetFreeSpace},
+    tmp::CleanTmpFiles,
+    versioning::{
+        DeleteFile, DeletedFile, FileVersion, InvalidVersionIdError,
+        ListVersions, VersionId, Versioning,
+    },
+};
+use self::{
+    compression::{Compressor, Decompressor},
+    encryption::Decryptor,
+    keys::KeyLocks,
+    versioning::symlink_target,
+};
+
 /// Execution of a filesystem operation.
 #[async_trait]
 pub trait Exec<Operation> {
@@ -27,6 +89,9 @@ pub trait Exec<Operation> {
 #[derive(Clone, Debug)]
 pub struct Storage {
     /// Absolute [`Path`] to the directory to persist data in.
+    ///
+    /// All the filesystem access to data is confined beneath it, so no
+    /// symlink can make an operation escape it.
     data_dir: PathBuf,
 
     /// Absolute [`Path`] to the directory to use for storing temporary data.
@@ -36,11 +101,87 @@ pub struct Storage {
     /// atomicity of [copy](async_fs::copy) and [rename](async_fs::rename)
     /// operations, which is vital for how this directory is used.
     ///
+    /// It's unique for every [`Storage`] created, so temporary files of other
+    /// processes sharing the same root directory are never touched.
+    ///
     /// [0]: https://en.wikipedia.org/wiki/Temporary_folder
     tmp_dir: PathBuf,
+
+    /// Absolute [`Path`] to the directory to persist metadata in (like
+    /// [`BucketConfig`]s).
+    meta_dir: PathBuf,
+
+    /// Absolute [`Path`] to the directory to persist deduplicated contents of
+    /// files in, as blobs named after their SHA-256 digests.
+    ///
+    /// Must be located on the same filesystem as the data directory, because
+    /// files are [hard links](async_fs::hard_link) to these blobs.
+    blobs_dir: PathBuf,
+
+    /// Lock serializing creation of new references to blobs with their
+    /// removal, so that no blob is removed while being referenced.
+    blobs_lock: Arc<Mutex<()>>,
+
+    /// Locks serializing [`Usage`] updates of the same buckets, so that
+    /// concurrent operations don't exceed their [`Quota`]s.
+    usage_locks: Arc<KeyLocks>,
+
+    /// Locks serializing mutations of the same keys, so that concurrent
+    /// operations on a key don't interleave their effects.
+    key_locks: Arc<KeyLocks>,
+
+    /// Locks serializing updates of the same entries of the reverse index of
+    /// symlinks, so that concurrent operations don't lose symlinks pointing
+    /// to the same target.
+    links_locks: Arc<KeyLocks>,
+
+    /// Master [`EncryptionKey`] for [`Encryption::Managed`] (if configured).
+    master_key: Option<EncryptionKey>,
+
+    /// Maximum number of symlinks a new symlink may be resolved through
+    /// (including itself).
+    max_symlink_hops: usize,
+
+    /// Indicator whether files having symlinks pointing to them are protected
+    /// from being overwritten or deleted.
+    protect_symlink_targets: bool,
+
+    /// Maximum number of previous targets kept in the history of every
+    /// symlink.
+    symlink_history_len: usize,
+
+    /// Indicator whether files are allowed to be written through directory
+    /// aliases.
+    alias_writes: bool,
+
+    /// Indicator whether contents of created files are deduplicated.
+    deduplicate: bool,
+
+    /// Number of bytes of free space kept in reserve.
+    free_space_reserve: u64,
+
+    /// Number of bytes of free space below which this [`Storage`] becomes
+    /// read-only.
+    low_watermark: u64,
+
+    /// Number of bytes of free space above which this [`Storage`] becomes
+    /// writable again.
+    high_watermark: u64,
+
+    /// Indicator whether this [`Storage`] is read-only, because of low free
+    /// space.
+    read_only: Arc<AtomicBool>,
 }
 
 impl Storage {
+    /// Default maximum number of symlinks a new symlink may be resolved
+    /// through (including itself).
+    pub const DEFAULT_MAX_SYMLINK_HOPS: usize = 8;
+
+    /// Default maximum number of previous targets kept in the history of
+    /// every symlink.
+    pub const DEFAULT_SYMLINK_HISTORY_LEN: usize = 10;
+
     /// Creates a new [`Storage`].
     ///
     /// If the provided `root` directory doesn't exist yet, tries to create it
@@ -58,10 +199,15 @@ impl Storage {
         let data = root.join("data");
         async_fs::create_dir_all(&data).await.map_err(tracerr::wrap!())?;
 
-        let tmp = root.join("tmp");
-        remove_existing_dir(&tmp).await.map_err(tracerr::wrap!())?;
+        let tmp = root.join("tmp").join(Uuid::new_v4().to_string());
         async_fs::create_dir_all(&tmp).await.map_err(tracerr::wrap!())?;
 
+        let meta = root.join("meta");
+        async_fs::create_dir_all(&meta).await.map_err(tracerr::wrap!())?;
+
+        let blobs = root.join("blobs");
+        async_fs::create_dir_all(&blobs).await.map_err(tracerr::wrap!())?;
+
         Ok(Self {
             data_dir: async_fs::canonicalize(data)
                 .await
@@ -69,8 +215,116 @@ impl Storage {
             tmp_dir: async_fs::canonicalize(tmp)
                 .await
                 .map_err(tracerr::wrap!())?,
+            meta_dir: async_fs::canonicalize(meta)
+                .await
+                .map_err(tracerr::wrap!())?,
+            blobs_dir: async_fs::canonicalize(blobs)
+                .await
+                .map_err(tracerr::wrap!())?,
+            blobs_lock: Arc::default(),
+            usage_locks: Arc::default(),
+            key_locks: Arc::default(),
+            links_locks: Arc::default(),
+            master_key: None,
+            max_symlink_hops: Self::DEFAULT_MAX_SYMLINK_HOPS,
+            protect_symlink_targets: false,
+            symlink_history_len: Self::DEFAULT_SYMLINK_HISTORY_LEN,
+            alias_writes: false,
+            deduplicate: false,
+            free_space_reserve: 0,
+            low_watermark: 0,
+            high_watermark: 0,
+            read_only: Arc::default(),
         })
     }
+
+    /// Sets the maximum number of symlinks a new symlink may be resolved
+    /// through (including itself).
+    ///
+    /// [`Storage::DEFAULT_MAX_SYMLINK_HOPS`] is used by default.
+    #[must_use]
+    pub const fn with_max_symlink_hops(mut self, hops: usize) -> Self {
+        self.max_symlink_hops = hops;
+        self
+    }
+
+    /// Returns the [`RelativePath`] of the provided absolute `path` inside the
+    /// data directory of this [`Storage`], if it's located there.
+    pub(crate) fn relative_path(&self, path: &Path) -> Option<RelativePath> {
+        let path = path.strip_prefix(&self.data_dir).ok()?;
+        path.components()
+            .all(|c| matches!(c, Component::Normal(_)))
+            .then(|| RelativePath(path.to_owned()))
+            .filter(|p| !p.0.as_os_str().is_empty())
+    }
+
+    /// Ensures the provided `src` path resolves to an existing file (or an
+    /// existing `directory`, if requested), when being pointed to by a new
+    /// symlink at the provided `link` path.
+    ///
+    /// Both paths are expected to be absolute.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::NotFound`], if the `src` doesn't exist or is a
+    ///   dangling symlink.
+    /// - With [`io::ErrorKind::IsADirectory`], if the `src` resolves to a
+    ///   directory, while a file is expected.
+    /// - With [`io::ErrorKind::NotADirectory`], if the `src` resolves to a
+    ///   file, while a `directory` is expected.
+    /// - With [`io::ErrorKind::TooManyLinks`], if the `src` resolves back to
+    ///   the `link`, or through more than [`Storage::max_symlink_hops`].
+    /// - If any of the symlinks fails to be read.
+    async fn ensure_resolvable(
+        &self,
+        link: &Path,
+        src: &Path,
+        directory: bool,
+    ) -> io::Result<()> {
+        let mut path = src.to_owned();
+        for _ in 0..self.max_symlink_hops {
+            if path == link {
+                return Err(io::Error::new(
+                    io::ErrorKind::TooManyLinks,
+                    "Symlink target resolves back to the symlink itself",
+                ));
+            }
+            let meta = match async_fs::symlink_metadata(&path).await {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "Symlink target doesn't exist",
+                    ));
+                }
+                Err(e) => return Err(e),
+            };
+            if meta.is_dir() != directory && !meta.is_symlink() {
+                return Err(if directory {
+                    io::Error::new(
+                        io::ErrorKind::NotADirectory,
+                        "Symlink target is not a directory",
+                    )
+                } else {
+                    io::Error::new(
+                        io::ErrorKind::IsADirectory,
+                        "Symlink target is a directory",
+                    )
+                });
+            }
+            let Some(next) = symlink_target(&path, &path).await? else {
+                return Ok(());
+            };
+            path = self.resolve_beneath(&next, false).await?;
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TooManyLinks,
+            format!(
+                "Symlink target resolves through more than {} symlinks",
+                self.max_symlink_hops,
+            ),
+        ))
+    }
 }
 
 /// Removes the existing `dir`ectory.
@@ -89,6 +343,124 @@ async fn remove_existing_dir(dir: impl AsRef<Path>) -> io::Result<()> {
     })
 }
 
+/// Removes the existing file (or symlink) at the provided `path`.
+///
+/// # Idempotent
+///
+/// Succeeds if the file doesn't exist already.
+///
+/// # Errors
+///
+/// If [`async_fs::remove_file()`] errors with anything other than
+/// [`io::ErrorKind::NotFound`].
+async fn remove_existing_file(path: impl AsRef<Path>) -> io::Result<()> {
+    async_fs::remove_file(path).await.or_else(|e| {
+        (e.kind() == io::ErrorKind::NotFound).then_some(()).ok_or(e)
+    })
+}
+
+/// Lists paths of all the files (and symlinks) inside the provided `dir`ectory
+/// recursively, relative to it.
+///
+/// Symlinks are not followed. Returns an empty list if the `dir`ectory doesn't
+/// exist.
+///
+/// # Errors
+///
+/// If any of the directories fails to be read.
+async fn list_files(dir: &Path) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![(dir.to_owned(), String::new())];
+    while let Some((dir, prefix)) = dirs.pop() {
+        let mut entries = match async_fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let name =
+                format!("{prefix}{}", entry.file_name().to_string_lossy());
+            if entry.file_type().await?.is_dir() {
+                dirs.push((entry.path(), format!("{name}/")));
+            } else {
+                files.push(name);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Writes the provided `bytes` into the file at the provided `path`
+/// atomically, via a temporary file in the provided `tmp_dir`.
+///
+/// Creates all the missing parent directories of the `path`.
+///
+/// # Errors
+///
+/// If any of filesystem operations fails.
+async fn write_atomically(
+    tmp_dir: &Path,
+    path: &Path,
+    bytes: &[u8],
+) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        async_fs::create_dir_all(dir).await?;
+    }
+
+    let tmp = tmp_dir.join(Uuid::new_v4().to_string());
+    if let Err(e) = async_fs::write(&tmp, bytes).await {
+        _ = async_fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    async_fs::rename(&tmp, path).await
+}
+
+/// Returns the target of a symlink at the provided `link` path pointing to the
+/// provided `target` path, relative to the directory of the `link`.
+///
+/// Both paths are expected to be absolute and normalized.
+fn relative_target(link: &Path, target: &Path) -> PathBuf {
+    let base = link.parent().unwrap_or(link).components().collect::<Vec<_>>();
+    let target = target.components().collect::<Vec<_>>();
+    let common = base.iter().zip(&target).take_while(|(a, b)| a == b).count();
+
+    iter::repeat_n(Component::ParentDir, base.len() - common)
+        .chain(target[common..].iter().copied())
+        .collect()
+}
+
+/// Returns the size of the contents of the file at the provided `path`,
+/// following symlinks.
+///
+/// For encrypted or compressed files, this is the size of their decrypted or
+/// decompressed contents.
+///
+/// # Errors
+///
+/// If the file fails to be read.
+pub(crate) async fn stored_content_size(path: &Path) -> io::Result<u64> {
+    let mut file = File::open(path).await?;
+    let len = file.metadata().await?.len();
+
+    let mut magic = Vec::with_capacity(encryption::MAGIC.len());
+    _ = (&mut file)
+        .take(encryption::MAGIC.len() as u64)
+        .read_to_end(&mut magic)
+        .await?;
+    if magic == encryption::MAGIC {
+        return encryption::content_size(len).map(|(size, _)| size).ok_or_else(
+            || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Encrypted file has invalid size",
+                )
+            },
+        );
+    }
+    Ok(compression::decompressed_size(&mut file).await?.unwrap_or(len))
+}
+
 /// Operation of a new file creation.
 #[derive(Clone, Debug)]
 pub struct CreateFile<Bytes> {
@@ -97,6 +469,16 @@ pub struct CreateFile<Bytes> {
 
     /// [`Stream`] of file bytes.
     pub bytes: Bytes,
+
+    /// [`Encryption`] to store the file with.
+    ///
+    /// If [`None`], then the file is stored as plaintext.
+    pub encryption: Option<Encryption>,
+
+    /// Content type of the file (if known).
+    ///
+    /// Used to skip [`Compression`] of already compressed contents.
+    pub content_type: Option<String>,
 }
 
 #[async_trait]
@@ -105,27 +487,133 @@ where
     S: Stream<Item = Result<Bytes, io::Error>> + fmt::Debug + Send + 'static,
     Bytes: AsRef<[u8]> + Send + Sync,
 {
-    type Ok = ();
+    type Ok = Option<VersionId>;
     type Err = Traced<io::Error>;
 
     #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
     #[tracing::instrument(level = "debug", err(Debug))]
     async fn exec(&self, op: CreateFile<S>) -> Result<Self::Ok, Self::Err> {
-        let path = self.data_dir.join(op.path);
-        if let Some(dir) = path.parent() {
-            async_fs::create_dir_all(dir).await.map_err(tracerr::wrap!())?;
+        self.ensure_writable()?;
+        let mut encryptor = op
+            .encryption
+            .as_ref()
+            .map(|e| self.encryptor(e))
+            .transpose()
+            .map_err(tracerr::wrap!())?;
+
+        // Encrypted contents are not compressed, so their size can always be
+        // reported without decrypting them.
+        let compression = if encryptor.is_none()
+            && op
+                .content_type
+                .as_deref()
+                .is_none_or(Compression::is_compressible)
+        {
+            self.exec(GetBucketConfig::<Compression>::new(op.path.bucket()))
+                .await
+                .map_err(tracerr::wrap!())?
+        } else {
+            None
+        };
+        let mut compressor = compression
+            .map(Compressor::new)
+            .transpose()
+            .map_err(tracerr::wrap!())?;
+
+        let mut hasher = Sha256::new();
+
+        // Uploads exceeding the `Quota` are aborted while being written, so
+        // they don't fill the disk before being rejected on commit.
+        let resolved = self.resolve_alias(&op.path).await?;
+        let allowance = self.quota_allowance(&resolved).await?;
+
+        // We don't want a failed or aborted upload to leave a partially
+        // written file behind, so it's required to do this in 2 steps:
+        // 1. write all the bytes into a temporary file;
+        // 2. replace the original file with the temporary one.
+        let tmp = self.tmp_dir.join(Uuid::new_v4().to_string());
+        let res = async {
+            let mut f = File::create(&tmp).await?;
+            let mut written = 0_u64;
+            let mut write = async |bytes: &[u8]| {
+                written = written.saturating_add(bytes.len() as u64);
+                if allowance.is_some_and(|max| written > max) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::QuotaExceeded,
+                        format!(
+                            "Quota of `{}` bucket is exceeded",
+                            resolved.bucket(),
+                        ),
+                    ));
+                }
+                hasher.update(bytes);
+                f.write_all(bytes).await
+            };
+
+            let bytes = op.bytes;
+            pin_mut!(bytes);
+            while let Some(res) = bytes.next().await {
+                let bytes = res?;
+                if let Some(enc) = encryptor.as_mut() {
+                    write(&enc.update(bytes.as_ref())?).await?;
+                } else if let Some(c) = compressor.as_mut() {
+                    write(&c.update(bytes.as_ref())?).await?;
+                } else {
+                    write(bytes.as_ref()).await?;
+                }
+            }
+            if let Some(enc) = encryptor {
+                write(&enc.finish()?).await?;
+            }
+            if let Some(c) = compressor {
+                write(&c.finish()?).await?;
+            }
+            f.flush().await?;
+            f.sync_all().await
+        }
+        .await;
+        if let Err(e) = res {
+            _ = async_fs::remove_file(&tmp).await;
+            return Err(tracerr::new!(e));
         }
 
-        let mut f = File::create(path).await.map_err(tracerr::wrap!())?;
+        // Uploads are written concurrently, while committed one by one, so the
+        // last committed one wins along with its checksum.
+        let _key = match self.lock_key(&op.path).await {
+            Ok(key) => key,
+            Err(e) => {
+                _ = async_fs::remove_file(&tmp).await;
+                return Err(e);
+            }
+        };
 
-        let bytes = op.bytes;
-        pin_mut!(bytes);
-        while let Some(res) = bytes.next().await {
-            f.write_all(res.map_err(tracerr::wrap!())?.as_ref())
-                .await
-                .map_err(tracerr::wrap!())?;
+        // Encrypted contents are unique anyway, so are not deduplicated.
+        let digest = hasher.finalize();
+        let res = async {
+            if self.deduplicate && op.encryption.is_none() {
+                self.deduplicate(&tmp, &digest).await?;
+            }
+            let meta =
+                async_fs::metadata(&tmp).await.map_err(tracerr::wrap!())?;
+            Ok((self.commit(&tmp, &op.path, None, false).await?, meta))
         }
-        f.flush().await.map_err(tracerr::wrap!())
+        .await;
+        let (version, meta) = match res {
+            Ok(ok) => ok,
+            Err(e) => {
+                _ = async_fs::remove_file(&tmp).await;
+                return Err(e);
+            }
+        };
+
+        // Checksum is persisted only once the file is committed, so a rejected
+        // upload doesn't replace the checksum of the existing file. Renaming
+        // keeps the `meta` of the committed file intact.
+        let path = self.resolve_alias(&op.path).await?;
+        self.persist_checksum(&path, &meta, &digest)
+            .await
+            .map_err(tracerr::wrap!())?;
+        Ok(version)
     }
 }
 
@@ -137,43 +625,154 @@ pub struct CreateSymlink {
 
     /// [`RelativePath`] of the symlink itself.
     pub dest: RelativePath,
+
+    /// [`RelativePath`] of the original source file the existing symlink is
+    /// expected to point to.
+    ///
+    /// If provided, the symlink is replaced only if it still points there,
+    /// allowing to repoint it without overwriting concurrent changes.
+    pub expected_src: Option<RelativePath>,
+
+    /// Indicator whether the symlink is allowed to be dangling.
+    ///
+    /// If `false`, the `src` must resolve to an existing file, through no more
+    /// symlinks than the [`Storage`] allows.
+    pub allow_dangling: bool,
+
+    /// Indicator whether the symlink aliases a whole directory (so all the
+    /// keys prefixed with it), rather than a single file.
+    ///
+    /// If `true`, the `src` must resolve to an existing directory instead.
+    /// Files are read through such an alias transparently, while writing them
+    /// is allowed only if the [`Storage`] allows writes through aliases.
+    pub directory: bool,
 }
 
 #[async_trait]
 impl Exec<CreateSymlink> for Storage {
-    type Ok = ();
+    type Ok = Option<VersionId>;
     type Err = Traced<io::Error>;
 
     #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
     #[tracing::instrument(level = "debug", err(Debug))]
     async fn exec(&self, op: CreateSymlink) -> Result<Self::Ok, Self::Err> {
-        let dest = self.data_dir.join(op.dest);
-        if let Some(dir) = dest.parent() {
-            async_fs::create_dir_all(dir).await.map_err(tracerr::wrap!())?;
+        let _key = self.lock_key(&op.dest).await?;
+        let dest = self.live_path(&op.dest).await.map_err(tracerr::wrap!())?;
+        let src = self.live_path(&op.src).await.map_err(tracerr::wrap!())?;
+        if !op.allow_dangling {
+            self.ensure_resolvable(&dest, &src, op.directory)
+                .await
+                .map_err(tracerr::wrap!())?;
         }
-        let src = self.data_dir.join(op.src);
 
-        match async_fs::unix::symlink(&src, &dest).await {
-            Ok(_) => return Ok(()),
-            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
-            Err(e) => return Err(tracerr::new!(e)),
-        }
+        // Relative targets keep symlinks valid wherever the data directory is
+        // mounted.
+        let src = relative_target(&dest, &src);
 
-        // We want symlinks to be overwritten atomically, so it's required to
-        // do this in 2 steps:
+        // We want symlinks to be overwritten atomically (and the replaced ones
+        // to be kept as versions), so it's required to do this in 2 steps:
         // 1. create temporary symlink file;
         // 2. replace the original file with the temporary one.
         let tmp = self.tmp_dir.join(Uuid::new_v4().to_string());
         async_fs::unix::symlink(src, &tmp).await.map_err(tracerr::wrap!())?;
-        async_fs::rename(&tmp, dest).await.map_err(tracerr::wrap!())
+
+        let res =
+            self.commit(&tmp, &op.dest, op.expected_src.as_ref(), false).await;
+        if res.is_err() {
+            _ = async_fs::remove_file(&tmp).await;
+        }
+        res
+    }
+}
+
+/// Operation of a hard link creation.
+///
+/// Unlike a [`CreateSymlink`], the created file is indistinguishable from the
+/// original source one, and remains intact once the source file is replaced
+/// or deleted.
+#[derive(Debug, Clone)]
+pub struct CreateHardLink {
+    /// [`RelativePath`] of the original source file.
+    ///
+    /// Symlinks are followed, so the hard link refers to the resolved file.
+    pub src: RelativePath,
+
+    /// [`RelativePath`] of the hard link itself.
+    pub dest: RelativePath,
+}
+
+#[async_trait]
+impl Exec<CreateHardLink> for Storage {
+    type Ok = Option<VersionId>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: CreateHardLink) -> Result<Self::Ok, Self::Err> {
+        let _key = self.lock_key(&op.dest).await?;
+        let src = self
+            .resolve_beneath(&self.data_dir.join(&op.src), true)
+            .await
+            .map_err(tracerr::wrap!())?;
+        match async_fs::metadata(&src).await {
+            Ok(meta) if meta.is_dir() => {
+                return Err(tracerr::new!(io::Error::new(
+                    io::ErrorKind::IsADirectory,
+                    "Hard link source is a directory",
+                )));
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(tracerr::new!(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "Hard link source doesn't exist",
+                )));
+            }
+            Err(e) => return Err(tracerr::new!(e)),
+        }
+
+        // Replacing the original file atomically requires the same 2 steps as
+        // for a symlink, while both of them must happen on the same filesystem.
+        let tmp = self.tmp_dir.join(Uuid::new_v4().to_string());
+        async_fs::hard_link(&src, &tmp).await.map_err(|e| {
+            if e.kind() == io::ErrorKind::CrossesDevices {
+                tracerr::new!(io::Error::new(
+                    io::ErrorKind::CrossesDevices,
+                    format!(
+                        "Hard link `{}` cannot point to `{}` located on \
+                         another filesystem",
+                        op.dest, op.src,
+                    ),
+                ))
+            } else {
+                tracerr::new!(e)
+            }
+        })?;
+
+        let res = self.commit(&tmp, &op.dest, None, false).await;
+        if res.is_err() {
+            _ = async_fs::remove_file(&tmp).await;
+        }
+        res
     }
 }
 
 /// Operation for getting an existing file.
+///
+/// Files quarantined by a [`ScrubFiles`] operation cannot be got, until being
+/// overwritten.
 #[derive(Debug, Clone)]
 pub struct GetFile {
     /// [`RelativePath`] of the file.
     pub path: RelativePath,
+
+    /// [`VersionId`] of the file version to get.
+    ///
+    /// If [`None`], then the current version is returned.
+    pub version_id: Option<VersionId>,
+
+    /// [`EncryptionKey`] of a customer the file is encrypted with (if any).
+    pub encryption_key: Option<EncryptionKey>,
 }
 
 #[async_trait]
@@ -184,26 +783,226 @@ impl Exec<GetFile> for Storage {
     #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
     #[tracing::instrument(level = "debug", err(Debug))]
     async fn exec(&self, op: GetFile) -> Result<Self::Ok, Self::Err> {
-        let path = self.data_dir.join(op.path);
-
-        match File::open(&path).await {
-            Ok(f) => Ok(Some(ReadOnlyFile(f))),
-            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
-            Err(e) => Err(tracerr::new!(e)),
+        if op.version_id.is_none() {
+            self.ensure_not_quarantined(&op.path).await?;
         }
+        let Some((path, version_id)) = self
+            .resolve_version(&op.path, op.version_id)
+            .await
+            .map_err(tracerr::wrap!())?
+        else {
+            return Ok(None);
+        };
+        let live = self.live_path(&op.path).await.map_err(tracerr::wrap!())?;
+        let target =
+            symlink_target(&path, &live).await.map_err(tracerr::wrap!())?;
+        let symlink_target =
+            target.as_deref().and_then(|t| self.relative_path(t));
+        let path = match target {
+            Some(t) => {
+                let resolved = self
+                    .resolve_beneath(&t, true)
+                    .await
+                    .map_err(tracerr::wrap!())?;
+                if let Some(original) = self.relative_path(&resolved) {
+                    self.ensure_not_quarantined(&original).await?;
+                }
+                resolved
+            }
+            None => path,
+        };
+
+        let mut file = match self.open_beneath(&path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(tracerr::new!(e)),
+        };
+        let indexed = match &version_id {
+            Some(id) => self
+                .version_created_at(&op.path, id)
+                .await
+                .map_err(tracerr::wrap!())?,
+            None => None,
+        };
+        let last_modified = match indexed {
+            Some(time) => time,
+            None => {
+                let meta = file.metadata().await.map_err(tracerr::wrap!())?;
+                self.created_at(&path, &meta).await.map_err(tracerr::wrap!())?
+            }
+        };
+        let decryptor = self
+            .decryptor(&mut file, op.encryption_key.as_ref())
+            .await
+            .map_err(tracerr::wrap!())?;
+        let decompressor = if decryptor.is_none() {
+            compression::decompressor(&mut file)
+                .await
+                .map_err(tracerr::wrap!())?
+        } else {
+            None
+        };
+
+        Ok(Some(ReadOnlyFile {
+            file,
+            version_id,
+            last_modified,
+            symlink_target,
+            decryptor,
+            decompressor,
+        }))
+    }
+}
+
+/// Operation for getting the target of an existing symlink, without
+/// following it.
+///
+/// Results in [`None`] if there is no such symlink version, or it's not a
+/// symlink.
+#[derive(Debug, Clone)]
+pub struct GetSymlink {
+    /// [`RelativePath`] of the symlink.
+    pub path: RelativePath,
+
+    /// [`VersionId`] of the symlink version to get.
+    ///
+    /// If [`None`], then the current version is returned.
+    pub version_id: Option<VersionId>,
+}
+
+#[async_trait]
+impl Exec<GetSymlink> for Storage {
+    type Ok = Option<RelativePath>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: GetSymlink) -> Result<Self::Ok, Self::Err> {
+        let Some((path, _)) = self
+            .resolve_version(&op.path, op.version_id)
+            .await
+            .map_err(tracerr::wrap!())?
+        else {
+            return Ok(None);
+        };
+
+        let live = self.live_path(&op.path).await.map_err(tracerr::wrap!())?;
+        Ok(symlink_target(&path, &live)
+            .await
+            .map_err(tracerr::wrap!())?
+            .and_then(|target| self.relative_path(&target)))
     }
 }
 
 /// Read-only [`File`].
-pub struct ReadOnlyFile(File);
+pub struct ReadOnlyFile {
+    /// Underlying [`File`].
+    file: File,
+
+    /// [`VersionId`] of this [`ReadOnlyFile`], if it's versioned.
+    version_id: Option<VersionId>,
+
+    /// Time when this [`ReadOnlyFile`] was created.
+    last_modified: SystemTime,
+
+    /// [`RelativePath`] of the target of the symlink this [`ReadOnlyFile`]
+    /// is opened via, if any.
+    symlink_target: Option<RelativePath>,
+
+    /// [`Decryptor`] of this [`ReadOnlyFile`], if it's encrypted.
+    decryptor: Option<Decryptor>,
+
+    /// [`Decompressor`] of this [`ReadOnlyFile`], if it's compressed.
+    decompressor: Option<Decompressor>,
+}
+
+impl ReadOnlyFile {
+    /// Returns the [`VersionId`] of this [`ReadOnlyFile`], if it's versioned.
+    #[must_use]
+    pub const fn version_id(&self) -> Option<&VersionId> {
+        self.version_id.as_ref()
+    }
+
+    /// Returns the time when this [`ReadOnlyFile`] was created.
+    #[must_use]
+    pub const fn last_modified(&self) -> SystemTime {
+        self.last_modified
+    }
+
+    /// Returns the [`RelativePath`] of the target of the symlink this
+    /// [`ReadOnlyFile`] is opened via, if any.
+    #[must_use]
+    pub const fn symlink_target(&self) -> Option<&RelativePath> {
+        self.symlink_target.as_ref()
+    }
+
+    /// Returns the [`Encryption`] of this [`ReadOnlyFile`], if it's
+    /// encrypted.
+    #[must_use]
+    pub fn encryption(&self) -> Option<&Encryption> {
+        self.decryptor.as_ref().map(Decryptor::encryption)
+    }
+
+    /// Returns [`Metadata`] of this [`ReadOnlyFile`].
+    ///
+    /// Note, that [`Metadata::len()`] is the size of the stored file, which
+    /// differs from the [`ReadOnlyFile::size()`] for encrypted or compressed
+    /// files.
+    ///
+    /// # Errors
+    ///
+    /// If [`File::metadata()`] fails.
+    pub async fn metadata(&self) -> io::Result<Metadata> {
+        self.file.metadata().await
+    }
+
+    /// Returns the size of the contents of this [`ReadOnlyFile`].
+    ///
+    /// # Errors
+    ///
+    /// If [`File::metadata()`] fails.
+    pub async fn size(&self) -> io::Result<u64> {
+        if let Some(d) = &self.decryptor {
+            Ok(d.size())
+        } else if let Some(d) = &self.decompressor {
+            Ok(d.size())
+        } else {
+            Ok(self.file.metadata().await?.len())
+        }
+    }
+}
 
 impl AsyncRead for ReadOnlyFile {
     fn poll_read(
-        mut self: Pin<&mut Self>,
+        self: Pin<&mut Self>,
         cx: &mut task::Context<'_>,
         buf: &mut [u8],
     ) -> task::Poll<io::Result<usize>> {
-        Pin::new(&mut self.0).poll_read(cx, buf)
+        let this = self.get_mut();
+        if let Some(d) = this.decryptor.as_mut() {
+            d.poll_read(Pin::new(&mut this.file), cx, buf)
+        } else if let Some(d) = this.decompressor.as_mut() {
+            d.poll_read(Pin::new(&mut this.file), cx, buf)
+        } else {
+            Pin::new(&mut this.file).poll_read(cx, buf)
+        }
+    }
+}
+
+impl AsyncSeek for ReadOnlyFile {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        pos: io::SeekFrom,
+    ) -> task::Poll<io::Result<u64>> {
+        let this = self.get_mut();
+        if let Some(d) = this.decryptor.as_mut() {
+            task::Poll::Ready(d.seek(pos))
+        } else if let Some(d) = this.decompressor.as_mut() {
+            task::Poll::Ready(d.seek(pos))
+        } else {
+            Pin::new(&mut this.file).poll_seek(cx, pos)
+        }
     }
 }
 
@@ -226,6 +1025,19 @@ impl RelativePath {
         self.0.push(other.0);
         self
     }
+
+    /// Returns the [`RelativePath`] of the bucket this one belongs to (its
+    /// first component).
+    #[must_use]
+    pub fn bucket(&self) -> Self {
+        Self(self.0.iter().take(1).collect())
+    }
+}
+
+impl fmt::Display for RelativePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.display().fmt(f)
+    }
 }
 
 impl AsRef<Path> for RelativePath {
@@ -254,6 +1066,39 @@ impl TryFrom<String> for RelativePath {
 #[display("Invalid `RelativePath` format")]
 pub struct InvalidRelativePathError;
 
+/// Error of an operation being denied by the [`Storage`] itself (like a file
+/// protected by an [`ObjectLock`], or a path escaping the data directory),
+/// rather than by the underlying filesystem.
+///
+/// Carried by [`io::Error`]s of the [`io::ErrorKind::PermissionDenied`] kind.
+#[derive(Debug, Display, Error)]
+#[display("{_0}")]
+pub struct AccessDeniedError(#[error(not(source))] Cow<'static, str>);
+
+impl AccessDeniedError {
+    /// Creates a new [`io::Error`] caused by an [`AccessDeniedError`] with the
+    /// provided message.
+    pub(crate) fn io(msg: impl Into<Cow<'static, str>>) -> io::Error {
+        io::Error::new(io::ErrorKind::PermissionDenied, Self(msg.into()))
+    }
+}
+
+/// Error of a precondition of an operation not holding (like a symlink not
+/// pointing to the expected source anymore).
+///
+/// Carried by [`io::Error`]s of the [`io::ErrorKind::AlreadyExists`] kind.
+#[derive(Debug, Display, Error)]
+#[display("{_0}")]
+pub struct PreconditionError(#[error(not(source))] Cow<'static, str>);
+
+impl PreconditionError {
+    /// Creates a new [`io::Error`] caused by a [`PreconditionError`] with the
+    /// provided message.
+    pub(crate) fn io(msg: impl Into<Cow<'static, str>>) -> io::Error {
+        io::Error::new(io::ErrorKind::AlreadyExists, Self(msg.into()))
+    }
+}
+
 #[cfg(test)]
 mod relative_path_spec {
     use super::{InvalidRelativePathError, RelativePath};
@@ -292,3 +1137,31 @@ mod relative_path_spec {
         assert!(case("my/path/../").is_err());
     }
 }
+
+#[cfg(test)]
+mod relative_target_spec {
+    use std::path::Path;
+
+    use super::relative_target;
+
+    fn case(link: &str, target: &str) -> String {
+        relative_target(Path::new(link), Path::new(target))
+            .display()
+            .to_string()
+    }
+
+    #[test]
+    fn points_into_same_dir() {
+        assert_eq!(case("/data/bucket/link", "/data/bucket/file"), "file");
+        assert_eq!(case("/data/b/link", "/data/b/dir/file"), "dir/file");
+    }
+
+    #[test]
+    fn points_into_another_dir() {
+        assert_eq!(case("/data/b/dir/link", "/data/b/file"), "../file");
+        assert_eq!(
+            case("/data/links/a/b/link", "/data/files/c/file"),
+            "../../../files/c/file",
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Lifecycle of files in buckets.
+
+use std::{
+    collections::HashSet,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BucketConfig, DeleteFile, FileVersion, RelativePath, VersionId};
+
+/// Number of seconds in a day.
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// Lifecycle configuration of a bucket, describing when its files expire.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Lifecycle {
+    /// [`LifecycleRule`]s of this [`Lifecycle`].
+    pub rules: Vec<LifecycleRule>,
+}
+
+impl BucketConfig for Lifecycle {
+    const NAME: &'static str = "lifecycle";
+}
+
+/// Rule of a [`Lifecycle`].
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct LifecycleRule {
+    /// Unique identifier of this [`LifecycleRule`].
+    pub id: Option<String>,
+
+    /// Indicator whether this [`LifecycleRule`] is applied.
+    pub enabled: bool,
+
+    /// Prefix of the keys this [`LifecycleRule`] is applied to.
+    pub prefix: String,
+
+    /// Number of days after its creation the current version of a file
+    /// expires.
+    pub expiration_days: Option<u32>,
+
+    /// Number of days after becoming noncurrent a version of a file expires.
+    pub noncurrent_version_expiration_days: Option<u32>,
+
+    /// Number of days after its initiation an incomplete multipart upload is
+    /// aborted.
+    ///
+    /// Has no effect, as multipart uploads are not supported yet.
+    pub abort_incomplete_multipart_upload_days: Option<u32>,
+}
+
+impl Lifecycle {
+    /// Returns [`DeleteFile`] operations expiring the provided
+    /// [`FileVersion`]s of the provided `bucket` at the provided moment of
+    /// time (`now`), according to this [`Lifecycle`].
+    ///
+    /// [`FileVersion`]s are expected to be ordered as the
+    /// [`ListVersions`](crate::ListVersions) operation returns them: grouped
+    /// by key, from the latest to the oldest one.
+    #[must_use]
+    pub fn expired(
+        &self,
+        bucket: &RelativePath,
+        versions: &[FileVersion],
+        now: SystemTime,
+    ) -> Vec<DeleteFile> {
+        let is_older = |t: SystemTime, days: u32| {
+            now.duration_since(t).unwrap_or_default()
+                >= Duration::from_secs(u64::from(days) * DAY_SECS)
+        };
+
+        let mut expired = HashSet::<(&str, Option<&VersionId>)>::new();
+        let mut ops = Vec::new();
+        for rule in self.rules.iter().filter(|r| r.enabled) {
+            for file in versions.chunk_by(|a, b| a.key == b.key) {
+                // Files listed through directory aliases are expired along
+                // with the buckets they're stored in.
+                if file[0].aliased {
+                    continue;
+                }
+                let key = file[0].key.as_str();
+                if !key.starts_with(&rule.prefix) {
+                    continue;
+                }
+                let Ok(path) = RelativePath::try_from(key.to_owned()) else {
+                    continue;
+                };
+                let path = bucket.clone().join(path);
+
+                if let Some(days) = rule.expiration_days {
+                    let current = &file[0];
+                    if current.is_latest
+                        && !current.is_delete_marker
+                        && is_older(current.last_modified, days)
+                        && expired.insert((key, None))
+                    {
+                        ops.push(DeleteFile {
+                            path: path.clone(),
+                            version_id: None,
+                            bypass_governance: false,
+                        });
+                    }
+                }
+
+                if let Some(days) = rule.noncurrent_version_expiration_days {
+                    // A version becomes noncurrent once the next one is
+                    // created.
+                    for pair in file.windows(2) {
+                        let (next, version) = (&pair[0], &pair[1]);
+                        if is_older(next.last_modified, days)
+                            && expired.insert((key, Some(&version.id)))
+                        {
+                            ops.push(DeleteFile {
+                                path: path.clone(),
+                                version_id: Some(version.id.clone()),
+                                bypass_governance: false,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        ops
+    }
+}
+
+#[cfg(test)]
+mod lifecycle_spec {
+    use std::time::{Duration, SystemTime};
+
+    use super::{DAY_SECS, Lifecycle, LifecycleRule};
+    use crate::{FileVersion, RelativePath, VersionId};
+
+    fn days_ago(now: SystemTime, days: u64) -> SystemTime {
+        now - Duration::from_secs(days * DAY_SECS)
+    }
+
+    fn version(
+        key: &str,
+        id: &str,
+        is_latest: bool,
+        is_delete_marker: bool,
+        last_modified: SystemTime,
+    ) -> FileVersion {
+        FileVersion {
+            key: key.into(),
+            id: VersionId::try_from(id.to_owned()).unwrap(),
+            is_latest,
+            is_delete_marker,
+            last_modified,
+            size: 0,
+            aliased: false,
+        }
+    }
+
+    fn expired(
+        rules: Vec<LifecycleRule>,
+        versions: &[FileVersion],
+        now: SystemTime,
+    ) -> Vec<(String, Option<String>)> {
+        let bucket = RelativePath::try_from("b".to_owned()).unwrap();
+        Lifecycle { rules }
+            .expired(&bucket, versions, now)
+            .into_iter()
+            .map(|op| {
+                (op.path.to_string(), op.version_id.map(|v| v.to_string()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expires_current_versions_by_prefix() {
+        let now = SystemTime::now();
+        let versions = [
+            version("tmp/a", "null", true, false, days_ago(now, 3)),
+            version("tmp/b", "null", true, false, days_ago(now, 1)),
+            version("tmp/c", "v2", true, true, days_ago(now, 5)),
+            version("tmp/c", "v1", false, false, days_ago(now, 6)),
+            version("keep/d", "null", true, false, days_ago(now, 10)),
+        ];
+        let rules = vec![LifecycleRule {
+            enabled: true,
+            prefix: "tmp/".into(),
+            expiration_days: Some(2),
+            ..LifecycleRule::default()
+        }];
+
+        assert_eq!(expired(rules, &versions, now), [("b/tmp/a".into(), None)]);
+    }
+
+    #[test]
+    fn expires_noncurrent_versions() {
+        let now = SystemTime::now();
+        let versions = [
+            version("a", "v3", true, false, days_ago(now, 1)),
+            version("a", "v2", false, false, days_ago(now, 4)),
+            version("a", "v1", false, false, days_ago(now, 9)),
+        ];
+        let rules = vec![LifecycleRule {
+            enabled: true,
+            noncurrent_version_expiration_days: Some(3),
+            ..LifecycleRule::default()
+        }];
+
+        assert_eq!(
+            expired(rules, &versions, now),
+            [("b/a".into(), Some("v1".into()))],
+        );
+    }
+
+    #[test]
+    fn skips_disabled_rules_and_duplicates() {
+        let now = SystemTime::now();
+        let versions = [version("a", "null", true, false, days_ago(now, 5))];
+        let rule = LifecycleRule {
+            enabled: true,
+            expiration_days: Some(1),
+            ..LifecycleRule::default()
+        };
+        let disabled = LifecycleRule { enabled: false, ..rule.clone() };
+
+        assert_eq!(
+            expired(vec![rule.clone(), rule], &versions, now),
+            [("b/a".into(), None)],
+        );
+        assert!(expired(vec![disabled], &versions, now).is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+//! Reverse index of symlinks.
+
+use std::{collections::BTreeSet, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tracerr::Traced;
+
+use crate::{
+    Exec, RelativePath, Storage, async_trait, keys::KeyGuard,
+    remove_existing_file, versioning::symlink_target, write_atomically,
+};
+
+/// Operation of listing all the symlinks pointing to a file.
+///
+/// Only the symlinks created via [`CreateSymlink`] operation are tracked.
+///
+/// [`CreateSymlink`]: crate::CreateSymlink
+#[derive(Clone, Debug)]
+pub struct ListSymlinks {
+    /// [`RelativePath`] of the file to list the symlinks pointing to.
+    pub target: RelativePath,
+}
+
+#[async_trait]
+impl Exec<ListSymlinks> for Storage {
+    type Ok = Vec<RelativePath>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: ListSymlinks) -> Result<Self::Ok, Self::Err> {
+        self.live_symlinks(&op.target).await.map_err(tracerr::wrap!())
+    }
+}
+
+/// Symlinks pointing to a single file, persisted in the reverse index.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Links {
+    /// [`RelativePath`] of the file the symlinks point to.
+    target: String,
+
+    /// [`RelativePath`]s of the symlinks.
+    links: BTreeSet<String>,
+}
+
+impl Storage {
+    /// Enables or disables protection of files having symlinks pointing to
+    /// them from being overwritten or deleted.
+    ///
+    /// Disabled by default.
+    #[must_use]
+    pub const fn with_protected_symlink_targets(mut self, on: bool) -> Self {
+        self.protect_symlink_targets = on;
+        self
+    }
+
+    /// Returns the [`RelativePath`] of the file the symlink at the provided
+    /// `path` currently points to, if it's a symlink.
+    ///
+    /// # Errors
+    ///
+    /// If the symlink fails to be read.
+    pub(crate) async fn current_target(
+        &self,
+        path: &RelativePath,
+    ) -> io::Result<Option<RelativePath>> {
+        let live = self.live_path(path).await?;
+        Ok(symlink_target(&live, &live)
+            .await?
+            .and_then(|t| self.relative_path(&t)))
+    }
+
+    /// Locks the entries of the reverse index of the file at the provided
+    /// `path` and of its `before` and `after` targets, so that the symlinks
+    /// pointing to them are neither checked nor
+    /// [reindexed](Storage::reindex_symlink) concurrently.
+    pub(crate) async fn lock_links(
+        &self,
+        path: &RelativePath,
+        before: Option<&RelativePath>,
+        after: Option<&RelativePath>,
+    ) -> Vec<KeyGuard<'_>> {
+        let targets = [Some(path), before, after];
+        self.links_locks
+            .lock_all(targets.into_iter().flatten().map(|t| self.links_path(t)))
+            .await
+    }
+
+    /// Moves the symlink at the provided `path` in the reverse index from the
+    /// `before` target to the `after` one.
+    ///
+    /// Must be called while holding the [locks](Storage::lock_links) of the
+    /// `before` and `after` targets.
+    ///
+    /// # Errors
+    ///
+    /// If the reverse index fails to be read or written.
+    pub(crate) async fn reindex_symlink(
+        &self,
+        path: &RelativePath,
+        before: Option<RelativePath>,
+        after: Option<RelativePath>,
+    ) -> Result<(), Traced<io::Error>> {
+        let key = path.to_string();
+        if before.as_ref().map(ToString::to_string)
+            == after.as_ref().map(ToString::to_string)
+        {
+            return Ok(());
+        }
+        if let Some(target) = before {
+            let mut links =
+                self.read_links(&target).await.map_err(tracerr::wrap!())?;
+            if links.links.remove(&key) {
+                self.write_links(&target, &links).await?;
+            }
+        }
+        if let Some(target) = after {
+            let mut links =
+                self.read_links(&target).await.map_err(tracerr::wrap!())?;
+            if links.links.insert(key) {
+                self.write_links(&target, &links).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks whether the file at the provided `path` is allowed to be
+    /// overwritten or deleted, considering the symlinks pointing to it.
+    ///
+    /// Non-existent files are always allowed to be created, even if there are
+    /// dangling symlinks pointing to them.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::ResourceBusy`], if protection of symlink
+    ///   targets is enabled and the file has symlinks pointing to it.
+    /// - If the reverse index fails to be read.
+    pub(crate) async fn ensure_unlinked(
+        &self,
+        path: &RelativePath,
+    ) -> Result<(), Traced<io::Error>> {
+        if !self.protect_symlink_targets {
+            return Ok(());
+        }
+        let live = self.live_path(path).await.map_err(tracerr::wrap!())?;
+        match async_fs::symlink_metadata(&live).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(tracerr::new!(e)),
+        }
+
+        let links = self.live_symlinks(path).await.map_err(tracerr::wrap!())?;
+        if let Some(link) = links.first() {
+            return Err(tracerr::new!(io::Error::new(
+                io::ErrorKind::ResourceBusy,
+                format!(
+                    "`{path}` has {} symlink(s) pointing to it, like `{link}`",
+                    links.len(),
+                ),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the [`RelativePath`]s of the symlinks in the reverse index,
+    /// which still point to the provided `target`.
+    ///
+    /// # Errors
+    ///
+    /// If the reverse index or any of the symlinks fails to be read.
+    async fn live_symlinks(
+        &self,
+        target: &RelativePath,
+    ) -> io::Result<Vec<RelativePath>> {
+        let expected = target.to_string();
+        let mut live = Vec::new();
+        for link in self.read_links(target).await?.links {
+            let Ok(link) = RelativePath::try_from(link) else {
+                continue;
+            };
+            let actual = self.current_target(&link).await?;
+            if actual.is_some_and(|t| t.to_string() == expected) {
+                live.push(link);
+            }
+        }
+        Ok(live)
+    }
+
+    /// Reads the [`Links`] of the provided `target` from the reverse index.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Links`] fail to be read or deserialized.
+    async fn read_links(&self, target: &RelativePath) -> io::Result<Links> {
+        match async_fs::read(self.links_path(target)).await {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                Ok(Links { target: target.to_string(), ..Links::default() })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Persists the provided [`Links`] of the provided `target` in the reverse
+    /// index, removing them completely if there are no symlinks.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Links`] fail to be written.
+    async fn write_links(
+        &self,
+        target: &RelativePath,
+        links: &Links,
+    ) -> Result<(), Traced<io::Error>> {
+        let path = self.links_path(target);
+        if links.links.is_empty() {
+            return remove_existing_file(path).await.map_err(tracerr::wrap!());
+        }
+
+        let bytes = serde_json::to_vec_pretty(links)
+            .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+        write_atomically(&self.tmp_dir, &path, &bytes)
+            .await
+            .map_err(tracerr::wrap!())
+    }
+
+    /// Returns the [`PathBuf`] where the [`Links`] of the provided `target`
+    /// are stored.
+    fn links_path(&self, target: &RelativePath) -> PathBuf {
+        self.meta_dir.join("links").join(target.bucket()).join(format!(
+            "{}.json",
+            hex::encode(Sha256::digest(target.to_string())),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod protection_spec {
+    use std::io;
+
+    use futures::executor::block_on;
+
+    use crate::{
+        CreateSymlink, DeleteFile, Exec as _, ListSymlinks, Storage,
+        testing::{TempDir, create_file, path},
+    };
+
+    fn create_symlink(src: &str, dest: &str) -> CreateSymlink {
+        CreateSymlink {
+            src: path(src),
+            dest: path(dest),
+            expected_src: None,
+            allow_dangling: false,
+            directory: false,
+        }
+    }
+
+    fn delete_file(s: &str) -> DeleteFile {
+        DeleteFile { path: path(s), version_id: None, bypass_governance: false }
+    }
+
+    #[test]
+    fn tracks_live_symlinks_only() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            storage.exec(create_file("b/file", b"data")).await.unwrap();
+            storage.exec(create_file("b/other", b"data")).await.unwrap();
+            storage.exec(create_symlink("b/file", "b/l1")).await.unwrap();
+            storage.exec(create_symlink("b/file", "b/l2")).await.unwrap();
+            storage.exec(create_symlink("b/file", "b/l3")).await.unwrap();
+            storage.exec(create_symlink("b/other", "b/l2")).await.unwrap();
+            storage.exec(delete_file("b/l3")).await.unwrap();
+
+            let links =
+                storage.exec(ListSymlinks { target: path("b/file") }).await;
+            assert_eq!(
+                links
+                    .unwrap()
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                ["b/l1"],
+            );
+        });
+    }
+
+    #[test]
+    fn refuses_overwriting_and_deleting_linked_files() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root)
+                .await
+                .unwrap()
+                .with_protected_symlink_targets(true);
+            storage.exec(create_file("b/file", b"data")).await.unwrap();
+            storage.exec(create_symlink("b/file", "b/link")).await.unwrap();
+
+            let overwrite = storage.exec(create_file("b/file", b"data")).await;
+            let delete = storage.exec(delete_file("b/file")).await;
+            storage.exec(delete_file("b/link")).await.unwrap();
+            let unlinked = storage.exec(delete_file("b/file")).await;
+            for res in [overwrite.map(drop), delete.map(drop)] {
+                assert_eq!(
+                    res.unwrap_err().as_ref().kind(),
+                    io::ErrorKind::ResourceBusy,
+                );
+            }
+            assert!(unlinked.is_ok(), "{unlinked:?}");
+        });
+    }
+}
@@ -0,0 +1,520 @@
+//! Object Lock of files, preventing them from being overwritten or deleted.
+
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tracerr::Traced;
+
+use crate::{
+    AccessDeniedError, BucketConfig, Exec, GetBucketConfig, RelativePath,
+    Storage, VersionId, async_trait, remove_existing_file, write_atomically,
+};
+
+/// Number of seconds in a day.
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// Object Lock configuration of a bucket.
+///
+/// Files of buckets without any [`ObjectLock`] configured cannot be locked.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize,
+)]
+pub struct ObjectLock {
+    /// [`DefaultRetention`] applied to every newly created file.
+    pub default_retention: Option<DefaultRetention>,
+}
+
+impl BucketConfig for ObjectLock {
+    const NAME: &'static str = "object_lock";
+}
+
+/// [`Retention`] applied to every newly created file in a bucket.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DefaultRetention {
+    /// [`RetentionMode`] of the applied [`Retention`].
+    pub mode: RetentionMode,
+
+    /// Number of days the file is retained for since its creation.
+    pub days: u32,
+}
+
+/// Mode of a [`Retention`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum RetentionMode {
+    /// File cannot be overwritten or deleted, unless governance is bypassed
+    /// explicitly.
+    Governance,
+
+    /// File cannot be overwritten or deleted by anyone, and the [`Retention`]
+    /// cannot be shortened.
+    Compliance,
+}
+
+/// Retention of a file, preventing it from being overwritten or deleted until
+/// the specified moment of time.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Retention {
+    /// [`RetentionMode`] of this [`Retention`].
+    pub mode: RetentionMode,
+
+    /// Moment of time until which the file is retained.
+    pub retain_until: SystemTime,
+}
+
+impl Retention {
+    /// Indicates whether this [`Retention`] is still in effect.
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.retain_until > SystemTime::now()
+    }
+
+    /// Indicates whether replacing this [`Retention`] with the provided `next`
+    /// one weakens the protection of the file.
+    #[must_use]
+    fn is_weakened_by(&self, next: Option<&Self>) -> bool {
+        next.is_none_or(|next| {
+            next.retain_until < self.retain_until
+                || (self.mode == RetentionMode::Compliance
+                    && next.mode == RetentionMode::Governance)
+        })
+    }
+}
+
+/// Lock state of a single version of a file.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize,
+)]
+pub struct ObjectLockStatus {
+    /// [`Retention`] of the file version, if any.
+    pub retention: Option<Retention>,
+
+    /// Indicator whether the file version is under a legal hold, preventing it
+    /// from being overwritten or deleted regardless of its [`Retention`].
+    pub legal_hold: bool,
+}
+
+impl ObjectLockStatus {
+    /// Checks whether this [`ObjectLockStatus`] allows the file version to be
+    /// overwritten or deleted.
+    ///
+    /// # Errors
+    ///
+    /// With [`io::ErrorKind::PermissionDenied`], if the file version is locked.
+    fn ensure_unlocked(&self, bypass_governance: bool) -> io::Result<()> {
+        if self.legal_hold {
+            return Err(AccessDeniedError::io("Object is under legal hold"));
+        }
+        match self.retention.filter(Retention::is_active).map(|r| r.mode) {
+            Some(RetentionMode::Compliance) => Err(AccessDeniedError::io(
+                "Object is retained in compliance mode",
+            )),
+            Some(RetentionMode::Governance) if !bypass_governance => Err(
+                AccessDeniedError::io("Object is retained in governance mode"),
+            ),
+            Some(RetentionMode::Governance) | None => Ok(()),
+        }
+    }
+}
+
+/// Operation for getting the [`ObjectLockStatus`] of a file.
+#[derive(Clone, Debug)]
+pub struct GetObjectLock {
+    /// [`RelativePath`] of the file.
+    pub path: RelativePath,
+
+    /// [`VersionId`] of the file version.
+    ///
+    /// If [`None`], then the current version is used.
+    pub version_id: Option<VersionId>,
+}
+
+#[async_trait]
+impl Exec<GetObjectLock> for Storage {
+    type Ok = Option<ObjectLockStatus>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: GetObjectLock) -> Result<Self::Ok, Self::Err> {
+        let path = self.resolve_alias(&op.path).await?;
+        let Some(id) = self.resolve_lockable(&path, op.version_id).await?
+        else {
+            return Ok(None);
+        };
+        self.read_lock(&path, &id)
+            .await
+            .map(|s| Some(s.unwrap_or_default()))
+            .map_err(tracerr::wrap!())
+    }
+}
+
+/// Operation of a file [`Retention`] update.
+#[derive(Clone, Debug)]
+pub struct SetObjectRetention {
+    /// [`RelativePath`] of the file.
+    pub path: RelativePath,
+
+    /// [`VersionId`] of the file version.
+    ///
+    /// If [`None`], then the current version is used.
+    pub version_id: Option<VersionId>,
+
+    /// New [`Retention`] of the file.
+    ///
+    /// [`None`] removes the existing one.
+    pub retention: Option<Retention>,
+
+    /// Indicator whether an active [`RetentionMode::Governance`] is allowed
+    /// to be shortened or removed.
+    pub bypass_governance: bool,
+}
+
+#[async_trait]
+impl Exec<SetObjectRetention> for Storage {
+    type Ok = ();
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(
+        &self,
+        op: SetObjectRetention,
+    ) -> Result<Self::Ok, Self::Err> {
+        let _key = self.lock_key(&op.path).await?;
+        let path = self.resolve_alias(&op.path).await?;
+        self.ensure_object_lock(&path).await?;
+        let Some(id) = self.resolve_lockable(&path, op.version_id).await?
+        else {
+            return Err(tracerr::new!(no_such_object()));
+        };
+
+        let mut status = self
+            .read_lock(&path, &id)
+            .await
+            .map_err(tracerr::wrap!())?
+            .unwrap_or_default();
+        if let Some(current) = status.retention.filter(Retention::is_active) {
+            if current.is_weakened_by(op.retention.as_ref())
+                && (current.mode == RetentionMode::Compliance
+                    || !op.bypass_governance)
+            {
+                return Err(tracerr::new!(AccessDeniedError::io(format!(
+                    "Retention in {:?} mode cannot be weakened",
+                    current.mode,
+                ))));
+            }
+        }
+        status.retention = op.retention;
+
+        self.write_lock(&path, &id, status).await
+    }
+}
+
+/// Operation of a file legal hold update.
+#[derive(Clone, Debug)]
+pub struct SetObjectLegalHold {
+    /// [`RelativePath`] of the file.
+    pub path: RelativePath,
+
+    /// [`VersionId`] of the file version.
+    ///
+    /// If [`None`], then the current version is used.
+    pub version_id: Option<VersionId>,
+
+    /// Indicator whether the legal hold is placed or removed.
+    pub enabled: bool,
+}
+
+#[async_trait]
+impl Exec<SetObjectLegalHold> for Storage {
+    type Ok = ();
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(
+        &self,
+        op: SetObjectLegalHold,
+    ) -> Result<Self::Ok, Self::Err> {
+        let _key = self.lock_key(&op.path).await?;
+        let path = self.resolve_alias(&op.path).await?;
+        self.ensure_object_lock(&path).await?;
+        let Some(id) = self.resolve_lockable(&path, op.version_id).await?
+        else {
+            return Err(tracerr::new!(no_such_object()));
+        };
+
+        let mut status = self
+            .read_lock(&path, &id)
+            .await
+            .map_err(tracerr::wrap!())?
+            .unwrap_or_default();
+        status.legal_hold = op.enabled;
+
+        self.write_lock(&path, &id, status).await
+    }
+}
+
+impl Storage {
+    /// Checks whether the version with the provided [`VersionId`] of the file
+    /// at the provided `path` is allowed to be overwritten or deleted.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::PermissionDenied`], if the file version is
+    ///   locked.
+    /// - If the [`ObjectLockStatus`] fails to be read.
+    pub(crate) async fn ensure_unlocked(
+        &self,
+        path: &RelativePath,
+        id: &VersionId,
+        bypass_governance: bool,
+    ) -> Result<(), Traced<io::Error>> {
+        self.read_lock(path, id)
+            .await
+            .map_err(tracerr::wrap!())?
+            .unwrap_or_default()
+            .ensure_unlocked(bypass_governance)
+            .map_err(tracerr::wrap!())
+    }
+
+    /// Locks the newly created version with the provided [`VersionId`] of the
+    /// file at the provided `path` according to the [`DefaultRetention`] of its
+    /// bucket (if any), discarding any previous [`ObjectLockStatus`] of it.
+    ///
+    /// # Errors
+    ///
+    /// If the [`ObjectLock`] fails to be read, or the [`ObjectLockStatus`]
+    /// fails to be written.
+    pub(crate) async fn lock_created(
+        &self,
+        path: &RelativePath,
+        id: &VersionId,
+    ) -> Result<(), Traced<io::Error>> {
+        let default = self
+            .exec(GetBucketConfig::<ObjectLock>::new(path.bucket()))
+            .await
+            .map_err(tracerr::wrap!())?
+            .and_then(|l| l.default_retention);
+        let Some(default) = default else {
+            return self.release_lock(path, id).await;
+        };
+
+        let retention = Retention {
+            mode: default.mode,
+            retain_until: SystemTime::now()
+                + Duration::from_secs(u64::from(default.days) * DAY_SECS),
+        };
+        let status =
+            ObjectLockStatus { retention: Some(retention), legal_hold: false };
+        self.write_lock(path, id, status).await
+    }
+
+    /// Discards the [`ObjectLockStatus`] of the removed version with the
+    /// provided [`VersionId`] of the file at the provided `path`.
+    ///
+    /// # Errors
+    ///
+    /// If the [`ObjectLockStatus`] fails to be removed.
+    pub(crate) async fn release_lock(
+        &self,
+        path: &RelativePath,
+        id: &VersionId,
+    ) -> Result<(), Traced<io::Error>> {
+        remove_existing_file(self.lock_path(path, id))
+            .await
+            .map_err(tracerr::wrap!())
+    }
+
+    /// Checks whether the bucket of the provided `path` has [`ObjectLock`]
+    /// configured.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::InvalidInput`], if the bucket has no
+    ///   [`ObjectLock`] configured.
+    /// - If the [`ObjectLock`] fails to be read.
+    async fn ensure_object_lock(
+        &self,
+        path: &RelativePath,
+    ) -> Result<(), Traced<io::Error>> {
+        self.exec(GetBucketConfig::<ObjectLock>::new(path.bucket()))
+            .await
+            .map_err(tracerr::wrap!())?
+            .map(drop)
+            .ok_or_else(|| {
+                tracerr::new!(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Bucket has no Object Lock configured",
+                ))
+            })
+    }
+
+    /// Resolves the [`VersionId`] which the [`ObjectLockStatus`] of the
+    /// provided version of the file at the provided `path` is stored under.
+    ///
+    /// Returns [`None`] if there is no such version, or it's a delete marker.
+    ///
+    /// # Errors
+    ///
+    /// If the version fails to be resolved.
+    async fn resolve_lockable(
+        &self,
+        path: &RelativePath,
+        version: Option<VersionId>,
+    ) -> Result<Option<VersionId>, Traced<io::Error>> {
+        let Some((file, id)) = self
+            .resolve_version(path, version)
+            .await
+            .map_err(tracerr::wrap!())?
+        else {
+            return Ok(None);
+        };
+        match async_fs::symlink_metadata(&file).await {
+            Ok(_) => Ok(Some(id.unwrap_or_else(VersionId::null))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(tracerr::new!(e)),
+        }
+    }
+
+    /// Reads the [`ObjectLockStatus`] of the version with the provided
+    /// [`VersionId`] of the file at the provided `path`, if any.
+    ///
+    /// # Errors
+    ///
+    /// If the [`ObjectLockStatus`] fails to be read or deserialized.
+    async fn read_lock(
+        &self,
+        path: &RelativePath,
+        id: &VersionId,
+    ) -> io::Result<Option<ObjectLockStatus>> {
+        match async_fs::read(self.lock_path(path, id)).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Persists the provided [`ObjectLockStatus`] of the version with the
+    /// provided [`VersionId`] of the file at the provided `path`.
+    ///
+    /// # Errors
+    ///
+    /// If the [`ObjectLockStatus`] fails to be written.
+    async fn write_lock(
+        &self,
+        path: &RelativePath,
+        id: &VersionId,
+        status: ObjectLockStatus,
+    ) -> Result<(), Traced<io::Error>> {
+        if status == ObjectLockStatus::default() {
+            return self.release_lock(path, id).await;
+        }
+
+        let bytes = serde_json::to_vec_pretty(&status)
+            .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+        write_atomically(&self.tmp_dir, &self.lock_path(path, id), &bytes)
+            .await
+            .map_err(tracerr::wrap!())
+    }
+
+    /// Returns the [`PathBuf`] where the [`ObjectLockStatus`] of the version
+    /// with the provided [`VersionId`] of the file at the provided `path` is
+    /// persisted.
+    fn lock_path(&self, path: &RelativePath, id: &VersionId) -> PathBuf {
+        self.meta_dir
+            .join("locks")
+            .join(path.bucket())
+            .join(hex::encode(Sha256::digest(path.to_string())))
+            .join(format!("{id}.json"))
+    }
+}
+
+/// Creates a new [`io::ErrorKind::NotFound`] error of a missing file version.
+fn no_such_object() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "No such object or version")
+}
+
+#[cfg(test)]
+mod object_lock_spec {
+    use std::time::{Duration, SystemTime};
+
+    use super::{ObjectLockStatus, Retention, RetentionMode};
+
+    fn retention(mode: RetentionMode, secs: i64) -> Retention {
+        let now = SystemTime::now();
+        let offset = Duration::from_secs(secs.unsigned_abs());
+        Retention {
+            mode,
+            retain_until: if secs < 0 { now - offset } else { now + offset },
+        }
+    }
+
+    #[test]
+    fn legal_hold_locks_regardless_of_retention() {
+        let status = ObjectLockStatus { retention: None, legal_hold: true };
+
+        assert!(status.ensure_unlocked(true).is_err());
+    }
+
+    #[test]
+    fn governance_can_be_bypassed() {
+        let status = ObjectLockStatus {
+            retention: Some(retention(RetentionMode::Governance, 60)),
+            legal_hold: false,
+        };
+
+        assert!(status.ensure_unlocked(false).is_err());
+        assert!(status.ensure_unlocked(true).is_ok());
+    }
+
+    #[test]
+    fn compliance_cannot_be_bypassed() {
+        let status = ObjectLockStatus {
+            retention: Some(retention(RetentionMode::Compliance, 60)),
+            legal_hold: false,
+        };
+
+        assert!(status.ensure_unlocked(true).is_err());
+    }
+
+    #[test]
+    fn expired_retention_unlocks() {
+        let status = ObjectLockStatus {
+            retention: Some(retention(RetentionMode::Compliance, -60)),
+            legal_hold: false,
+        };
+
+        assert!(status.ensure_unlocked(false).is_ok());
+    }
+
+    #[test]
+    fn detects_weakened_retention() {
+        let current = retention(RetentionMode::Compliance, 60);
+
+        assert!(current.is_weakened_by(None));
+        assert!(
+            current.is_weakened_by(Some(&retention(
+                RetentionMode::Compliance,
+                30
+            )))
+        );
+        assert!(
+            current.is_weakened_by(Some(&retention(
+                RetentionMode::Governance,
+                90
+            )))
+        );
+        assert!(
+            !current.is_weakened_by(Some(&retention(
+                RetentionMode::Compliance,
+                90
+            )))
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Atomic publishing of symlink sets.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest as _, Sha256};
+use tracerr::Traced;
+use uuid::Uuid;
+
+use crate::{
+    Exec, PreconditionError, RelativePath, Storage, async_trait,
+    relative_target, remove_existing_dir, remove_existing_file,
+    versioning::{list_dirs, symlink_target},
+};
+
+/// Operation of publishing a set of symlinks atomically.
+///
+/// The symlinks are staged in a separate directory first, and then the
+/// published directory is repointed to it with a single rename, so clients
+/// never observe a partially published set. The previously published set is
+/// kept until the next publishing, so requests still resolving paths through
+/// it don't fail, while the older ones are removed.
+///
+/// Every symlink must resolve to an existing file, the same way as a
+/// [`CreateSymlink`] does.
+///
+/// The published directory itself is not versioned and not counted in the
+/// [`Usage`] of its bucket. Being a symlink to the storage metadata, it's also
+/// not relocatable along with the data directory, and its symlinks are not
+/// listed by the [`ListSymlinks`] operation, so don't protect their targets
+/// from being replaced or deleted.
+///
+/// [`CreateSymlink`]: crate::CreateSymlink
+/// [`ListSymlinks`]: crate::ListSymlinks
+/// [`Usage`]: crate::Usage
+#[derive(Clone, Debug)]
+pub struct PublishSymlinks {
+    /// [`RelativePath`] of the directory to publish the symlinks in.
+    pub dir: RelativePath,
+
+    /// Symlinks to publish, as pairs of their [`RelativePath`]s inside the
+    /// `dir`ectory and the [`RelativePath`]s of the original source files
+    /// they point to.
+    pub links: Vec<(RelativePath, RelativePath)>,
+}
+
+#[async_trait]
+impl Exec<PublishSymlinks> for Storage {
+    type Ok = ();
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: PublishSymlinks) -> Result<Self::Ok, Self::Err> {
+        self.ensure_writable()?;
+
+        let mut keys = op.links.iter().map(|(l, _)| l).collect::<Vec<_>>();
+        keys.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+        if let Some(w) =
+            keys.windows(2).find(|w| w[1].as_ref().starts_with(w[0]))
+        {
+            return Err(tracerr::new!(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Symlink `{}` conflicts with `{}`", w[0], w[1]),
+            )));
+        }
+
+        let _key = self.lock_key(&op.dir).await?;
+        let live = self.live_path(&op.dir).await.map_err(tracerr::wrap!())?;
+        let mut links = Vec::with_capacity(op.links.len());
+        for (link, src) in &op.links {
+            let src = self.live_path(src).await.map_err(tracerr::wrap!())?;
+            self.ensure_resolvable(&live.join(link), &src, false)
+                .await
+                .map_err(tracerr::wrap!())?;
+            links.push((link, src));
+        }
+
+        let releases = self.releases_dir(&op.dir);
+        let release = releases.join(Uuid::new_v4().to_string());
+
+        let staged = self.tmp_dir.join(Uuid::new_v4().to_string());
+        let res = async {
+            for (link, src) in &links {
+                // Targets are relative to the place the symlinks will reside
+                // in, once published.
+                let target = relative_target(&release.join(link), src);
+                let path = staged.join(link);
+                if let Some(dir) = path.parent() {
+                    async_fs::create_dir_all(dir).await?;
+                }
+                async_fs::unix::symlink(target, path).await?;
+            }
+            async_fs::create_dir_all(&staged).await?;
+            async_fs::create_dir_all(&releases).await?;
+            async_fs::rename(&staged, &release).await
+        }
+        .await;
+        if let Err(e) = res {
+            _ = remove_existing_dir(&staged).await;
+            return Err(tracerr::new!(e));
+        }
+
+        let link = self.tmp_dir.join(Uuid::new_v4().to_string());
+        let res = self.swap_release(&op.dir, &link, &release).await;
+        if res.is_err() {
+            _ = remove_existing_file(&link).await;
+            if let Err(e) = remove_existing_dir(&release).await {
+                tracing::warn!("Failed to remove unpublished symlinks: {e}");
+            }
+        }
+        let previous = res?;
+
+        // Requests may still be resolving paths through the previous set, so
+        // it's kept until the next publishing, while the older ones are
+        // removed.
+        let res = async {
+            for dir in list_dirs(&releases).await? {
+                if dir != release && Some(&dir) != previous.as_ref() {
+                    remove_existing_dir(&dir).await?;
+                }
+            }
+            io::Result::Ok(())
+        }
+        .await;
+        if let Err(e) = res {
+            tracing::warn!("Failed to remove published symlinks: {e}");
+        }
+        Ok(())
+    }
+}
+
+impl Storage {
+    /// Atomically repoints the published `dir`ectory to the provided
+    /// `release` one, via the provided temporary `link`.
+    ///
+    /// Returns the previously published `release` directory, if any.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::AlreadyExists`], if something else than a
+    ///   published directory exists at the `dir` path.
+    /// - If any of filesystem operations fails.
+    async fn swap_release(
+        &self,
+        dir: &RelativePath,
+        link: &Path,
+        release: &Path,
+    ) -> Result<Option<PathBuf>, Traced<io::Error>> {
+        let live = self.live_path(dir).await.map_err(tracerr::wrap!())?;
+        let releases = self.releases_dir(dir);
+
+        async_fs::unix::symlink(relative_target(&live, release), link)
+            .await
+            .map_err(tracerr::wrap!())?;
+        if let Some(dir) = live.parent() {
+            async_fs::create_dir_all(dir).await.map_err(tracerr::wrap!())?;
+        }
+
+        let previous = match async_fs::symlink_metadata(&live).await {
+            Ok(meta) => Some(
+                if meta.is_symlink() {
+                    symlink_target(&live, &live)
+                        .await
+                        .map_err(tracerr::wrap!())?
+                } else {
+                    None
+                }
+                .filter(|t| t.starts_with(&releases))
+                .ok_or_else(|| {
+                    tracerr::new!(PreconditionError::io(format!(
+                        "`{dir}` is not a directory of published symlinks",
+                    )))
+                })?,
+            ),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(tracerr::new!(e)),
+        };
+        async_fs::rename(link, &live).await.map_err(tracerr::wrap!())?;
+
+        Ok(previous)
+    }
+
+    /// Returns the [`PathBuf`] to the directory where all the published sets
+    /// of symlinks are stored.
+    pub(crate) fn releases_root(&self) -> PathBuf {
+        self.meta_dir.join("releases")
+    }
+
+    /// Returns the [`PathBuf`] to the directory where the sets of symlinks
+    /// published in the provided `dir`ectory are stored.
+    fn releases_dir(&self, dir: &RelativePath) -> PathBuf {
+        self.releases_root()
+            .join(dir.bucket())
+            .join(hex::encode(Sha256::digest(dir.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod publish_spec {
+    use std::io;
+
+    use futures::executor::block_on;
+
+    use super::PublishSymlinks;
+    use crate::{
+        Exec as _, GetFreeSpace, Storage,
+        testing::{TempDir, create_file, path},
+    };
+
+    fn publish(src: &str) -> PublishSymlinks {
+        PublishSymlinks {
+            dir: path("links/release"),
+            links: vec![(path("app"), path(src))],
+        }
+    }
+
+    #[test]
+    fn keeps_previous_release_until_next_publishing() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            let published = storage.data_dir.join("links/release");
+            storage.exec(create_file("data/file", b"x")).await.unwrap();
+
+            storage.exec(publish("data/file")).await.unwrap();
+            let first = async_fs::canonicalize(&published).await.unwrap();
+            storage.exec(publish("data/file")).await.unwrap();
+            assert!(
+                async_fs::metadata(first.join("app")).await.is_ok(),
+                "Previous release is removed",
+            );
+
+            storage.exec(publish("data/file")).await.unwrap();
+            assert!(
+                async_fs::metadata(&first).await.is_err(),
+                "Outdated release is kept",
+            );
+        });
+    }
+
+    #[test]
+    fn rejects_writing_through_published_symlinks() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage =
+                Storage::new(&*root).await.unwrap().with_alias_writes(true);
+            storage.exec(create_file("data/file", b"x")).await.unwrap();
+            storage.exec(publish("data/file")).await.unwrap();
+
+            let err = storage
+                .exec(create_file("links/release/app", b"y"))
+                .await
+                .unwrap_err();
+
+            assert_eq!(err.as_ref().kind(), io::ErrorKind::PermissionDenied);
+            let app = storage.data_dir.join("links/release/app");
+            assert_eq!(async_fs::read(app).await.unwrap(), b"x");
+        });
+    }
+
+    #[test]
+    fn rejects_publishing_while_read_only() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            storage.exec(create_file("data/file", b"x")).await.unwrap();
+            let storage =
+                storage.with_free_space_watermarks(u64::MAX, u64::MAX);
+            _ = storage.exec(GetFreeSpace).await.unwrap();
+
+            let err = storage.exec(publish("data/file")).await.unwrap_err();
+
+            assert_eq!(err.as_ref().kind(), io::ErrorKind::ReadOnlyFilesystem);
+            let published = storage.data_dir.join("links/release");
+            assert!(
+                async_fs::symlink_metadata(published).await.is_err(),
+                "Symlinks are published",
+            );
+        });
+    }
+}
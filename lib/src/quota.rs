@@ -0,0 +1,400 @@
+//! Quotas of buckets.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracerr::Traced;
+
+use crate::{
+    BucketConfig, Exec, GetBucketConfig, RelativePath, Storage, async_trait,
+    keys::KeyGuard,
+    list_files,
+    versioning::{INDEX_FILE, list_dirs},
+    write_atomically,
+};
+
+/// Quota of a bucket, limiting the amount of data stored in it.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize,
+)]
+pub struct Quota {
+    /// Maximum number of bytes stored in the bucket.
+    pub max_bytes: Option<u64>,
+
+    /// Maximum number of objects stored in the bucket.
+    pub max_objects: Option<u64>,
+}
+
+impl BucketConfig for Quota {
+    const NAME: &'static str = "quota";
+}
+
+impl Quota {
+    /// Checks whether this [`Quota`] allows the provided `current` [`Usage`]
+    /// of a bucket to change to the provided `next` one.
+    ///
+    /// Decreasing [`Usage`] is always allowed, even if it still exceeds this
+    /// [`Quota`].
+    #[must_use]
+    pub fn allows(&self, current: Usage, next: Usage) -> bool {
+        let fits = |max: Option<u64>, current, next| {
+            next <= current || max.is_none_or(|max| next <= max)
+        };
+        fits(self.max_bytes, current.bytes, next.bytes)
+            && fits(self.max_objects, current.objects, next.objects)
+    }
+}
+
+/// Usage of a bucket, tracked incrementally on every file creation and
+/// deletion.
+///
+/// Every stored version of a file is counted (noncurrent ones too), while
+/// delete markers are not. Symlinks are counted as objects of zero size.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize,
+)]
+pub struct Usage {
+    /// Number of bytes stored in the bucket.
+    pub bytes: u64,
+
+    /// Number of objects stored in the bucket.
+    pub objects: u64,
+}
+
+impl Usage {
+    /// Returns this [`Usage`] with the object of the provided `added` size
+    /// stored, and the object of the provided `removed` size gone.
+    #[must_use]
+    fn apply(self, added: Option<u64>, removed: Option<u64>) -> Self {
+        let mut next = self;
+        if let Some(size) = added {
+            next.bytes = next.bytes.saturating_add(size);
+            next.objects = next.objects.saturating_add(1);
+        }
+        if let Some(size) = removed {
+            next.bytes = next.bytes.saturating_sub(size);
+            next.objects = next.objects.saturating_sub(1);
+        }
+        next
+    }
+}
+
+/// [`Usage`] of a bucket [reserved](Storage::reserve_usage) for a change,
+/// keeping the bucket locked until it's [stored](Storage::store_usage).
+pub(crate) struct ReservedUsage<'a> {
+    /// [`RelativePath`] of the bucket.
+    bucket: RelativePath,
+
+    /// New [`Usage`] of the bucket.
+    usage: Usage,
+
+    /// Lock of the [`Usage`] of the bucket.
+    _lock: KeyGuard<'a>,
+}
+
+/// Operation for getting the [`Usage`] of a bucket.
+#[derive(Clone, Debug)]
+pub struct GetBucketUsage {
+    /// [`RelativePath`] of the bucket.
+    pub bucket: RelativePath,
+}
+
+#[async_trait]
+impl Exec<GetBucketUsage> for Storage {
+    type Ok = Usage;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: GetBucketUsage) -> Result<Self::Ok, Self::Err> {
+        let _lock = self.usage_locks.lock(op.bucket.as_ref()).await;
+        self.load_usage(&op.bucket).await.map_err(tracerr::wrap!())
+    }
+}
+
+/// Operation for getting the number of bytes a file at a path may take at
+/// most, according to the [`Quota`] of its bucket.
+///
+/// Returns [`None`] if the bucket has no limit on the number of bytes.
+#[derive(Clone, Debug)]
+pub struct GetQuotaAllowance {
+    /// [`RelativePath`] of the file.
+    pub path: RelativePath,
+}
+
+#[async_trait]
+impl Exec<GetQuotaAllowance> for Storage {
+    type Ok = Option<u64>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: GetQuotaAllowance) -> Result<Self::Ok, Self::Err> {
+        let path = self.resolve_alias(&op.path).await?;
+        self.quota_allowance(&path).await
+    }
+}
+
+impl Storage {
+    /// Returns the number of bytes a file at the provided `path` may take at
+    /// most, according to the [`Quota`] of its bucket, if it limits them.
+    ///
+    /// The `path` is expected to be [resolved](Storage::resolve_alias)
+    /// already.
+    ///
+    /// This is only an estimation made before a file is written, while the
+    /// [`Quota`] is enforced once it's [reserved](Storage::reserve_usage).
+    ///
+    /// # Errors
+    ///
+    /// If the [`Quota`], the [`Usage`] or the replaced file fail to be read.
+    pub(crate) async fn quota_allowance(
+        &self,
+        path: &RelativePath,
+    ) -> Result<Option<u64>, Traced<io::Error>> {
+        let bucket = path.bucket();
+        let Some(max) = self
+            .exec(GetBucketConfig::<Quota>::new(bucket.clone()))
+            .await
+            .map_err(tracerr::wrap!())?
+            .and_then(|q| q.max_bytes)
+        else {
+            return Ok(None);
+        };
+        let usage = self.exec(GetBucketUsage { bucket }).await?;
+
+        // The replaced file frees its bytes, unless it's preserved as a
+        // noncurrent version.
+        let replaced = if self.versioning(path).await?.is_none() {
+            let live = self.live_path(path).await.map_err(tracerr::wrap!())?;
+            stored_size(&live).await.map_err(tracerr::wrap!())?.unwrap_or(0)
+        } else {
+            0
+        };
+        Ok(Some(max.saturating_sub(usage.bytes).saturating_add(replaced)))
+    }
+
+    /// Reserves the [`Usage`] of the bucket of the provided `path` for
+    /// storing an object of the provided `added` size in place of the one of
+    /// the provided `removed` size, checking its [`Quota`].
+    ///
+    /// The `path` is expected to be [resolved](Storage::resolve_alias)
+    /// already, so objects written through directory aliases are charged to
+    /// the buckets of the aliased directories.
+    ///
+    /// Returns the new [`Usage`] to be [stored](Storage::store_usage) once the
+    /// change is actually made. The [`Usage`] of the bucket is locked until
+    /// then, so the change should be made right away.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::QuotaExceeded`], if the [`Quota`] of the bucket
+    ///   doesn't allow the change.
+    /// - If the [`Usage`] or the [`Quota`] fails to be read.
+    pub(crate) async fn reserve_usage(
+        &self,
+        path: &RelativePath,
+        added: Option<u64>,
+        removed: Option<u64>,
+    ) -> Result<ReservedUsage<'_>, Traced<io::Error>> {
+        let bucket = path.bucket();
+        let lock = self.usage_locks.lock(bucket.as_ref()).await;
+        let current =
+            self.load_usage(&bucket).await.map_err(tracerr::wrap!())?;
+        let next = current.apply(added, removed);
+
+        if added.is_some() {
+            let quota = self
+                .exec(GetBucketConfig::<Quota>::new(bucket.clone()))
+                .await
+                .map_err(tracerr::wrap!())?;
+            if quota.is_some_and(|q| !q.allows(current, next)) {
+                return Err(tracerr::new!(io::Error::new(
+                    io::ErrorKind::QuotaExceeded,
+                    format!("Quota of `{bucket}` bucket is exceeded"),
+                )));
+            }
+        }
+        Ok(ReservedUsage { bucket, usage: next, _lock: lock })
+    }
+
+    /// Persists the provided [`ReservedUsage`] of a bucket, unlocking it.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Usage`] fails to be written.
+    pub(crate) async fn store_usage(
+        &self,
+        reserved: ReservedUsage<'_>,
+    ) -> Result<(), Traced<io::Error>> {
+        let bytes = serde_json::to_vec_pretty(&reserved.usage)
+            .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+        write_atomically(
+            &self.tmp_dir,
+            &self.usage_path(&reserved.bucket),
+            &bytes,
+        )
+        .await
+        .map_err(tracerr::wrap!())
+    }
+
+    /// Loads the persisted [`Usage`] of the provided `bucket`.
+    ///
+    /// If there is no persisted [`Usage`] yet, it's calculated by walking all
+    /// the files of the `bucket`.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Usage`] or the files of the `bucket` fail to be read.
+    async fn load_usage(&self, bucket: &RelativePath) -> io::Result<Usage> {
+        match async_fs::read(self.usage_path(bucket)).await {
+            Ok(bytes) => return Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let mut usage = Usage::default();
+        let data_dir =
+            self.resolve_beneath(&self.data_dir.join(bucket), true).await?;
+        for key in list_files(&data_dir).await? {
+            usage = usage.apply(stored_size(&data_dir.join(key)).await?, None);
+        }
+        let versions_dir = self.meta_dir.join("versions").join(bucket);
+        for dir in list_dirs(&versions_dir).await? {
+            for name in list_files(&dir).await? {
+                if name != INDEX_FILE {
+                    usage =
+                        usage.apply(stored_size(&dir.join(name)).await?, None);
+                }
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Returns the [`Path`] where the [`Usage`] of the bucket of the provided
+    /// `path` is persisted.
+    fn usage_path(&self, path: &RelativePath) -> PathBuf {
+        self.meta_dir.join("buckets").join(path.bucket()).join("usage.json")
+    }
+}
+
+/// Returns the size of the object stored at the provided `path`, counted in
+/// [`Usage`], if there is any.
+///
+/// Symlinks are not followed and have zero size.
+///
+/// # Errors
+///
+/// If the metadata of the `path` fails to be read.
+pub(crate) async fn stored_size(path: &Path) -> io::Result<Option<u64>> {
+    match async_fs::symlink_metadata(path).await {
+        Ok(meta) if meta.is_symlink() => Ok(Some(0)),
+        Ok(meta) => Ok(Some(meta.len())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod quota_spec {
+    use super::{Quota, Usage};
+
+    const fn usage(bytes: u64, objects: u64) -> Usage {
+        Usage { bytes, objects }
+    }
+
+    #[test]
+    fn limits_bytes_and_objects() {
+        let quota = Quota { max_bytes: Some(100), max_objects: Some(2) };
+
+        assert!(quota.allows(usage(0, 0), usage(100, 1)));
+        assert!(!quota.allows(usage(0, 0), usage(101, 1)));
+        assert!(!quota.allows(usage(10, 2), usage(20, 3)));
+    }
+
+    #[test]
+    fn allows_decreasing_usage() {
+        let quota = Quota { max_bytes: Some(100), max_objects: Some(1) };
+
+        assert!(quota.allows(usage(300, 5), usage(200, 4)));
+        assert!(quota.allows(usage(300, 5), usage(250, 5)));
+        assert!(!quota.allows(usage(300, 5), usage(301, 5)));
+    }
+
+    #[test]
+    fn tracks_replaced_objects() {
+        let used = usage(10, 1);
+
+        assert_eq!(used.apply(Some(5), Some(10)), usage(5, 1));
+        assert_eq!(used.apply(Some(5), None), usage(15, 2));
+        assert_eq!(used.apply(None, Some(20)), usage(0, 0));
+    }
+}
+
+#[cfg(test)]
+mod allowance_spec {
+    use std::io;
+
+    use futures::{executor::block_on, stream};
+
+    use super::Quota;
+    use crate::{
+        CreateFile, Exec as _, SetBucketConfig, Storage,
+        testing::{TempDir, create_file, path},
+    };
+
+    async fn limit_bytes(storage: &Storage, max: u64) {
+        storage
+            .exec(SetBucketConfig {
+                bucket: path("b"),
+                config: Some(Quota { max_bytes: Some(max), max_objects: None }),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn aborts_uploads_exceeding_quota() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            limit_bytes(&storage, 4).await;
+
+            let chunks = vec![
+                Ok(b"abc".to_vec()),
+                Ok(b"def".to_vec()),
+                Err(io::Error::other("Upload is read beyond quota")),
+            ];
+            let rejected = storage
+                .exec(CreateFile {
+                    path: path("b/file"),
+                    bytes: stream::iter(chunks),
+                    encryption: None,
+                    content_type: None,
+                })
+                .await;
+
+            assert_eq!(
+                rejected.unwrap_err().as_ref().kind(),
+                io::ErrorKind::QuotaExceeded,
+            );
+        });
+    }
+
+    #[test]
+    fn allows_replacing_files_in_full_bucket() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            storage.exec(create_file("b/file", b"abcd")).await.unwrap();
+            limit_bytes(&storage, 4).await;
+
+            let replaced = storage.exec(create_file("b/file", b"efgh")).await;
+
+            assert!(replaced.is_ok(), "Replacing file is rejected");
+        });
+    }
+}
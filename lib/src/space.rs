@@ -0,0 +1,149 @@
+//! Free space of the filesystem the data is stored on.
+//!
+//! Once the free space drops below the low watermark, the [`Storage`] becomes
+//! read-only, rejecting any new files to be written, and stays so until the
+//! free space rises above the high watermark. Deleting files is still allowed,
+//! so the space may be freed.
+
+use std::{io, sync::atomic::Ordering};
+
+use tracerr::Traced;
+
+use crate::{Exec, Storage, async_trait};
+
+/// Operation of getting the free space available for writing new files.
+///
+/// Also switches the [`Storage`] into the read-only mode (or back), if the
+/// free space has crossed its watermarks.
+#[derive(Clone, Copy, Debug)]
+pub struct GetFreeSpace;
+
+/// Free space of a [`Storage`], as returned by the [`GetFreeSpace`]
+/// operation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FreeSpace {
+    /// Number of bytes available for writing new files, excluding the reserve
+    /// the [`Storage`] keeps.
+    pub available: u64,
+
+    /// Indicator whether the [`Storage`] is read-only, because of low free
+    /// space.
+    pub read_only: bool,
+}
+
+#[async_trait]
+impl Exec<GetFreeSpace> for Storage {
+    type Ok = FreeSpace;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, _: GetFreeSpace) -> Result<Self::Ok, Self::Err> {
+        let stat = rustix::fs::statvfs(&self.data_dir)
+            .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+        let free = stat.f_bavail.saturating_mul(stat.f_frsize);
+
+        let read_only = if free < self.low_watermark {
+            true
+        } else if free > self.high_watermark {
+            false
+        } else {
+            self.read_only.load(Ordering::Acquire)
+        };
+        if self.read_only.swap(read_only, Ordering::AcqRel) != read_only {
+            if read_only {
+                tracing::warn!(free, "Storage became read-only");
+            } else {
+                tracing::info!(free, "Storage became writable");
+            }
+        }
+
+        Ok(FreeSpace {
+            available: free.saturating_sub(self.free_space_reserve),
+            read_only,
+        })
+    }
+}
+
+impl Storage {
+    /// Sets the number of bytes of free space to be kept in reserve, so not
+    /// considered available for new files.
+    ///
+    /// No space is reserved by default.
+    #[must_use]
+    pub const fn with_free_space_reserve(mut self, bytes: u64) -> Self {
+        self.free_space_reserve = bytes;
+        self
+    }
+
+    /// Sets the numbers of bytes of free space, below which (`low`) this
+    /// [`Storage`] becomes read-only, and above which (`high`) it becomes
+    /// writable again.
+    ///
+    /// Watermarks are checked on every [`GetFreeSpace`] operation, so it
+    /// should be executed periodically. By default, this [`Storage`] never
+    /// becomes read-only.
+    #[must_use]
+    pub const fn with_free_space_watermarks(
+        mut self,
+        low: u64,
+        high: u64,
+    ) -> Self {
+        self.low_watermark = low;
+        self.high_watermark = high;
+        self
+    }
+
+    /// Ensures this [`Storage`] is not read-only.
+    ///
+    /// # Errors
+    ///
+    /// With [`io::ErrorKind::ReadOnlyFilesystem`], if this [`Storage`] is
+    /// read-only because of low free space.
+    pub(crate) fn ensure_writable(&self) -> Result<(), Traced<io::Error>> {
+        if self.read_only.load(Ordering::Acquire) {
+            return Err(tracerr::new!(io::Error::new(
+                io::ErrorKind::ReadOnlyFilesystem,
+                "Storage is read-only, because of low free space",
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod watermarks_spec {
+    use std::io;
+
+    use futures::executor::block_on;
+
+    use crate::{
+        Exec as _, GetFreeSpace, Storage,
+        testing::{TempDir, create_file},
+    };
+
+    #[test]
+    fn switches_into_read_only_mode_and_back() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root)
+                .await
+                .unwrap()
+                .with_free_space_watermarks(u64::MAX, u64::MAX);
+            let full = storage.exec(GetFreeSpace).await.unwrap();
+            let rejected = storage.exec(create_file("b/file", b"data")).await;
+
+            let storage = storage.with_free_space_watermarks(0, 0);
+            let freed = storage.exec(GetFreeSpace).await.unwrap();
+            let accepted = storage.exec(create_file("b/file", b"data")).await;
+
+            assert!(full.read_only, "Storage is not read-only");
+            assert_eq!(
+                rejected.unwrap_err().as_ref().kind(),
+                io::ErrorKind::ReadOnlyFilesystem,
+            );
+            assert!(!freed.read_only, "Storage is still read-only");
+            assert!(accepted.is_ok(), "{accepted:?}");
+        });
+    }
+}
@@ -0,0 +1,59 @@
+//! Helpers shared by specs.
+
+use std::{
+    env, fs, io,
+    ops::Deref,
+    path::{Path, PathBuf},
+    vec,
+};
+
+use futures::stream;
+use uuid::Uuid;
+
+use crate::{CreateFile, RelativePath};
+
+/// Unique temporary directory, removed once dropped, even if a spec panics.
+#[derive(Debug)]
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates a new unique [`TempDir`].
+    pub(crate) fn new() -> Self {
+        let dir = env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Parses the provided string as a [`RelativePath`].
+pub(crate) fn path(s: &str) -> RelativePath {
+    RelativePath::try_from(s.to_owned()).unwrap()
+}
+
+/// Creates a new [`CreateFile`] operation of a file with the provided `bytes`
+/// at the provided `path`.
+pub(crate) fn create_file(
+    path: &str,
+    bytes: &[u8],
+) -> CreateFile<stream::Iter<vec::IntoIter<io::Result<Vec<u8>>>>> {
+    CreateFile {
+        path: self::path(path),
+        bytes: stream::iter(vec![Ok(bytes.to_vec())]),
+        encryption: None,
+        content_type: None,
+    }
+}
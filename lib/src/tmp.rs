@@ -0,0 +1,223 @@
+//! Temporary files of [`Storage`]s.
+//!
+//! Every [`Storage`] uses its own temporary directory inside the shared one,
+//! so multiple processes may use the same root directory (like during a
+//! rolling restart), without removing temporary files of each other. Stale
+//! temporary files (left by crashed or aborted operations) are removed by a
+//! [`CleanTmpFiles`] operation instead, along with the temporary directories
+//! of the [`Storage`]s not running anymore.
+
+use std::{
+    fs::Metadata,
+    io,
+    os::unix::fs::MetadataExt as _,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use futures::StreamExt as _;
+use tracerr::Traced;
+use uuid::Uuid;
+
+use crate::{
+    Exec, Storage, async_trait, remove_existing_dir, remove_existing_file,
+};
+
+/// Operation of removing stale temporary files of all the [`Storage`]s
+/// sharing the same root directory.
+///
+/// The temporary directory of a [`Storage`] is considered stale too, unless
+/// it executes this operation more often than the `max_age`, so it should be
+/// executed periodically by every running [`Storage`].
+///
+/// Returns the number of removed files.
+#[derive(Clone, Copy, Debug)]
+pub struct CleanTmpFiles {
+    /// Age of a temporary file, since its last change, after which it's
+    /// considered stale.
+    ///
+    /// Should exceed the duration of the slowest operation, as files being
+    /// written are not considered stale.
+    pub max_age: Duration,
+}
+
+#[async_trait]
+impl Exec<CleanTmpFiles> for Storage {
+    type Ok = usize;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: CleanTmpFiles) -> Result<Self::Ok, Self::Err> {
+        let Some(shared) = self.tmp_dir.parent() else {
+            return Ok(0);
+        };
+
+        // Changing the own temporary directory keeps it from being considered
+        // stale by other `Storage`s.
+        let heartbeat = self.tmp_dir.join(Uuid::new_v4().to_string());
+        async_fs::write(&heartbeat, b"").await.map_err(tracerr::wrap!())?;
+        remove_existing_file(&heartbeat).await.map_err(tracerr::wrap!())?;
+
+        let mut removed = 0;
+        for entry in read_dir(shared).await.map_err(tracerr::wrap!())? {
+            if entry != self.tmp_dir {
+                let Some(meta) = stale_metadata(&entry, op.max_age)
+                    .await
+                    .map_err(tracerr::wrap!())?
+                else {
+                    continue;
+                };
+                if !meta.is_dir() {
+                    removed += remove_stale(&entry, op.max_age).await;
+                    continue;
+                }
+            }
+
+            for file in read_dir(&entry).await.map_err(tracerr::wrap!())? {
+                removed += remove_stale(&file, op.max_age).await;
+            }
+            if entry != self.tmp_dir {
+                // Fails if there are fresh files left in the directory.
+                _ = async_fs::remove_dir(&entry).await;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Lists the paths of all the entries of the provided `dir`ectory.
+///
+/// Returns an empty list if the `dir`ectory doesn't exist.
+///
+/// # Errors
+///
+/// If the `dir`ectory fails to be read.
+async fn read_dir(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = match async_fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut paths = Vec::new();
+    while let Some(entry) = entries.next().await {
+        paths.push(entry?.path());
+    }
+    Ok(paths)
+}
+
+/// Removes the temporary file (or directory) at the provided `path`, if it
+/// hasn't been changed for longer than the provided `max_age`.
+///
+/// Returns the number of removed entries. Failures are logged and skipped, so
+/// a single unremovable entry doesn't block the cleanup of all the others.
+async fn remove_stale(path: &Path, max_age: Duration) -> usize {
+    let res = match stale_metadata(path, max_age).await {
+        Ok(Some(meta)) if meta.is_dir() => remove_existing_dir(path).await,
+        Ok(Some(_)) => remove_existing_file(path).await,
+        Ok(None) => return 0,
+        Err(e) => Err(e),
+    };
+    match res {
+        Ok(()) => 1,
+        Err(e) => {
+            tracing::warn!(
+                path = %path.display(),
+                "Failed to remove stale tmp file: {e}",
+            );
+            0
+        }
+    }
+}
+
+/// Returns the [`Metadata`] of the file at the provided `path`, if it hasn't
+/// been changed for longer than the provided `max_age`.
+///
+/// The status change time is used, as it's updated on both writing to the
+/// file and creating a new link to it.
+///
+/// # Errors
+///
+/// If the [`Metadata`] fails to be read.
+async fn stale_metadata(
+    path: &Path,
+    max_age: Duration,
+) -> io::Result<Option<Metadata>> {
+    let meta = match async_fs::symlink_metadata(path).await {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let changed = Duration::new(
+        meta.ctime().try_into().unwrap_or_default(),
+        meta.ctime_nsec().try_into().unwrap_or_default(),
+    );
+    Ok((now.saturating_sub(changed) > max_age).then_some(meta))
+}
+
+#[cfg(test)]
+mod clean_spec {
+    use std::time::Duration;
+
+    use futures::executor::block_on;
+
+    use crate::{CleanTmpFiles, Exec as _, Storage, testing::TempDir};
+
+    #[test]
+    fn keeps_temporary_files_of_other_storages() {
+        block_on(async {
+            let root = TempDir::new();
+            let first = Storage::new(&*root).await.unwrap();
+            async_fs::write(first.tmp_dir.join("upload"), b"data")
+                .await
+                .unwrap();
+            let second = Storage::new(&*root).await.unwrap();
+
+            let fresh = second
+                .exec(CleanTmpFiles { max_age: Duration::from_secs(60) })
+                .await;
+            let kept = async_fs::read(first.tmp_dir.join("upload")).await;
+            let stale =
+                second.exec(CleanTmpFiles { max_age: Duration::ZERO }).await;
+            let first_dir = async_fs::metadata(&first.tmp_dir).await;
+            let second_dir = async_fs::metadata(&second.tmp_dir).await;
+
+            assert_eq!(fresh.unwrap(), 0);
+            assert_eq!(kept.unwrap(), b"data");
+            assert_eq!(stale.unwrap(), 1);
+            assert!(first_dir.is_err(), "Stale directory is not removed");
+            assert!(second_dir.is_ok(), "Own directory is removed");
+        });
+    }
+
+    #[test]
+    fn removes_stale_staging_directories() {
+        block_on(async {
+            let root = TempDir::new();
+            let storage = Storage::new(&*root).await.unwrap();
+            let staged = storage.tmp_dir.join("staged");
+            async_fs::create_dir_all(staged.join("dir")).await.unwrap();
+            async_fs::unix::symlink("../file", staged.join("dir/link"))
+                .await
+                .unwrap();
+            async_fs::write(storage.tmp_dir.join("upload"), b"data")
+                .await
+                .unwrap();
+
+            let first =
+                storage.exec(CleanTmpFiles { max_age: Duration::ZERO }).await;
+            let second =
+                storage.exec(CleanTmpFiles { max_age: Duration::ZERO }).await;
+
+            assert_eq!(first.unwrap(), 2);
+            assert_eq!(second.unwrap(), 0);
+            assert!(
+                async_fs::metadata(&staged).await.is_err(),
+                "Stale directory is not removed",
+            );
+        });
+    }
+}
@@ -0,0 +1,950 @@
+//! Versioning of files.
+
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use derive_more::with_trait::{Display, Error};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tracerr::Traced;
+use uuid::Uuid;
+
+use crate::{
+    BucketConfig, Exec, GetBucketConfig, PreconditionError, RelativePath,
+    Storage, async_trait, confinement::normalize, list_files,
+    quota::stored_size, remove_existing_dir, remove_existing_file,
+    stored_content_size, write_atomically,
+};
+
+/// Versioning state of a bucket.
+///
+/// Buckets without any [`Versioning`] configured keep no versions at all: an
+/// overwritten or deleted file is gone forever.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Versioning {
+    /// Every overwrite or deletion of a file keeps its previous version.
+    Enabled,
+
+    /// Existing versions are kept, but new ones are not created: a file is
+    /// written with the [`VersionId::null()`], replacing the previous `null`
+    /// version (if any).
+    Suspended,
+}
+
+impl BucketConfig for Versioning {
+    const NAME: &'static str = "versioning";
+}
+
+/// Identifier of a file version.
+///
+/// # Format
+///
+/// Only ASCII alphanumeric characters, `-` and `_` are allowed, and the length
+/// is limited to 64 characters.
+#[derive(
+    Clone, Debug, Deserialize, Display, Eq, Hash, PartialEq, Serialize,
+)]
+#[serde(transparent)]
+pub struct VersionId(String);
+
+impl VersionId {
+    /// Returns the `null` [`VersionId`], identifying files written while
+    /// [`Versioning`] is not [enabled](Versioning::Enabled).
+    #[must_use]
+    pub fn null() -> Self {
+        Self("null".into())
+    }
+
+    /// Indicates whether this [`VersionId`] is the [`VersionId::null()`] one.
+    #[must_use]
+    pub fn is_null(&self) -> bool {
+        self.0 == "null"
+    }
+
+    /// Generates a new unique [`VersionId`].
+    #[must_use]
+    fn generate() -> Self {
+        Self(Uuid::new_v4().simple().to_string())
+    }
+}
+
+impl AsRef<str> for VersionId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for VersionId {
+    type Error = InvalidVersionIdError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if s.is_empty()
+            || s.len() > 64
+            || !s
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-_".contains(&b))
+        {
+            return Err(InvalidVersionIdError);
+        }
+        Ok(Self(s))
+    }
+}
+
+/// Error of parsing [`VersionId`] from a [`String`].
+#[derive(Debug, Display, Error)]
+#[display("Invalid `VersionId` format")]
+pub struct InvalidVersionIdError;
+
+/// Operation of a file deletion.
+///
+/// Files having symlinks pointing to them are refused to be deleted, if the
+/// [`Storage`] protects them.
+///
+/// # Idempotent
+///
+/// Succeeds if the file (or its version) doesn't exist already.
+#[derive(Clone, Debug)]
+pub struct DeleteFile {
+    /// [`RelativePath`] of the file to be deleted.
+    pub path: RelativePath,
+
+    /// [`VersionId`] of the file version to be deleted permanently.
+    ///
+    /// If [`None`], then the current version is deleted, leaving a delete
+    /// marker in place of it if the bucket has [`Versioning`] configured.
+    pub version_id: Option<VersionId>,
+
+    /// Indicator whether the file version retained in
+    /// [`RetentionMode::Governance`] is allowed to be deleted.
+    ///
+    /// [`RetentionMode::Governance`]: crate::RetentionMode::Governance
+    pub bypass_governance: bool,
+}
+
+/// Outcome of a [`DeleteFile`] operation.
+#[derive(Clone, Debug)]
+pub struct DeletedFile {
+    /// [`VersionId`] of the deleted version or of the created delete marker.
+    pub version_id: Option<VersionId>,
+
+    /// Indicator whether a delete marker has been created or deleted.
+    pub delete_marker: bool,
+}
+
+#[async_trait]
+impl Exec<DeleteFile> for Storage {
+    type Ok = DeletedFile;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: DeleteFile) -> Result<Self::Ok, Self::Err> {
+        let _key = self.lock_key(&op.path).await?;
+        self.delete_file(op).await
+    }
+}
+
+impl Storage {
+    /// Executes the provided [`DeleteFile`] operation, while the lock of its
+    /// key is already held by the caller.
+    ///
+    /// # Errors
+    ///
+    /// If the file (or its version) is not allowed or fails to be deleted.
+    pub(crate) async fn delete_file(
+        &self,
+        op: DeleteFile,
+    ) -> Result<DeletedFile, Traced<io::Error>> {
+        self.ensure_unaliased(&op.path).await?;
+        let path = self.resolve_alias(&op.path).await?;
+        let live = self.live_path(&path).await.map_err(tracerr::wrap!())?;
+        let before =
+            self.current_target(&path).await.map_err(tracerr::wrap!())?;
+
+        let Some(id) = op.version_id else {
+            let _links = self.lock_links(&path, before.as_ref(), None).await;
+            let Some(versioning) = self.versioning(&path).await? else {
+                let null = VersionId::null();
+                let removed =
+                    stored_size(&live).await.map_err(tracerr::wrap!())?;
+                if removed.is_some() {
+                    self.ensure_unlocked(&path, &null, op.bypass_governance)
+                        .await?;
+                }
+                self.ensure_unlinked(&path).await?;
+                let usage = self.reserve_usage(&path, None, removed).await?;
+                remove_existing_file(&live).await.map_err(tracerr::wrap!())?;
+                self.forget_corruption(&path).await?;
+                self.release_lock(&path, &null).await?;
+                self.store_usage(usage).await?;
+                self.reindex_symlink(&path, before, None).await?;
+                return Ok(DeletedFile {
+                    version_id: None,
+                    delete_marker: false,
+                });
+            };
+
+            let (dir, mut index) =
+                self.load_index(&path).await.map_err(tracerr::wrap!())?;
+            let id = versioning.next_id();
+            let removed = index.stored_size(&dir, &live, &id).await?;
+            if removed.is_some() {
+                self.ensure_unlocked(&path, &id, op.bypass_governance).await?;
+            }
+            self.ensure_unlinked(&path).await?;
+            let usage = self.reserve_usage(&path, None, removed).await?;
+            index.preserve_current(&dir, &live, &id).await?;
+            index.discard(&dir, &id).await?;
+            index.versions.push(Version {
+                id: id.clone(),
+                created_at: SystemTime::now(),
+                delete_marker: true,
+            });
+            remove_existing_file(&live).await.map_err(tracerr::wrap!())?;
+            self.forget_corruption(&path).await?;
+            self.write_index(&dir, &index).await?;
+            self.release_lock(&path, &id).await?;
+            self.store_usage(usage).await?;
+            self.reindex_symlink(&path, before, None).await?;
+
+            return Ok(DeletedFile {
+                version_id: Some(id),
+                delete_marker: true,
+            });
+        };
+
+        let (dir, mut index) =
+            self.load_index(&path).await.map_err(tracerr::wrap!())?;
+        let Some(pos) = index.versions.iter().position(|v| v.id == id) else {
+            return Ok(DeletedFile {
+                version_id: Some(id),
+                delete_marker: false,
+            });
+        };
+
+        let after = if pos + 1 == index.versions.len() {
+            // The previous version (if any) becomes the current.
+            match index.versions[..pos].last().filter(|v| !v.delete_marker) {
+                Some(prev) => {
+                    symlink_target(&dir.join(prev.id.as_ref()), &live)
+                        .await
+                        .map_err(tracerr::wrap!())?
+                        .and_then(|t| self.relative_path(&t))
+                }
+                None => None,
+            }
+        } else {
+            before.clone()
+        };
+        let _links =
+            self.lock_links(&path, before.as_ref(), after.as_ref()).await;
+
+        let freed = index.stored_size(&dir, &live, &id).await?;
+        if freed.is_some() {
+            self.ensure_unlocked(&path, &id, op.bypass_governance).await?;
+        }
+        if pos + 1 == index.versions.len() {
+            self.ensure_unlinked(&path).await?;
+        }
+        let usage = self.reserve_usage(&path, None, freed).await?;
+        let removed = index.versions.remove(pos);
+        if pos == index.versions.len() {
+            // The latest version is removed, so the previous one (if any)
+            // becomes the current.
+            remove_existing_file(&live).await.map_err(tracerr::wrap!())?;
+            self.forget_corruption(&path).await?;
+            if let Some(prev) =
+                index.versions.last().filter(|v| !v.delete_marker)
+            {
+                async_fs::rename(dir.join(prev.id.as_ref()), &live)
+                    .await
+                    .map_err(tracerr::wrap!())?;
+            }
+        } else {
+            remove_existing_file(dir.join(removed.id.as_ref()))
+                .await
+                .map_err(tracerr::wrap!())?;
+        }
+        self.write_index(&dir, &index).await?;
+        self.release_lock(&path, &removed.id).await?;
+        self.store_usage(usage).await?;
+        self.reindex_symlink(&path, before, after).await?;
+
+        Ok(DeletedFile {
+            version_id: Some(removed.id),
+            delete_marker: removed.delete_marker,
+        })
+    }
+}
+
+/// Operation of listing all the versions of files in a bucket.
+#[derive(Clone, Debug)]
+pub struct ListVersions {
+    /// [`RelativePath`] of the bucket.
+    pub bucket: RelativePath,
+
+    /// Prefix of the keys to list the versions of.
+    pub prefix: Option<String>,
+}
+
+/// Version of a file, as returned by the [`ListVersions`] operation.
+#[derive(Clone, Debug)]
+pub struct FileVersion {
+    /// Key of the file (its path relative to the bucket).
+    pub key: String,
+
+    /// [`VersionId`] of this [`FileVersion`].
+    pub id: VersionId,
+
+    /// Indicator whether this [`FileVersion`] is the current one.
+    pub is_latest: bool,
+
+    /// Indicator whether this [`FileVersion`] is a delete marker.
+    pub is_delete_marker: bool,
+
+    /// Time when this [`FileVersion`] was created.
+    pub last_modified: SystemTime,
+
+    /// Size of this [`FileVersion`] in bytes.
+    pub size: u64,
+
+    /// Indicator whether this [`FileVersion`] is listed through a directory
+    /// alias, so belongs to the aliased directory rather than to the listed
+    /// bucket.
+    pub aliased: bool,
+}
+
+#[async_trait]
+impl Exec<ListVersions> for Storage {
+    type Ok = Vec<FileVersion>;
+    type Err = Traced<io::Error>;
+
+    #[allow(clippy::blocks_in_conditions)] // due to `instrument` attribute
+    #[tracing::instrument(level = "debug", err(Debug))]
+    async fn exec(&self, op: ListVersions) -> Result<Self::Ok, Self::Err> {
+        let bucket_prefix = format!("{}/", op.bucket);
+        let prefix = op.prefix.unwrap_or_default();
+
+        let mut versions = Vec::new();
+        let mut indexed = HashSet::new();
+
+        let dirs = list_dirs(&self.meta_dir.join("versions").join(&op.bucket))
+            .await
+            .map_err(tracerr::wrap!())?;
+        for dir in dirs {
+            let Some(index) =
+                read_index(&dir).await.map_err(tracerr::wrap!())?
+            else {
+                continue;
+            };
+            let Some(key) = index.path.strip_prefix(&bucket_prefix) else {
+                continue;
+            };
+            _ = indexed.insert(key.to_owned());
+            if !key.starts_with(&prefix) {
+                continue;
+            }
+
+            let live = self
+                .resolve_beneath(&self.data_dir.join(&index.path), false)
+                .await
+                .map_err(tracerr::wrap!())?;
+            let latest = index.versions.len().saturating_sub(1);
+            for (n, v) in index.versions.iter().enumerate().rev() {
+                let size = if v.delete_marker {
+                    0
+                } else if n == latest {
+                    self.file_size(&live).await
+                } else {
+                    let path = dir.join(v.id.as_ref());
+                    match symlink_target(&path, &live).await {
+                        Ok(Some(target)) => self.file_size(&target).await,
+                        Ok(None) => self.file_size(&path).await,
+                        Err(_) => 0,
+                    }
+                };
+                versions.push(FileVersion {
+                    key: key.to_owned(),
+                    id: v.id.clone(),
+                    is_latest: n == latest,
+                    is_delete_marker: v.delete_marker,
+                    last_modified: v.created_at,
+                    size,
+                    aliased: false,
+                });
+            }
+        }
+
+        let bucket_dir = self
+            .resolve_beneath(&self.data_dir.join(&op.bucket), true)
+            .await
+            .map_err(tracerr::wrap!())?;
+        for key in list_files(&bucket_dir).await.map_err(tracerr::wrap!())? {
+            if indexed.contains(&key) {
+                continue;
+            }
+            let path = bucket_dir.join(&key);
+
+            // Files of aliased directories are listed as the current versions
+            // of the keys prefixed with the alias.
+            if let Some(files) =
+                self.list_aliased(&path).await.map_err(tracerr::wrap!())?
+            {
+                for file in files {
+                    let aliased = format!("{key}/{file}");
+                    if aliased.starts_with(&prefix) {
+                        versions.extend(
+                            self.current_version(
+                                aliased,
+                                &path.join(file),
+                                true,
+                            )
+                            .await?,
+                        );
+                    }
+                }
+                continue;
+            }
+
+            if key.starts_with(&prefix) {
+                versions.extend(self.current_version(key, &path, false).await?);
+            }
+        }
+
+        // Sorting is stable, so versions of the same key remain ordered from
+        // the latest to the oldest one.
+        versions.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(versions)
+    }
+}
+
+/// Index of all the versions of a single file.
+///
+/// Files of noncurrent versions are stored in the same directory as the
+/// [`Index`] itself, named by their [`VersionId`]s, while the current version
+/// is always the file in the [`Storage::data_dir`].
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct Index {
+    /// [`RelativePath`] of the versioned file.
+    pub(crate) path: String,
+
+    /// Versions of the file, from the oldest to the latest one.
+    pub(crate) versions: Vec<Version>,
+}
+
+/// Single version of a file in its [`Index`].
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct Version {
+    /// [`VersionId`] of this [`Version`].
+    pub(crate) id: VersionId,
+
+    /// Time when this [`Version`] was created.
+    pub(crate) created_at: SystemTime,
+
+    /// Indicator whether this [`Version`] is a delete marker.
+    pub(crate) delete_marker: bool,
+}
+
+impl Index {
+    /// Returns the size of the stored (not being a delete marker) version with
+    /// the provided [`VersionId`], if there is any.
+    ///
+    /// The latest version is stored at the provided `live` path, while the
+    /// noncurrent ones are stored in the provided `dir`ectory.
+    ///
+    /// # Errors
+    ///
+    /// If the metadata of the version file fails to be read.
+    async fn stored_size(
+        &self,
+        dir: &Path,
+        live: &Path,
+        id: &VersionId,
+    ) -> Result<Option<u64>, Traced<io::Error>> {
+        let latest = self.versions.len().saturating_sub(1);
+        let Some(pos) = self
+            .versions
+            .iter()
+            .position(|v| &v.id == id)
+            .filter(|&n| !self.versions[n].delete_marker)
+        else {
+            return Ok(None);
+        };
+        let path = if pos == latest { live } else { &dir.join(id.as_ref()) };
+        stored_size(path).await.map_err(tracerr::wrap!())
+    }
+
+    /// Keeps the current version of the file at the provided `live` path in
+    /// the provided `dir`ectory, unless it's going to be replaced by the
+    /// version with the provided [`VersionId`].
+    ///
+    /// # Errors
+    ///
+    /// If the current version fails to be hard-linked.
+    async fn preserve_current(
+        &self,
+        dir: &Path,
+        live: &Path,
+        next: &VersionId,
+    ) -> Result<(), Traced<io::Error>> {
+        let Some(current) = self.versions.last() else {
+            return Ok(());
+        };
+        if current.delete_marker || &current.id == next {
+            return Ok(());
+        }
+
+        async_fs::create_dir_all(dir).await.map_err(tracerr::wrap!())?;
+        // Hard link keeps the current version in place until it's replaced
+        // atomically.
+        match async_fs::hard_link(live, dir.join(current.id.as_ref())).await {
+            Ok(()) => Ok(()),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::AlreadyExists,
+                ) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(tracerr::new!(e)),
+        }
+    }
+
+    /// Discards the noncurrent version with the provided [`VersionId`] (if
+    /// any) from this [`Index`] and the provided `dir`ectory.
+    ///
+    /// # Errors
+    ///
+    /// If the file of the version fails to be removed.
+    async fn discard(
+        &mut self,
+        dir: &Path,
+        id: &VersionId,
+    ) -> Result<(), Traced<io::Error>> {
+        let Some(pos) = self.versions.iter().position(|v| &v.id == id) else {
+            return Ok(());
+        };
+        let removed = self.versions.remove(pos);
+        if pos < self.versions.len() {
+            remove_existing_file(dir.join(removed.id.as_ref()))
+                .await
+                .map_err(tracerr::wrap!())?;
+        }
+        Ok(())
+    }
+}
+
+impl Versioning {
+    /// Returns the [`VersionId`] of the next version, created according to
+    /// this [`Versioning`].
+    fn next_id(self) -> VersionId {
+        match self {
+            Self::Enabled => VersionId::generate(),
+            Self::Suspended => VersionId::null(),
+        }
+    }
+}
+
+impl Storage {
+    /// Places the provided `tmp` file to the provided `path`, keeping the
+    /// replaced version, if the bucket has [`Versioning`] configured.
+    ///
+    /// Returns the [`VersionId`] of the placed file, if the bucket has
+    /// [`Versioning`] configured.
+    ///
+    /// If `expected_src` is provided, the file is placed only if the existing
+    /// one at the `path` is a symlink pointing to it.
+    ///
+    /// The replaced target of a symlink is added to its history, unless this
+    /// is a `rollback` to the latest target in the history, which is removed
+    /// from there instead.
+    ///
+    /// # Errors
+    ///
+    /// - With [`io::ErrorKind::AlreadyExists`], if the existing file is not a
+    ///   symlink pointing to the `expected_src`, or the history of the symlink
+    ///   has changed before its `rollback`.
+    /// - With [`io::ErrorKind::PermissionDenied`], if the replaced version is
+    ///   locked.
+    /// - With [`io::ErrorKind::ResourceBusy`], if the replaced file has
+    ///   symlinks pointing to it, while the [`Storage`] protects them.
+    /// - With [`io::ErrorKind::QuotaExceeded`], if the [`Quota`] of the bucket
+    ///   doesn't allow placing the file.
+    /// - With [`io::ErrorKind::ReadOnlyFilesystem`], if the [`Storage`] is
+    ///   read-only because of low free space.
+    /// - If any of filesystem operations fails.
+    ///
+    /// [`Quota`]: crate::Quota
+    pub(crate) async fn commit(
+        &self,
+        tmp: &Path,
+        path: &RelativePath,
+        expected_src: Option<&RelativePath>,
+        rollback: bool,
+    ) -> Result<Option<VersionId>, Traced<io::Error>> {
+        self.ensure_writable()?;
+        self.ensure_unaliased(path).await?;
+        let path = &self.resolve_alias(path).await?;
+        let live = self.live_path(path).await.map_err(tracerr::wrap!())?;
+        if let Some(dir) = live.parent() {
+            async_fs::create_dir_all(dir).await.map_err(tracerr::wrap!())?;
+        }
+
+        let before =
+            self.current_target(path).await.map_err(tracerr::wrap!())?;
+        if let Some(expected) = expected_src {
+            if before.as_ref().map(AsRef::<Path>::as_ref)
+                != Some(expected.as_ref())
+            {
+                return Err(tracerr::new!(PreconditionError::io(format!(
+                    "Symlink `{path}` doesn't point to `{expected}` anymore",
+                ))));
+            }
+        }
+        let after = symlink_target(tmp, &live)
+            .await
+            .map_err(tracerr::wrap!())?
+            .and_then(|t| self.relative_path(&t));
+        let _links =
+            self.lock_links(path, before.as_ref(), after.as_ref()).await;
+        self.ensure_unlinked(path).await?;
+        if rollback {
+            self.ensure_rollback(path, after.as_ref()).await?;
+        }
+        let added = stored_size(tmp).await.map_err(tracerr::wrap!())?;
+
+        let Some(versioning) = self.versioning(path).await? else {
+            let null = VersionId::null();
+            let removed = stored_size(&live).await.map_err(tracerr::wrap!())?;
+            if removed.is_some() {
+                self.ensure_unlocked(path, &null, false).await?;
+            }
+            let usage = self.reserve_usage(path, added, removed).await?;
+            async_fs::rename(tmp, &live).await.map_err(tracerr::wrap!())?;
+            self.forget_corruption(path).await?;
+            self.lock_created(path, &null).await?;
+            self.store_usage(usage).await?;
+            self.update_history(
+                path,
+                before.as_ref(),
+                after.as_ref(),
+                rollback,
+            )
+            .await?;
+            self.reindex_symlink(path, before, after).await?;
+            return Ok(None);
+        };
+
+        let (dir, mut index) =
+            self.load_index(path).await.map_err(tracerr::wrap!())?;
+        let id = versioning.next_id();
+        let removed = index.stored_size(&dir, &live, &id).await?;
+        if removed.is_some() {
+            self.ensure_unlocked(path, &id, false).await?;
+        }
+        let usage = self.reserve_usage(path, added, removed).await?;
+        index.preserve_current(&dir, &live, &id).await?;
+        index.discard(&dir, &id).await?;
+        index.versions.push(Version {
+            id: id.clone(),
+            created_at: SystemTime::now(),
+            delete_marker: false,
+        });
+        async_fs::rename(tmp, &live).await.map_err(tracerr::wrap!())?;
+        self.forget_corruption(path).await?;
+        self.write_index(&dir, &index).await?;
+        self.lock_created(path, &id).await?;
+        self.store_usage(usage).await?;
+        self.update_history(path, before.as_ref(), after.as_ref(), rollback)
+            .await?;
+        self.reindex_symlink(path, before, after).await?;
+
+        Ok(Some(id))
+    }
+
+    /// Returns the current unversioned [`FileVersion`] of the file with the
+    /// provided `key`, stored at the provided `path` (and listed through a
+    /// directory alias, if `aliased`), if it still exists.
+    ///
+    /// # Errors
+    ///
+    /// If the metadata of the file fails to be read.
+    async fn current_version(
+        &self,
+        key: String,
+        path: &Path,
+        aliased: bool,
+    ) -> Result<Option<FileVersion>, Traced<io::Error>> {
+        let meta = match async_fs::symlink_metadata(path).await {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(tracerr::new!(e)),
+        };
+        Ok(Some(FileVersion {
+            key,
+            id: VersionId::null(),
+            is_latest: true,
+            is_delete_marker: false,
+            last_modified: self
+                .created_at(path, &meta)
+                .await
+                .map_err(tracerr::wrap!())?,
+            size: self.file_size(path).await,
+            aliased,
+        }))
+    }
+
+    /// Resolves the [`Path`] to the file of the provided version at the
+    /// provided `path`, along with its [`VersionId`].
+    ///
+    /// If no `version` is provided, resolves the current one.
+    ///
+    /// Returns [`None`] if there is no such version, or it's a delete marker.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Index`] of the file fails to be read.
+    pub(crate) async fn resolve_version(
+        &self,
+        path: &RelativePath,
+        version: Option<VersionId>,
+    ) -> io::Result<Option<(PathBuf, Option<VersionId>)>> {
+        let live = self.live_path(path).await?;
+        let dir = self.versions_dir(path);
+
+        Ok(match (version, read_index(&dir).await?) {
+            (None, None) => Some((live, None)),
+            (Some(id), None) => id.is_null().then_some((live, Some(id))),
+            (None, Some(index)) => index
+                .versions
+                .last()
+                .filter(|v| !v.delete_marker)
+                .map(|v| (live, Some(v.id.clone()))),
+            (Some(id), Some(index)) => {
+                let latest = index.versions.len().saturating_sub(1);
+                index
+                    .versions
+                    .iter()
+                    .position(|v| v.id == id)
+                    .filter(|&n| !index.versions[n].delete_marker)
+                    .map(|n| {
+                        if n == latest {
+                            (live, Some(id))
+                        } else {
+                            (dir.join(id.as_ref()), Some(id))
+                        }
+                    })
+            }
+        })
+    }
+
+    /// Returns the time when the version with the provided [`VersionId`] of
+    /// the file at the provided `path` was created, if it's indexed.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Index`] of the file fails to be read.
+    pub(crate) async fn version_created_at(
+        &self,
+        path: &RelativePath,
+        id: &VersionId,
+    ) -> io::Result<Option<SystemTime>> {
+        Ok(read_index(&self.versions_dir(path))
+            .await?
+            .and_then(|i| i.versions.into_iter().find(|v| &v.id == id))
+            .map(|v| v.created_at))
+    }
+
+    /// Returns the [`Versioning`] of the bucket of the provided `path`, if
+    /// any.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Versioning`] fails to be read.
+    pub(crate) async fn versioning(
+        &self,
+        path: &RelativePath,
+    ) -> Result<Option<Versioning>, Traced<io::Error>> {
+        self.exec(GetBucketConfig::new(path.bucket()))
+            .await
+            .map_err(tracerr::wrap!())
+    }
+
+    /// Returns the size of the contents of the file at the provided `path`,
+    /// following symlinks beneath the data directory.
+    ///
+    /// Returns `0` if the file doesn't exist or cannot be resolved.
+    async fn file_size(&self, path: &Path) -> u64 {
+        match self.resolve_beneath(path, true).await {
+            Ok(path) => stored_content_size(&path).await.unwrap_or_default(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Returns the [`PathBuf`] to the directory where the [`Index`] and the
+    /// noncurrent versions of the file at the provided `path` are stored.
+    fn versions_dir(&self, path: &RelativePath) -> PathBuf {
+        self.meta_dir
+            .join("versions")
+            .join(path.bucket())
+            .join(hex::encode(Sha256::digest(path.to_string())))
+    }
+
+    /// Loads the [`Index`] of the file at the provided `path`, along with the
+    /// directory it's stored in.
+    ///
+    /// If there is no [`Index`] yet, the existing file (if any) is considered
+    /// as the [`VersionId::null()`] version.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Index`] or the existing file fails to be read.
+    async fn load_index(
+        &self,
+        path: &RelativePath,
+    ) -> io::Result<(PathBuf, Index)> {
+        let dir = self.versions_dir(path);
+        if let Some(index) = read_index(&dir).await? {
+            return Ok((dir, index));
+        }
+
+        let mut index = Index { path: path.to_string(), versions: vec![] };
+        let live = self.live_path(path).await?;
+        match async_fs::symlink_metadata(&live).await {
+            Ok(meta) => index.versions.push(Version {
+                id: VersionId::null(),
+                created_at: self.created_at(&live, &meta).await?,
+                delete_marker: false,
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok((dir, index))
+    }
+
+    /// Persists the provided [`Index`] in the provided `dir`ectory.
+    ///
+    /// Removes the `dir`ectory completely, if the [`Index`] has no versions.
+    ///
+    /// # Errors
+    ///
+    /// If the [`Index`] fails to be written.
+    async fn write_index(
+        &self,
+        dir: &Path,
+        index: &Index,
+    ) -> Result<(), Traced<io::Error>> {
+        if index.versions.is_empty() {
+            return remove_existing_dir(dir).await.map_err(tracerr::wrap!());
+        }
+
+        let bytes = serde_json::to_vec_pretty(index)
+            .map_err(|e| tracerr::new!(io::Error::from(e)))?;
+        write_atomically(&self.tmp_dir, &dir.join(INDEX_FILE), &bytes)
+            .await
+            .map_err(tracerr::wrap!())
+    }
+}
+
+/// Name of the file storing an [`Index`].
+pub(crate) const INDEX_FILE: &str = "index.json";
+
+/// Reads the [`Index`] stored in the provided `dir`ectory, if any.
+///
+/// # Errors
+///
+/// If the [`Index`] fails to be read or deserialized.
+pub(crate) async fn read_index(dir: &Path) -> io::Result<Option<Index>> {
+    match async_fs::read(dir.join(INDEX_FILE)).await {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Lists all the subdirectories of the provided `dir`ectory.
+///
+/// Returns an empty list if the `dir`ectory doesn't exist.
+///
+/// # Errors
+///
+/// If the `dir`ectory fails to be read.
+pub(crate) async fn list_dirs(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    use futures::TryStreamExt as _;
+
+    let entries = match async_fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    entries
+        .try_filter_map(|e| async move {
+            Ok(e.file_type().await?.is_dir().then(|| e.path()))
+        })
+        .try_collect()
+        .await
+}
+
+/// Reads the target of the symlink stored at the provided `path` (if it's a
+/// symlink), with the provided `live` path being the one of its key (having
+/// its parent directories resolved already).
+///
+/// Symlinks have targets relative to the directory of their `live` path, so
+/// their noncurrent versions, stored apart from it, cannot be followed by the
+/// filesystem directly. The returned target is not followed any further.
+///
+/// Returns [`None`] if the `path` is not a symlink or doesn't exist.
+///
+/// # Errors
+///
+/// If the symlink fails to be read.
+pub(crate) async fn symlink_target(
+    path: &Path,
+    live: &Path,
+) -> io::Result<Option<PathBuf>> {
+    let target = match async_fs::read_link(path).await {
+        Ok(target) => target,
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::InvalidInput | io::ErrorKind::NotFound,
+            ) =>
+        {
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+
+    Ok(Some(normalize(&live.parent().unwrap_or(live).join(target))))
+}
+
+#[cfg(test)]
+mod version_id_spec {
+    use super::VersionId;
+
+    fn case(s: &str) -> bool {
+        VersionId::try_from(s.to_owned()).is_ok()
+    }
+
+    #[test]
+    fn accepts_generated_and_null() {
+        assert!(case(VersionId::generate().as_ref()));
+        assert!(case("null"));
+        assert!(VersionId::try_from("null".to_owned()).unwrap().is_null());
+    }
+
+    #[test]
+    fn rejects_non_filename_safe() {
+        assert!(!case(""));
+        assert!(!case("."));
+        assert!(!case(".."));
+        assert!(!case("a/b"));
+        assert!(!case("index.json"));
+        assert!(!case(&"a".repeat(65)));
+    }
+}
@@ -0,0 +1,35 @@
+//! Background sweeping of dangling symlinks.
+
+use std::time::Duration;
+
+use baza::{Exec as _, RelativePath, Storage, SweepDanglingSymlinks};
+
+/// Reports the dangling symlinks of the provided `bucket`, deleting the ones
+/// dangling for longer than the provided `grace_period` (if any).
+pub(crate) async fn sweep(
+    storage: Storage,
+    bucket: RelativePath,
+    grace_period: Option<Duration>,
+) {
+    let op = SweepDanglingSymlinks { bucket: bucket.clone(), grace_period };
+    let dangling = match storage.exec(op).await {
+        Ok(dangling) => dangling,
+        Err(e) => {
+            tracing::error!(
+                %bucket,
+                "Failed to sweep dangling symlinks: {e}: {}",
+                e.trace(),
+            );
+            return;
+        }
+    };
+
+    for link in dangling {
+        let target = link.target.map(|t| t.to_string());
+        if link.deleted {
+            tracing::info!(path = %link.path, ?target, "Deleted dangling");
+        } else {
+            tracing::warn!(path = %link.path, ?target, "Dangling symlink");
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Background garbage collection of deduplicated blobs.
+
+use baza::{CollectBlobs, Exec as _, Storage};
+
+/// Removes the blobs not referenced by any file anymore from the provided
+/// [`Storage`], freeing the space they occupy.
+pub(crate) async fn collect(storage: Storage) {
+    match storage.exec(CollectBlobs).await {
+        Ok(0) => {}
+        Ok(removed) => tracing::info!(removed, "Collected blobs"),
+        Err(e) => {
+            tracing::error!("Failed to collect blobs: {e}: {}", e.trace());
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Background expiration of files according to bucket [`Lifecycle`]s.
+
+use std::time::SystemTime;
+
+use baza::{
+    Exec as _, GetBucketConfig, Lifecycle, ListVersions, RelativePath, Storage,
+};
+
+/// Deletes the file versions of the provided `bucket` expired according to
+/// its [`Lifecycle`] (if any), one by one, so a failure to delete one of them
+/// doesn't keep the others.
+pub(crate) async fn expire(storage: Storage, bucket: RelativePath) {
+    let now = SystemTime::now();
+
+    let lifecycle = match storage
+        .exec(GetBucketConfig::<Lifecycle>::new(bucket.clone()))
+        .await
+    {
+        Ok(Some(lifecycle)) => lifecycle,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::error!(
+                %bucket,
+                "Failed to load lifecycle: {e}: {}",
+                e.trace(),
+            );
+            return;
+        }
+    };
+    let versions = match storage
+        .exec(ListVersions { bucket: bucket.clone(), prefix: None })
+        .await
+    {
+        Ok(versions) => versions,
+        Err(e) => {
+            tracing::error!(
+                %bucket,
+                "Failed to list versions: {e}: {}",
+                e.trace(),
+            );
+            return;
+        }
+    };
+
+    for op in lifecycle.expired(&bucket, &versions, now) {
+        let (path, version_id) = (op.path.clone(), op.version_id.clone());
+        match storage.exec(op).await {
+            Ok(_) => tracing::info!(%path, ?version_id, "Expired"),
+            Err(e) => tracing::error!(
+                %path,
+                ?version_id,
+                "Failed to expire: {e}: {}",
+                e.trace(),
+            ),
+        }
+    }
+}
@@ -1,6 +1,13 @@
-use std::path::PathBuf;
+mod dangling;
+mod dedup;
+mod lifecycle;
+mod scrub;
+mod space;
+mod tmp;
 
-use baza::Storage;
+use std::{path::PathBuf, time::Duration};
+
+use baza::{EncryptionKey, Exec as _, ListBuckets, RelativePath, Storage};
 use baza_api_s3 as s3;
 use secrecy::SecretString;
 use tracing::Level;
@@ -11,9 +18,76 @@ async fn main() -> Result<(), String> {
 
     tracing_subscriber::fmt().with_max_level(args.log_level).init();
 
-    let storage = Storage::new(args.root).await.map_err(|e| {
-        format!("Failed to initialize `Storage`: {e}: {}", e.trace())
-    })?;
+    if args.tmp_cleanup_interval >= args.tmp_max_age {
+        return Err(
+            "`--tmp-cleanup-interval` must be less than `--tmp-max-age`".into(),
+        );
+    }
+    if args.free_space_low_watermark > args.free_space_high_watermark {
+        return Err("`--free-space-low-watermark` must not exceed \
+                    `--free-space-high-watermark`"
+            .into());
+    }
+
+    let mut storage = Storage::new(args.root)
+        .await
+        .map_err(|e| {
+            format!("Failed to initialize `Storage`: {e}: {}", e.trace())
+        })?
+        .with_max_symlink_hops(args.max_symlink_hops)
+        .with_protected_symlink_targets(args.protect_symlink_targets)
+        .with_symlink_history_len(args.symlink_history_len)
+        .with_alias_writes(args.allow_alias_writes)
+        .with_deduplication(args.deduplicate)
+        .with_free_space_reserve(args.free_space_reserve)
+        .with_free_space_watermarks(
+            args.free_space_low_watermark,
+            args.free_space_high_watermark,
+        );
+    if let Some(key) = args.master_key {
+        storage = storage.with_master_key(key);
+    }
+
+    let max_age = Duration::from_secs(args.tmp_max_age);
+    spawn_periodic(
+        &storage,
+        Duration::from_secs(args.tmp_cleanup_interval),
+        move |s| tmp::clean(s, max_age),
+    );
+    spawn_periodic(
+        &storage,
+        Duration::from_secs(args.free_space_check_interval),
+        space::check,
+    );
+    spawn_per_bucket(
+        &storage,
+        Duration::from_secs(args.lifecycle_interval),
+        lifecycle::expire,
+    );
+    if let Some(interval) = args.dangling_symlinks_interval {
+        let grace_period =
+            args.dangling_symlinks_grace_period.map(Duration::from_secs);
+        spawn_per_bucket(
+            &storage,
+            Duration::from_secs(interval),
+            move |s, b| dangling::sweep(s, b, grace_period),
+        );
+    }
+    if let Some(interval) = args.scrub_interval {
+        let (rate, quarantine) = (args.scrub_rate, args.scrub_quarantine);
+        spawn_per_bucket(
+            &storage,
+            Duration::from_secs(interval),
+            move |s, b| scrub::scrub(s, b, rate, quarantine),
+        );
+    }
+    if args.deduplicate {
+        spawn_periodic(
+            &storage,
+            Duration::from_secs(args.blobs_gc_interval),
+            dedup::collect,
+        );
+    }
 
     s3::run_http_server(
         storage,
@@ -25,6 +99,56 @@ async fn main() -> Result<(), String> {
     .map_err(|e| format!("Failed to run S3 HTTP server: {e}"))
 }
 
+/// Spawns the provided `task` to be run on the provided [`Storage`] once per
+/// the provided `interval`, forever.
+///
+/// The `task` is expected to log its own failures, so it's simply run again
+/// on the next tick.
+fn spawn_periodic<F, Fut>(storage: &Storage, interval: Duration, task: F)
+where
+    F: Fn(Storage) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let storage = storage.clone();
+    drop(tokio::spawn(async move {
+        let mut timer = tokio::time::interval(interval);
+        loop {
+            _ = timer.tick().await;
+            task(storage.clone()).await;
+        }
+    }));
+}
+
+/// Spawns the provided `task` to be run on every bucket of the provided
+/// [`Storage`] once per the provided `interval`, forever.
+///
+/// Buckets are processed one by one, so a failing one doesn't prevent the
+/// others from being processed.
+fn spawn_per_bucket<F, Fut>(storage: &Storage, interval: Duration, task: F)
+where
+    F: Fn(Storage, RelativePath) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    spawn_periodic(storage, interval, move |storage| {
+        let task = task.clone();
+        async move {
+            let buckets = match storage.exec(ListBuckets).await {
+                Ok(buckets) => buckets,
+                Err(e) => {
+                    tracing::error!(
+                        "Failed to list buckets: {e}: {}",
+                        e.trace(),
+                    );
+                    return;
+                }
+            };
+            for bucket in buckets {
+                task(storage.clone(), bucket).await;
+            }
+        }
+    });
+}
+
 /// CLI options.
 #[derive(Debug, clap::Parser)]
 #[command(about)]
@@ -50,4 +174,115 @@ struct CliOpts {
     /// S3 API secret key.
     #[arg(long, env = "BAZA_SECRET_KEY", default_value = "baza")]
     secret_key: SecretString,
+
+    /// Interval (in seconds) between cleanups of stale temporary files.
+    #[arg(long, default_value_t = 600, value_parser = positive())]
+    tmp_cleanup_interval: u64,
+
+    /// Age (in seconds) of a temporary file since its last change, after
+    /// which it's considered stale, being left by a crashed or aborted
+    /// operation.
+    ///
+    /// Must exceed the `--tmp-cleanup-interval`, as temporary directories of
+    /// other running instances sharing the same root directory are kept alive
+    /// by their cleanups.
+    #[arg(long, default_value_t = 3600)]
+    tmp_max_age: u64,
+
+    /// Interval (in seconds) between applying lifecycle rules of buckets.
+    #[arg(long, default_value_t = 3600, value_parser = positive())]
+    lifecycle_interval: u64,
+
+    /// Interval (in seconds) between sweeps of dangling symlinks.
+    ///
+    /// If not specified, then dangling symlinks are not swept.
+    #[arg(long, value_parser = positive())]
+    dangling_symlinks_interval: Option<u64>,
+
+    /// Period (in seconds) a symlink should stay dangling for, before being
+    /// deleted by a sweep.
+    ///
+    /// If not specified, then dangling symlinks are only reported.
+    #[arg(long)]
+    dangling_symlinks_grace_period: Option<u64>,
+
+    /// Interval (in seconds) between scrubs of stored objects, verifying their
+    /// contents against the checksums persisted on their creation.
+    ///
+    /// If not specified, then objects are not scrubbed.
+    #[arg(long, value_parser = positive())]
+    scrub_interval: Option<u64>,
+
+    /// Maximum number of objects scrubbed per second.
+    #[arg(long, default_value_t = 100)]
+    scrub_rate: usize,
+
+    /// Quarantine corrupt objects detected by scrubs, so they cannot be read
+    /// until being overwritten or deleted.
+    #[arg(long)]
+    scrub_quarantine: bool,
+
+    /// Hex-encoded 256-bit master key to encrypt files with, when server-side
+    /// encryption is requested.
+    ///
+    /// If not specified, then server-side encryption is not available.
+    #[arg(long, env = "BAZA_MASTER_KEY", hide_env_values = true)]
+    master_key: Option<EncryptionKey>,
+
+    /// Maximum number of symlinks a new symlink may be resolved through
+    /// (including itself), before being rejected as a cycle.
+    #[arg(long, default_value_t = Storage::DEFAULT_MAX_SYMLINK_HOPS)]
+    max_symlink_hops: usize,
+
+    /// Refuse to overwrite or delete objects having symlinks pointing to them.
+    #[arg(long)]
+    protect_symlink_targets: bool,
+
+    /// Maximum number of previous targets kept in the history of every
+    /// symlink, allowing to roll it back (`0` disables the history).
+    #[arg(long, default_value_t = Storage::DEFAULT_SYMLINK_HISTORY_LEN)]
+    symlink_history_len: usize,
+
+    /// Allow writing objects through directory aliases, placing them into the
+    /// aliased directories.
+    #[arg(long)]
+    allow_alias_writes: bool,
+
+    /// Store identical contents of objects only once, making objects
+    /// references to them.
+    #[arg(long)]
+    deduplicate: bool,
+
+    /// Interval (in seconds) between garbage collections of deduplicated
+    /// contents not referenced by any object anymore.
+    #[arg(long, default_value_t = 3600, value_parser = positive())]
+    blobs_gc_interval: u64,
+
+    /// Number of bytes of free space kept in reserve, so uploads exceeding
+    /// the rest of free space are rejected upfront.
+    #[arg(long, default_value_t = 0)]
+    free_space_reserve: u64,
+
+    /// Number of bytes of free space, below which the storage becomes
+    /// read-only, rejecting any uploads.
+    #[arg(long, default_value_t = 0)]
+    free_space_low_watermark: u64,
+
+    /// Number of bytes of free space, above which the read-only storage
+    /// becomes writable again.
+    ///
+    /// Must not be less than the `--free-space-low-watermark`.
+    #[arg(long, default_value_t = 0)]
+    free_space_high_watermark: u64,
+
+    /// Interval (in seconds) between checks of free space against its
+    /// watermarks.
+    #[arg(long, default_value_t = 10, value_parser = positive())]
+    free_space_check_interval: u64,
+}
+
+/// Returns a [`clap`] parser of positive numbers (like intervals, which cannot
+/// be zero).
+fn positive() -> clap::builder::RangedU64ValueParser<u64> {
+    clap::value_parser!(u64).range(1..)
 }
@@ -0,0 +1,57 @@
+//! Background scrubbing of stored files, detecting their silent corruption.
+
+use std::time::Duration;
+
+use baza::{Exec as _, ListFiles, RelativePath, ScrubFiles, Storage};
+
+/// Verifies the files of the provided `bucket` against their checksums at the
+/// provided `rate` per second, quarantining the corrupt ones, if `quarantine`
+/// is `true`.
+///
+/// The `bucket` is listed once, and its files are scrubbed in batches of the
+/// `rate` size, one batch per second.
+pub(crate) async fn scrub(
+    storage: Storage,
+    bucket: RelativePath,
+    rate: usize,
+    quarantine: bool,
+) {
+    let keys = match storage.exec(ListFiles { bucket: bucket.clone() }).await {
+        Ok(keys) => keys,
+        Err(e) => {
+            tracing::error!(%bucket, "Failed to list files: {e}: {}", e.trace());
+            return;
+        }
+    };
+
+    let mut ticks = tokio::time::interval(Duration::from_secs(1));
+    for batch in keys.chunks(rate.max(1)) {
+        _ = ticks.tick().await;
+        let op = ScrubFiles {
+            bucket: bucket.clone(),
+            keys: batch.to_vec(),
+            quarantine,
+        };
+        let scrubbed = match storage.exec(op).await {
+            Ok(scrubbed) => scrubbed,
+            Err(e) => {
+                tracing::error!(
+                    %bucket,
+                    "Failed to scrub files: {e}: {}",
+                    e.trace(),
+                );
+                return;
+            }
+        };
+
+        for file in scrubbed.corrupt {
+            tracing::error!(
+                path = %file.path,
+                expected = file.expected,
+                actual = file.actual,
+                quarantined = file.quarantined,
+                "Corrupt file",
+            );
+        }
+    }
+}
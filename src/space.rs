@@ -0,0 +1,11 @@
+//! Background checks of free space.
+
+use baza::{Exec as _, GetFreeSpace, Storage};
+
+/// Checks the free space of the provided [`Storage`] against its watermarks,
+/// so it becomes read-only (or writable again) once they're crossed.
+pub(crate) async fn check(storage: Storage) {
+    if let Err(e) = storage.exec(GetFreeSpace).await {
+        tracing::error!("Failed to check free space: {e}: {}", e.trace());
+    }
+}
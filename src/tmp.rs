@@ -0,0 +1,21 @@
+//! Background cleanup of stale temporary files.
+
+use std::time::Duration;
+
+use baza::{CleanTmpFiles, Exec as _, Storage};
+
+/// Removes the temporary files not changed for longer than the provided
+/// `max_age` (along with the temporary directories of stopped instances) from
+/// the provided [`Storage`], keeping its own temporary directory alive.
+pub(crate) async fn clean(storage: Storage, max_age: Duration) {
+    match storage.exec(CleanTmpFiles { max_age }).await {
+        Ok(0) => {}
+        Ok(removed) => tracing::info!(removed, "Removed stale tmp files"),
+        Err(e) => {
+            tracing::error!(
+                "Failed to remove stale tmp files: {e}: {}",
+                e.trace(),
+            );
+        }
+    }
+}